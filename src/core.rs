@@ -1,17 +1,93 @@
-use crate::typemap::TypeMap;
-use crate::{debug, info};
+use crate::text::{render_glyph, render_text};
+use crate::typemap::{KeyedTypeMap, TypeMap};
+use crate::{debug, error, info, warn};
 
-use image::DynamicImage;
-use std::collections::HashMap;
-use std::convert::{From, Into};
+use hidapi::HidApi;
+use image::codecs::gif::GifDecoder;
+use image::imageops::FilterType;
+use image::AnimationDecoder;
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage, RgbaImage};
+use std::any::{Any, TypeId};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::convert::{From, Into, TryFrom};
 use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::future::{self, Future};
+use std::hash::{Hash, Hasher};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process;
 use std::result;
-use std::sync::{mpsc, Arc, RwLock};
-use std::thread;
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use streamdeck::{pids, Kind};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::MissedTickBehavior;
+use tokio::{task, time};
 
-const POLLING_RATE: Duration = Duration::from_millis(125);
+/// The USB vendor id of Elgato.
+pub const ELGATO_VID: u16 = 0x0fd9;
+
+/// The `HidApi` all devices are opened with. hidapi allows only a single
+/// `HidApi` at a time, so creating one per device fails for the second
+/// device.
+static HID_API: Shared<HidApi> = Shared::new();
+
+/// The USB product id of the Stream Deck Pedal. The `streamdeck` crate
+/// doesn't support the pedal, so it is driven through hidapi directly.
+pub const PEDAL_PID: u16 = 0x0086;
+
+/// The length of the input reports of the pedal: a 4 byte header followed by
+/// the states of the three keys.
+const PEDAL_REPORT_LEN: usize = 7;
+
+/// The default maximum time the worker thread blocks waiting for button
+/// input before handling queued messages again.
+const READ_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// The delay before the first connection retry.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The maximum delay between two connection attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// The minimum delay between two frames of a GIF. Many GIFs specify no delay
+/// at all.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+
+/// The interval in which the watchdog checks the worker threads.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The time after which a worker thread without a heartbeat is considered
+/// hung, in addition to the interval of the [`ReadStrategy`].
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The minimum time a key has to be held down to count as a long press.
+pub const LONG_PRESS: Duration = Duration::from_millis(750);
+
+/// How long a key stays dimmed after a press when [`State::dim_on_press`] is
+/// enabled.
+const PRESS_FEEDBACK: Duration = Duration::from_millis(80);
+
+/// The time of one fade in and out of [`StreamDeck::pulse_border`].
+const BORDER_PULSE_PERIOD: Duration = Duration::from_secs(2);
+
+/// The number of colors shown during one period of a border pulse.
+const BORDER_PULSE_STEPS: u32 = 20;
+
+/// The number of resized images remembered by [`StreamDeck::fit_image`].
+const RESIZE_CACHE_SIZE: usize = 64;
+
+/// The number of icons and GIFs remembered by the [`IconCache`].
+const ICON_CACHE_SIZE: usize = 64;
 
 pub type Result<T> = result::Result<T, Error>;
 
@@ -35,258 +111,4006 @@ where
 pub struct State {
     pub buttons: Arc<RwLock<HashMap<u8, ButtonWrapper>>>,
     pub typemap: Arc<RwLock<TypeMap>>,
+    /// Per-button state keyed by the key index. Every deck has its own
+    /// store.
+    pub keyed: Arc<RwLock<KeyedTypeMap>>,
+    /// The brightness of the deck in percent.
+    pub brightness: u8,
+    /// Dim the deck after a period without button presses. Disabled when
+    /// `None`.
+    pub idle: Option<Idle>,
+    /// Retry connecting to the deck if it is not available yet.
+    pub retry: Retry,
+    /// How the worker thread reads button input from the deck.
+    pub read_strategy: ReadStrategy,
+    /// The interval in which [`Button::on_tick`] is called. Disabled when
+    /// `None`.
+    pub tick: Option<Duration>,
+    /// Briefly darken a key when it is pressed to acknowledge the press.
+    pub dim_on_press: bool,
+    /// A sound file played using `paplay` when a key is pressed. Disabled
+    /// when `None`.
+    pub click_sound: Option<PathBuf>,
+    /// The address the HTTP control API listens on. Disabled when `None`.
+    #[cfg(feature = "http")]
+    pub http_addr: Option<std::net::SocketAddr>,
+    /// Rebuilds the buttons of every deck when the process receives
+    /// `SIGHUP`. Disabled when `None`.
+    pub reload: Option<LoadButtons>,
+    /// The decoded icons shared by all decks, see [`State::get_icon`].
+    pub icons: IconCache,
 }
 
+/// Builds the buttons for the deck with the serial and model. Used to reload
+/// the buttons, see [`State::reload`].
+pub type LoadButtons = Arc<dyn Fn(&str, Model) -> Result<HashMap<u8, ButtonWrapper>> + Send + Sync>;
+
 impl State {
     pub fn new() -> Self {
         Self {
             buttons: Arc::new(RwLock::new(HashMap::new())),
             typemap: Arc::new(RwLock::new(TypeMap::new())),
+            keyed: Arc::new(RwLock::new(KeyedTypeMap::new())),
+            brightness: 100,
+            idle: None,
+            retry: Retry::default(),
+            read_strategy: ReadStrategy::default(),
+            tick: Some(Duration::from_secs(1)),
+            dim_on_press: false,
+            click_sound: None,
+            #[cfg(feature = "http")]
+            http_addr: None,
+            reload: None,
+            icons: IconCache::default(),
         }
     }
-}
 
-pub struct ButtonWrapper {
-    button: Box<dyn Button>,
-}
+    /// Calls `f` with the shared typemap, holding the write lock only for
+    /// the duration of `f`.
+    pub fn with_typemap<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut TypeMap) -> R,
+    {
+        f(&mut self.typemap.write().unwrap())
+    }
 
-impl ButtonWrapper {
-    pub fn new(button: Box<dyn Button>) -> Self {
-        Self { button }
+    /// Removes the value of the type `T` from the shared typemap. Returns
+    /// `true` if a value was removed.
+    pub fn remove<T>(&self) -> bool
+    where
+        T: Any,
+    {
+        self.take::<T>().is_some()
     }
 
-    /// Call the `init` method of the button.
-    async fn exec_init(
-        &mut self,
-        key: u8,
-        streamdeck: StreamDeck,
-        state: &mut State,
-    ) -> Result<()> {
-        self.button.init(state, Key::new(key, streamdeck)).await
+    /// Removes and returns the value of the type `T` from the shared
+    /// typemap.
+    pub fn take<T>(&self) -> Option<T>
+    where
+        T: Any,
+    {
+        self.with_typemap(|typemap| typemap.remove::<T>())
     }
 
-    /// Call the `on_click` method of the button.
-    async fn exec_click(
-        &mut self,
-        key: u8,
-        streamdeck: StreamDeck,
-        state: &mut State,
-    ) -> Result<()> {
-        self.button.on_click(state, Key::new(key, streamdeck)).await
+    /// Registers the service `T`. Registered services are started by the
+    /// main loop before the buttons are initialized. Registering the same
+    /// service multiple times has no effect.
+    pub fn register_service<T>(&self)
+    where
+        T: SharedService,
+    {
+        self.with_typemap(|typemap| {
+            if !typemap.contains_key::<Services>() {
+                typemap.insert(Services::default());
+            }
+
+            let services = typemap.get_mut::<Services>().unwrap();
+            if services
+                .0
+                .iter()
+                .all(|service| service.id != TypeId::of::<T>())
+            {
+                services.0.push(ServiceEntry {
+                    id: TypeId::of::<T>(),
+                    name: std::any::type_name::<T>(),
+                    start: start_service::<T>,
+                    stop: stop_service::<T>,
+                });
+            }
+        });
+    }
+
+    /// Stores `value` for the button at `key`, replacing any previous value
+    /// of the same type.
+    pub fn insert_keyed<T>(&self, key: u8, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.keyed.write().unwrap().insert(key, value);
+    }
+
+    /// Returns a clone of the value of the type `T` stored for the button at
+    /// `key`.
+    pub fn get_keyed<T>(&self, key: u8) -> Option<T>
+    where
+        T: Any + Clone,
+    {
+        self.keyed.read().unwrap().get::<T>(key).cloned()
+    }
+
+    /// Removes and returns the value of the type `T` stored for the button
+    /// at `key`.
+    pub fn remove_keyed<T>(&self, key: u8) -> Option<T>
+    where
+        T: Any,
+    {
+        self.keyed.write().unwrap().remove::<T>(key)
+    }
+
+    /// Returns the image at `path` scaled to `width`x`height` like in
+    /// [`StreamDeck::fit_image`]. The decoded and scaled images are cached,
+    /// so repeatedly loading the same icon only decodes it once. The cache
+    /// is cleared when the buttons are reloaded, see [`State::reload`].
+    pub fn get_icon<P>(&self, path: P, size: (u32, u32)) -> Result<DynamicImage>
+    where
+        P: AsRef<Path>,
+    {
+        self.icons.image(path.as_ref(), size)
     }
 }
 
-pub async fn main_loop(vid: u16, pid: u16, serial: Option<String>, mut state: State) -> ! {
-    let deck = match StreamDeck::connect(vid, pid, serial) {
-        Ok(deck) => deck,
-        Err(err) => {
-            println!("[FATAL] Failed to connect to Streamdeck: {:?}", err);
-            process::exit(1);
-        }
-    };
+/// The icons and GIF frames loaded from files, keyed by their path and size.
+/// Shared by the [`State`] and the decks, see [`Key::image_from_path`] and
+/// [`Key::animate_from_gif`]. The cache is cleared once it holds
+/// `ICON_CACHE_SIZE` entries.
+#[derive(Clone, Debug, Default)]
+pub struct IconCache {
+    images: Arc<Mutex<HashMap<IconId, DynamicImage>>>,
+    gifs: Arc<Mutex<HashMap<IconId, Frames>>>,
+}
+
+/// The path and size of an icon in the [`IconCache`].
+type IconId = (PathBuf, u32, u32);
 
-    info!("Connected to streamdeck (VID = {}, PID = {})", vid, pid);
+/// The frames of a GIF with the delay after every frame.
+type Frames = Vec<(DynamicImage, Duration)>;
 
-    // Call the `init` method on every button.
+impl IconCache {
+    /// Returns the image at `path` scaled to `width`x`height`, decoding it
+    /// only if it is not cached yet.
+    fn image(&self, path: &Path, (width, height): (u32, u32)) -> Result<DynamicImage> {
+        let id = (path.to_owned(), width, height);
+        if let Some(icon) = self.images.lock().unwrap().get(&id) {
+            return Ok(icon.clone());
+        }
 
-    let buttons = state.buttons.clone();
-    for (key, button) in buttons.write().unwrap().iter_mut() {
-        debug!("Initializing key {}", key);
+        // Decode outside of the lock.
+        let icon = image::open(path)?.resize_to_fill(width, height, FilterType::Lanczos3);
 
-        match button.exec_init(*key, deck.clone(), &mut state).await {
-            Ok(()) => (),
-            Err(err) => println!("[ERROR] Failed to initialize key {}: {:?}", key, err),
+        let mut images = self.images.lock().unwrap();
+        if images.len() >= ICON_CACHE_SIZE {
+            images.clear();
         }
+        images.insert(id, icon.clone());
+
+        Ok(icon)
     }
 
-    loop {
-        // Wait for a button to be pressed (or released).
-        let (tx, rx) = mpsc::channel();
+    /// Returns the frames of the GIF at `path` scaled to `width`x`height`
+    /// with their delays, decoding it only if it is not cached yet.
+    fn gif(&self, path: &Path, (width, height): (u32, u32)) -> Result<Frames> {
+        let id = (path.to_owned(), width, height);
+        if let Some(frames) = self.gifs.lock().unwrap().get(&id) {
+            return Ok(frames.clone());
+        }
 
-        deck.send(Message::ReadButtons(tx)).unwrap();
-        let keys = match rx.recv().unwrap() {
-            Some(keys) => keys,
-            None => {
-                thread::sleep(POLLING_RATE);
-                continue;
-            }
-        };
+        let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
 
-        // Find the pressed button.
-        let key = match keys.iter().enumerate().find(|&(_, &x)| x == 1) {
-            Some((i, _)) => i as u8,
-            None => continue,
-        };
+        let mut frames = Vec::new();
+        for frame in decoder.into_frames() {
+            let frame = frame?;
+            let delay = Duration::from(frame.delay()).max(MIN_FRAME_DELAY);
 
-        #[cfg(debug_assertions)]
-        debug!("Key {} (ROW {} COL {}) pressed", key, key / 8, key % 8);
+            let image = DynamicImage::ImageRgba8(frame.into_buffer());
+            frames.push((
+                image.resize_to_fill(width, height, FilterType::Lanczos3),
+                delay,
+            ));
+        }
 
-        // Execute the buttons job.
-        {
-            let buttons = state.buttons.clone();
-            let mut buttons = buttons.write().unwrap();
-            match buttons.get_mut(&key) {
-                Some(button) => match button.exec_click(key, deck.clone(), &mut state).await {
-                    Ok(()) => (),
-                    Err(err) => println!("[ERROR] Error executing job for key {}: {:?}", key, err),
-                },
-                None => (),
-            }
+        let mut gifs = self.gifs.lock().unwrap();
+        if gifs.len() >= ICON_CACHE_SIZE {
+            gifs.clear();
         }
+        gifs.insert(id, frames.clone());
+
+        Ok(frames)
+    }
+
+    /// Removes all cached icons so they are loaded from the files again.
+    pub fn clear(&self) {
+        self.images.lock().unwrap().clear();
+        self.gifs.lock().unwrap().clear();
     }
 }
 
-enum Message {
-    SetColor(u8, Color),
-    SetImage(u8, DynamicImage),
-    ReadButtons(mpsc::Sender<Option<Vec<u8>>>),
+/// Configuration of the idle mode.
+#[derive(Clone, Debug)]
+pub struct Idle {
+    /// The time without button presses after which the deck is dimmed.
+    pub timeout: Duration,
+    /// The brightness of the deck in percent while idle.
+    pub brightness: u8,
+    /// Whether the press waking the deck up is swallowed instead of being
+    /// dispatched to the pressed button.
+    pub swallow_wake: bool,
+    /// An image spread over all keys while idle, like
+    /// [`StreamDeck::set_full_image`]. The keys show their current images
+    /// again when the deck wakes up. Set `brightness` to 100 to only show the
+    /// image without dimming the deck.
+    pub standby: Option<DynamicImage>,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            brightness: 10,
+            swallow_wake: true,
+            standby: None,
+        }
+    }
 }
 
+/// Configuration of the connection retries. The delay between attempts
+/// doubles after every failed attempt, up to [`MAX_RETRY_DELAY`].
 #[derive(Clone, Debug)]
-pub struct StreamDeck {
-    tx: mpsc::Sender<Message>,
+pub struct Retry {
+    /// The maximum number of connection attempts.
+    pub max_attempts: u32,
+    /// The maximum total time spent retrying.
+    pub timeout: Duration,
 }
 
-impl StreamDeck {
-    pub fn connect(vid: u16, pid: u16, serial: Option<String>) -> Result<Self> {
-        let (tx, rx) = mpsc::channel();
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 20,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
 
-        let mut deck = streamdeck::StreamDeck::connect(vid, pid, serial)?;
+/// A long-lived connection shared between buttons, e.g. the websocket
+/// connection to OBS. Register a service using [`State::register_service`].
+#[async_trait::async_trait]
+pub trait SharedService: Send + Sync + 'static {
+    /// Start the service. Called once before the buttons are initialized,
+    /// usually inserting a client into the typemap.
+    async fn start(state: &mut State) -> Result<()>;
 
-        deck.set_blocking(false)?;
+    /// Stop the service and close its connection. Called when the main loop
+    /// shuts down.
+    async fn stop(state: &mut State) -> Result<()>;
+}
 
-        std::thread::spawn(move || {
-            while let Ok(msg) = rx.recv() {
-                match msg {
-                    Message::SetColor(key, color) => {
-                        deck.set_button_rgb(key, &color.into()).unwrap()
-                    }
-                    Message::SetImage(key, image) => deck.set_button_image(key, image).unwrap(),
-                    Message::ReadButtons(tx) => {
-                        let keys = match deck.read_buttons(None) {
-                            Ok(keys) => Some(keys),
-                            Err(err) => match err {
-                                streamdeck::Error::NoData => None,
-                                _ => panic!("{:?}", err),
-                            },
-                        };
-
-                        let _ = tx.send(keys);
-                    }
-                }
-            }
-        });
+type ServiceFn = for<'a> fn(&'a mut State) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+fn start_service<T>(state: &mut State) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>
+where
+    T: SharedService,
+{
+    T::start(state)
+}
 
-        Ok(Self { tx })
+fn stop_service<T>(state: &mut State) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>
+where
+    T: SharedService,
+{
+    T::stop(state)
+}
+
+#[derive(Clone)]
+struct ServiceEntry {
+    id: TypeId,
+    name: &'static str,
+    start: ServiceFn,
+    stop: ServiceFn,
+}
+
+/// The services registered in the typemap.
+#[derive(Clone, Default)]
+struct Services(Vec<ServiceEntry>);
+
+impl Services {
+    /// Returns the services registered in the typemap of `state`.
+    fn get(state: &State) -> Self {
+        state.with_typemap(|typemap| typemap.get::<Self>().cloned().unwrap_or_default())
     }
 
-    fn send(&self, msg: Message) -> Result<()> {
-        let _ = self.tx.send(msg);
-        Ok(())
+    async fn start(&self, state: &mut State) {
+        for service in &self.0 {
+            debug!("Starting service {}", service.name);
+
+            if let Err(err) = (service.start)(state).await {
+                error!("Failed to start service {}: {:?}", service.name, err);
+            }
+        }
+    }
+
+    /// Stops the services in the reverse order they were started in.
+    async fn stop(&self, state: &mut State) {
+        for service in self.0.iter().rev() {
+            debug!("Stopping service {}", service.name);
+
+            if let Err(err) = (service.stop)(state).await {
+                error!("Failed to stop service {}: {:?}", service.name, err);
+            }
+        }
     }
 }
 
-#[async_trait::async_trait]
-pub trait Button: Send + Sync {
-    async fn init(&mut self, state: &mut State, key: Key) -> Result<()>;
-    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()>;
+/// How the worker thread reads button input from the deck. The worker
+/// handles queued messages between reads, so the duration bounds the latency
+/// of writes to the deck.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadStrategy {
+    /// Block until the deck reports input or the timeout elapses. Reacts to
+    /// presses immediately without spinning.
+    Blocking(Duration),
+    /// Check for input without blocking and sleep for the interval if there
+    /// is none. Presses are noticed up to the interval late.
+    Poll(Duration),
 }
 
-#[derive(Clone, Debug)]
-pub struct Key {
-    key: u8,
-    deck: StreamDeck,
+impl Default for ReadStrategy {
+    fn default() -> Self {
+        Self::Blocking(READ_TIMEOUT)
+    }
 }
 
-impl Key {
-    fn new(key: u8, deck: StreamDeck) -> Self {
-        Self { key, deck }
+impl ReadStrategy {
+    /// Returns the longest time a single read blocks the worker thread.
+    fn interval(&self) -> Duration {
+        match self {
+            Self::Blocking(timeout) => *timeout,
+            Self::Poll(interval) => *interval,
+        }
     }
 
-    /// Set the key to a constant color.
-    pub fn color<T>(&self, color: T) -> Result<()>
+    /// Put `deck` into the read mode of the strategy.
+    fn configure<D>(&self, deck: &mut D) -> result::Result<(), streamdeck::Error>
     where
-        T: Into<Color>,
+        D: Hardware,
     {
-        self.deck.send(Message::SetColor(self.key, color.into()))
+        deck.set_blocking(matches!(self, Self::Blocking(_)))
     }
 
-    pub fn image(&self, image: DynamicImage) -> Result<()> {
-        self.deck.send(Message::SetImage(self.key, image))
+    /// Read the button states from `deck`. Returns `None` if there was no
+    /// input.
+    fn read<D>(&self, deck: &mut D) -> result::Result<Option<Vec<u8>>, streamdeck::Error>
+    where
+        D: Hardware,
+    {
+        let res = match self {
+            Self::Blocking(timeout) => deck.read_buttons(Some(*timeout)),
+            Self::Poll(_) => deck.read_buttons(None),
+        };
+
+        match (res, self) {
+            (Ok(keys), _) => Ok(Some(keys)),
+            (Err(streamdeck::Error::NoData), Self::Blocking(_)) => Ok(None),
+            (Err(streamdeck::Error::NoData), Self::Poll(interval)) => {
+                std::thread::sleep(*interval);
+                Ok(None)
+            }
+            (Err(err), _) => Err(err),
+        }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+/// A button behind its own lock, shared between the keys of a [`Span`].
+///
+/// The main loop only locks the map of buttons to look up a button and
+/// releases it before calling the button, so a slow button doesn't block
+/// other users of the map. The main loop dispatches one call at a time, so
+/// the mutex only serializes calls with tasks the button spawned itself.
+pub type SharedButton = Arc<tokio::sync::Mutex<Box<dyn Button>>>;
+
+/// A rectangle of keys sharing a single button, created with
+/// [`Layout::span`].
+///
+/// [`Layout::span`]: crate::layout::Layout::span
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The row of the top left key.
+    pub row: u8,
+    /// The column of the top left key.
+    pub col: u8,
+    pub rows: u8,
+    pub columns: u8,
 }
 
-impl From<(u8, u8, u8)> for Color {
-    fn from(t: (u8, u8, u8)) -> Self {
-        Self {
-            r: t.0,
-            g: t.1,
-            b: t.2,
+impl Span {
+    /// Returns the key indices of the span on a deck with `deck_columns`
+    /// columns. Columns past the edge of the deck and keys past the largest
+    /// key index are skipped.
+    pub fn keys(&self, deck_columns: u8) -> Vec<u8> {
+        let (row, rows) = (u32::from(self.row), u32::from(self.rows));
+        let (col, columns) = (u32::from(self.col), u32::from(self.columns));
+        let deck_columns = u32::from(deck_columns);
+
+        let mut keys = Vec::new();
+        for row in row..row + rows {
+            for col in col..(col + columns).min(deck_columns) {
+                if let Ok(key) = u8::try_from(row * deck_columns + col) {
+                    keys.push(key);
+                }
+            }
         }
+
+        keys
     }
 }
 
-impl From<u32> for Color {
-    fn from(t: u32) -> Self {
+#[derive(Clone)]
+pub struct ButtonWrapper {
+    button: SharedButton,
+    /// The span of keys sharing the button, or `None` if the button is only
+    /// placed on a single key.
+    span: Option<Span>,
+    /// The [`Button::name`] of the button. Stored to not lock the button for
+    /// logging.
+    name: String,
+}
+
+impl ButtonWrapper {
+    pub fn new(button: Box<dyn Button>) -> Self {
         Self {
-            r: t as u8,
-            g: (t << 8) as u8,
-            b: (t << 16) as u8,
+            name: button.name().to_owned(),
+            button: Arc::new(tokio::sync::Mutex::new(button)),
+            span: None,
         }
     }
-}
 
-impl From<Color> for streamdeck::Colour {
-    fn from(t: Color) -> Self {
+    /// Creates a wrapper for one key of `span` dispatching to the `button`
+    /// shared by all its keys.
+    pub fn shared(button: SharedButton, span: Span) -> Self {
+        let name = match button.try_lock() {
+            Ok(button) => button.name().to_owned(),
+            Err(_) => String::from("SharedButton"),
+        };
+
         Self {
-            r: t.r,
-            g: t.g,
-            b: t.b,
+            button,
+            span: Some(span),
+            name,
         }
     }
-}
 
-/// A button that does nothing.
-#[derive(Debug)]
-pub struct NullButton;
+    /// Returns the [`Button::name`] of the button.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-impl Default for NullButton {
-    fn default() -> Self {
-        Self
+    /// Replaces the button with `button`. The key is no longer part of a
+    /// span, the other keys of the span keep the old button. Neither is the
+    /// new button initialized nor the old one deinitialized, use
+    /// [`State::rebind`] to do both.
+    ///
+    /// Calls of the old button that are still running finish normally.
+    pub fn replace(&mut self, button: Box<dyn Button>) {
+        *self = Self::new(button);
     }
-}
 
-#[async_trait::async_trait]
-impl Button for NullButton {
-    async fn init(&mut self, _: &mut State, _: Key) -> Result<()> {
-        Ok(())
+    /// Locks the button and returns it with the `Key` handle for `key`.
+    async fn get(
+        &self,
+        key: u8,
+        streamdeck: StreamDeck,
+    ) -> (tokio::sync::MutexGuard<'_, Box<dyn Button>>, Key) {
+        let key = Key {
+            key,
+            deck: streamdeck,
+            span: self.span,
+        };
+
+        (self.button.lock().await, key)
     }
 
-    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
-        Ok(())
+    /// Call the `init` method of the button.
+    async fn exec_init(&self, key: u8, streamdeck: StreamDeck, state: &mut State) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.init(state, key).await
+    }
+
+    /// Call the `on_long_press` method of the button.
+    async fn exec_long_press(
+        &self,
+        key: u8,
+        streamdeck: StreamDeck,
+        state: &mut State,
+    ) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.on_long_press(state, key).await
+    }
+
+    /// Call the `on_hold_release` method of the button.
+    async fn exec_hold_release(
+        &self,
+        key: u8,
+        streamdeck: StreamDeck,
+        state: &mut State,
+        held: Duration,
+    ) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.on_hold_release(state, key, held).await
+    }
+
+    /// Call the `on_click` method of the button.
+    async fn exec_click(&self, key: u8, streamdeck: StreamDeck, state: &mut State) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.on_click(state, key).await
+    }
+
+    /// Call the `on_tick` method of the button.
+    async fn exec_tick(&self, key: u8, streamdeck: StreamDeck, state: &mut State) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.on_tick(state, key).await
+    }
+
+    /// Call the `deinit` method of the button.
+    async fn exec_deinit(&self, key: u8, streamdeck: StreamDeck, state: &mut State) -> Result<()> {
+        let (mut button, key) = self.get(key, streamdeck).await;
+        button.deinit(state, key).await
+    }
+
+    /// Returns `true` if both wrappers dispatch to the same button, e.g. two
+    /// keys of a span.
+    fn shares_button(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.button, &other.button)
     }
 }
 
-#[cfg(tests)]
-mod tests {
-    use super::Color;
+/// A Streamdeck device managed by [`main_loop_devices`].
+pub struct Device {
+    pub vid: u16,
+    pub pid: u16,
+    /// The serial of the device. Connects to the first device matching
+    /// `vid` and `pid` when `None`.
+    pub serial: Option<String>,
+    /// The buttons of this device.
+    pub buttons: Arc<RwLock<HashMap<u8, ButtonWrapper>>>,
+}
 
-    #[test]
-    fn test_color() {
-        assert_eq!(
-            Color::from((32, 65, 128)),
-            Color {
-                r: 32,
-                g: 56,
-                b: 128
-            }
+/// Drop buttons of layouts made for larger models that have no key on
+/// `deck`.
+fn drop_missing_keys(buttons: &mut HashMap<u8, ButtonWrapper>, deck: &StreamDeck) {
+    buttons.retain(|key, _| {
+        if *key < deck.keys() {
+            return true;
+        }
+
+        warn!(
+            "Ignoring button at key {}, deck {} only has {} keys",
+            key,
+            deck.serial(),
+            deck.keys()
+        );
+        false
+    });
+}
+
+/// Run the main loop for a single device using the buttons from
+/// `state.buttons`.
+pub async fn main_loop(vid: u16, pid: u16, serial: Option<String>, state: State) -> ! {
+    let device = Device {
+        vid,
+        pid,
+        serial,
+        buttons: state.buttons.clone(),
+    };
+
+    main_loop_devices(vec![device], state).await
+}
+
+/// Run the main loop for multiple devices. Every device has its own buttons
+/// and worker thread, while the typemap and idle mode are shared between all
+/// devices.
+pub async fn main_loop_devices(devices: Vec<Device>, mut state: State) -> ! {
+    let services = Services::get(&state);
+    services.start(&mut state).await;
+
+    // The connected decks.
+    let mut decks = Vec::new();
+    let (events_tx, events) = unbounded_channel();
+
+    for (index, device) in devices.into_iter().enumerate() {
+        let (deck, mut device_events) =
+            match connect_with_retry(device.vid, device.pid, device.serial, &state).await {
+                Ok(res) => res,
+                Err(err) => {
+                    error!("Failed to connect to Streamdeck: {:?}", err);
+                    services.stop(&mut state).await;
+                    process::exit(1);
+                }
+            };
+
+        info!(
+            "Connected to streamdeck {} (VID = {}, PID = {})",
+            deck.serial(),
+            device.vid,
+            device.pid
         );
+
+        // Tag the button states with the index of the deck they were read
+        // from.
+        let events_tx = events_tx.clone();
+        let serial = deck.serial().to_owned();
+        task::spawn(async move {
+            while let Some(keys) = device_events.recv().await {
+                if events_tx.send((index, keys)).is_err() {
+                    break;
+                }
+            }
+
+            error!("Lost connection to Streamdeck {}", serial);
+        });
+
+        task::spawn(watchdog(deck.clone(), state.read_strategy));
+
+        let mut state = state.clone();
+        state.buttons = device.buttons;
+        state.keyed = Arc::new(RwLock::new(KeyedTypeMap::new()));
+
+        drop_missing_keys(&mut state.buttons.write().unwrap(), &deck);
+
+        let brightness = state.brightness;
+        let mut deck = ConnectedDeck {
+            keys: vec![0; deck.keys() as usize],
+            pressed_at: HashMap::new(),
+            deck,
+            state,
+        };
+
+        // Call the `init` method on every button.
+        deck.init(None).await;
+
+        if let Err(err) = deck.deck.set_brightness(brightness) {
+            error!(
+                "Failed to set brightness of deck {}: {:?}",
+                deck.deck.serial(),
+                err
+            );
+        }
+        decks.push(deck);
+    }
+    drop(events_tx);
+
+    // Requests of buttons to call `init` again.
+    let (reinit_tx, reinit) = unbounded_channel();
+    state.with_typemap(|typemap| typemap.insert(ReinitSender(reinit_tx)));
+
+    // Clicks requested from outside of the main loop. The channel stays empty
+    // unless the HTTP control API is enabled.
+    let (remote_tx, remote) = unbounded_channel();
+
+    #[cfg(feature = "http")]
+    if let Some(addr) = state.http_addr {
+        crate::http::spawn(addr, remote_tx.clone(), state.clone());
+    }
+    drop(remote_tx);
+
+    let ticks = state.tick.map(|period| {
+        let mut interval = time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    let hangup = match &state.reload {
+        Some(_) => listen_signal(SignalKind::hangup(), "SIGHUP"),
+        None => None,
+    };
+
+    let mut inputs = Inputs {
+        events,
+        remote,
+        reinit,
+        ticks,
+        hangup,
+        interrupt: listen_signal(SignalKind::interrupt(), "SIGINT"),
+        terminate: listen_signal(SignalKind::terminate(), "SIGTERM"),
+    };
+
+    let mut last_press = Instant::now();
+    let mut is_idle = false;
+
+    loop {
+        // Wait for a button to be pressed (or released). Enter idle mode
+        // when no button was pressed until the idle timeout elapsed.
+        let event = match &state.idle {
+            Some(idle) if !is_idle => {
+                let timeout = idle.timeout.saturating_sub(last_press.elapsed());
+
+                match time::timeout(timeout, inputs.next()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        debug!("Entering idle mode");
+
+                        for deck in &decks {
+                            if let Err(err) = deck.deck.set_idle_brightness(Some(idle.brightness)) {
+                                error!("Failed to dim deck {}: {:?}", deck.deck.serial(), err);
+                            }
+
+                            if let Some(image) = &idle.standby {
+                                if let Err(err) = deck.deck.set_standby(Some(image.clone())) {
+                                    error!(
+                                        "Failed to show standby image on deck {}: {:?}",
+                                        deck.deck.serial(),
+                                        err
+                                    );
+                                }
+                            }
+                        }
+                        is_idle = true;
+                        continue;
+                    }
+                }
+            }
+            _ => inputs.next().await,
+        };
+
+        let (index, keys) = match event {
+            Some(Input::Keys(index, keys)) => (index, keys),
+            Some(Input::Shutdown) => {
+                info!("Shutting down");
+                for deck in &mut decks {
+                    deck.deinit().await;
+                }
+                services.stop(&mut state).await;
+                process::exit(0);
+            }
+            Some(Input::Tick) => {
+                for deck in &mut decks {
+                    deck.tick().await;
+                }
+                continue;
+            }
+            Some(Input::Reinit(Reinit::Key(serial, key))) => {
+                for deck in decks.iter_mut().filter(|deck| deck.deck.serial() == serial) {
+                    deck.init(Some(key)).await;
+                }
+                continue;
+            }
+            Some(Input::Reinit(Reinit::Rebind(serial, key, old))) => {
+                for deck in decks.iter_mut().filter(|deck| deck.deck.serial() == serial) {
+                    deck.deinit_replaced(key, &old).await;
+                    deck.init(Some(key)).await;
+                }
+                continue;
+            }
+            Some(Input::Reinit(Reinit::All)) => {
+                for deck in &mut decks {
+                    deck.init(None).await;
+                }
+                continue;
+            }
+            Some(Input::Reload) => {
+                info!("Reloading buttons");
+
+                // Services like the OBS client live in the typemap and are
+                // kept. Icons are loaded again as they may have changed.
+                state.icons.clear();
+                if let Some(load) = state.reload.clone() {
+                    for deck in &mut decks {
+                        deck.reload(&load).await;
+                    }
+                }
+                continue;
+            }
+            Some(Input::Click(click)) => {
+                debug!("Key {} on deck {} clicked remotely", click.key, click.deck);
+
+                let res = match decks.get_mut(click.deck) {
+                    Some(deck) => deck.click(click.key).await,
+                    None => None,
+                };
+
+                let _ = click.tx.send(res);
+                continue;
+            }
+            None => break,
+        };
+
+        // Find the buttons that were pressed or released since the last
+        // read. Buttons that are held down are only reported once.
+        let pressed = pressed_keys(&decks[index].keys, &keys);
+        let released = pressed_keys(&keys, &decks[index].keys);
+        decks[index].keys = keys;
+
+        // Dispatch releases and long presses of the released buttons.
+        {
+            let ConnectedDeck {
+                deck,
+                state,
+                pressed_at,
+                ..
+            } = &mut decks[index];
+
+            for key in released {
+                let held = match pressed_at.remove(&key) {
+                    Some(instant) => instant.elapsed(),
+                    None => continue,
+                };
+
+                let button = match state.buttons.read().unwrap().get(&key) {
+                    Some(button) => button.clone(),
+                    None => continue,
+                };
+
+                if let Err(err) = button
+                    .exec_hold_release(key, deck.clone(), state, held)
+                    .await
+                {
+                    error!(
+                        "Button '{}' on key {} failed on release: {:?}",
+                        button.name(),
+                        key,
+                        err
+                    );
+                }
+
+                if held >= LONG_PRESS {
+                    debug!("Key {} on deck {} long pressed", key, deck.serial());
+
+                    if let Err(err) = button.exec_long_press(key, deck.clone(), state).await {
+                        error!(
+                            "Button '{}' on key {} failed on long press: {:?}",
+                            button.name(),
+                            key,
+                            err
+                        );
+                    }
+                }
+            }
+        }
+
+        if pressed.is_empty() {
+            continue;
+        }
+
+        last_press = Instant::now();
+
+        // Wake up from idle mode.
+        if is_idle {
+            debug!("Leaving idle mode");
+
+            // Restore the brightness last set on every deck, which buttons
+            // may have changed since the start.
+            for deck in &decks {
+                if let Err(err) = deck.deck.set_idle_brightness(None) {
+                    error!(
+                        "Failed to restore brightness of deck {}: {:?}",
+                        deck.deck.serial(),
+                        err
+                    );
+                }
+
+                if matches!(&state.idle, Some(idle) if idle.standby.is_some()) {
+                    if let Err(err) = deck.deck.set_standby(None) {
+                        error!(
+                            "Failed to remove standby image on deck {}: {:?}",
+                            deck.deck.serial(),
+                            err
+                        );
+                    }
+                }
+            }
+            is_idle = false;
+
+            if matches!(&state.idle, Some(idle) if idle.swallow_wake) {
+                continue;
+            }
+        }
+
+        let deck = &mut decks[index];
+
+        for key in pressed {
+            deck.pressed_at.insert(key, Instant::now());
+
+            debug!(
+                "Key {} (ROW {} COL {}) on deck {} pressed",
+                key,
+                key / deck.deck.columns(),
+                key % deck.deck.columns(),
+                deck.deck.serial()
+            );
+
+            if state.dim_on_press {
+                deck.deck.flash(key);
+            }
+
+            if let Some(path) = &state.click_sound {
+                play_sound(path);
+            }
+
+            // Execute the buttons job.
+            if let Some(Err(err)) = deck.click(key).await {
+                error!(
+                    "Button '{}' on key {} failed: {:?}",
+                    deck.button_name(key),
+                    key,
+                    err
+                );
+            }
+        }
+    }
+
+    error!("Lost connection to Streamdeck");
+    for deck in &mut decks {
+        deck.deinit().await;
+    }
+    services.stop(&mut state).await;
+    process::exit(1);
+}
+
+/// Exit the process if the worker thread of `deck` reading with
+/// `read_strategy` stops responding. The worker reconnects to the device on
+/// its own, so a missing heartbeat means it either panicked or is stuck in a
+/// call to the device and can't be recovered. The watchdog stops once the
+/// worker gave up reconnecting, leaving the other decks running.
+async fn watchdog(deck: StreamDeck, read_strategy: ReadStrategy) {
+    let timeout = WATCHDOG_TIMEOUT + read_strategy.interval();
+    let mut interval = time::interval(WATCHDOG_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if deck.worker_exited() {
+            return;
+        }
+
+        if deck.since_heartbeat() > timeout {
+            error!(
+                "Worker thread of Streamdeck {} is not responding",
+                deck.serial()
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Connect to a Streamdeck, retrying with an exponential backoff until the
+/// attempts or the timeout of [`State::retry`] are exhausted. Returns the
+/// error of the last attempt.
+async fn connect_with_retry(
+    vid: u16,
+    pid: u16,
+    serial: Option<String>,
+    state: &State,
+) -> Result<(StreamDeck, UnboundedReceiver<Vec<u8>>)> {
+    let retry = &state.retry;
+    let start = Instant::now();
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        let res = StreamDeck::connect(
+            vid,
+            pid,
+            serial.clone(),
+            state.read_strategy,
+            retry.clone(),
+            state.icons.clone(),
+        );
+        let err = match res {
+            Ok(res) => return Ok(res),
+            Err(err) => err,
+        };
+
+        if attempt >= retry.max_attempts || start.elapsed() + delay > retry.timeout {
+            return Err(err);
+        }
+
+        info!(
+            "Failed to connect to Streamdeck (attempt {}/{}), retrying in {:?}: {:?}",
+            attempt, retry.max_attempts, delay, err
+        );
+
+        time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+        attempt += 1;
+    }
+}
+
+/// A deck connected in [`main_loop_devices`].
+struct ConnectedDeck {
+    deck: StreamDeck,
+    /// The state passed to the buttons of this deck.
+    state: State,
+    /// The button states of the last read.
+    keys: Vec<u8>,
+    /// The time at which the currently held buttons were pressed.
+    pressed_at: HashMap<u8, Instant>,
+}
+
+impl ConnectedDeck {
+    /// Call the `init` method of the button at `key`, or of all buttons if
+    /// `key` is `None`.
+    async fn init(&mut self, key: Option<u8>) {
+        for (key, button) in self
+            .buttons()
+            .into_iter()
+            .filter(|(k, _)| key.map_or(true, |key| *k == key))
+        {
+            debug!("Initializing key {} on deck {}", key, self.deck.serial());
+
+            if let Err(err) = button
+                .exec_init(key, self.deck.clone(), &mut self.state)
+                .await
+            {
+                error!(
+                    "Button '{}' on key {} failed to initialize: {:?}",
+                    button.name(),
+                    key,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Replace the buttons with the ones built by `load` and initialize
+    /// them. The current buttons are kept if `load` fails.
+    async fn reload(&mut self, load: &LoadButtons) {
+        let mut buttons = match load(self.deck.serial(), self.deck.model) {
+            Ok(buttons) => buttons,
+            Err(err) => {
+                error!(
+                    "Failed to reload buttons of deck {}, keeping the current ones: {:?}",
+                    self.deck.serial(),
+                    err
+                );
+                return;
+            }
+        };
+        drop_missing_keys(&mut buttons, &self.deck);
+
+        self.deinit().await;
+        *self.state.buttons.write().unwrap() = buttons;
+        self.pressed_at.clear();
+
+        // Clear the keys that no longer have a button.
+        if let Err(err) = self.deck.reset() {
+            error!("Failed to reset deck {}: {:?}", self.deck.serial(), err);
+        }
+        self.init(None).await;
+    }
+
+    /// Call the `deinit` method of all buttons.
+    async fn deinit(&mut self) {
+        for (key, button) in self.buttons() {
+            self.deinit_button(key, &button).await;
+        }
+    }
+
+    /// Call the `deinit` method of the button `old` replaced at `key`,
+    /// unless another key of its span still uses it.
+    async fn deinit_replaced(&mut self, key: u8, old: &ButtonWrapper) {
+        let in_use = self
+            .buttons()
+            .iter()
+            .any(|(_, button)| button.shares_button(old));
+
+        if !in_use {
+            self.deinit_button(key, old).await;
+        }
+    }
+
+    async fn deinit_button(&mut self, key: u8, button: &ButtonWrapper) {
+        debug!("Deinitializing key {} on deck {}", key, self.deck.serial());
+
+        if let Err(err) = button
+            .exec_deinit(key, self.deck.clone(), &mut self.state)
+            .await
+        {
+            error!(
+                "Button '{}' on key {} failed to deinitialize: {:?}",
+                button.name(),
+                key,
+                err
+            );
+        }
+    }
+
+    /// Call the `on_tick` method of all buttons.
+    async fn tick(&mut self) {
+        for (key, button) in self.buttons() {
+            if let Err(err) = button
+                .exec_tick(key, self.deck.clone(), &mut self.state)
+                .await
+            {
+                error!(
+                    "Button '{}' on key {} failed on tick: {:?}",
+                    button.name(),
+                    key,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Returns the buttons of the deck ordered by their key. The map of
+    /// buttons is only locked while copying the handles.
+    fn buttons(&self) -> Vec<(u8, ButtonWrapper)> {
+        let buttons = self.state.buttons.read().unwrap();
+
+        let mut buttons: Vec<_> = buttons
+            .iter()
+            .map(|(key, button)| (*key, button.clone()))
+            .collect();
+        buttons.sort_by_key(|(key, _)| *key);
+        buttons
+    }
+
+    /// Returns the [`Button::name`] of the button at `key`.
+    fn button_name(&self, key: u8) -> String {
+        let buttons = self.state.buttons.read().unwrap();
+        buttons
+            .get(&key)
+            .map(|button| button.name().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Execute the `on_click` method of the button at `key`. Returns `None`
+    /// if there is no button at `key`.
+    async fn click(&mut self, key: u8) -> Option<Result<()>> {
+        // Don't hold the lock of the map while the button runs.
+        let button = self.state.buttons.read().unwrap().get(&key)?.clone();
+        Some(
+            button
+                .exec_click(key, self.deck.clone(), &mut self.state)
+                .await,
+        )
+    }
+}
+
+/// A click of a button requested from outside of the main loop.
+pub struct RemoteClick {
+    /// The index of the deck in [`main_loop_devices`].
+    pub deck: usize,
+    pub key: u8,
+    /// Receives the result of the click, or `None` if there is no button at
+    /// `key`.
+    pub tx: oneshot::Sender<Option<Result<()>>>,
+}
+
+/// A request to call the `init` method of buttons again, see
+/// [`State::reinit`].
+enum Reinit {
+    /// The button at the key of the deck with the serial.
+    Key(String, u8),
+    /// The button at the key of the deck with the serial after it replaced
+    /// the button.
+    Rebind(String, u8, ButtonWrapper),
+    All,
+}
+
+/// The sender of [`Reinit`] requests to the main loop.
+struct ReinitSender(UnboundedSender<Reinit>);
+
+impl State {
+    /// Calls the `init` method of the button at `key` again, e.g. to repaint
+    /// it after the key was overwritten. The main loop handles the request
+    /// after the current button returned.
+    ///
+    /// `init` may run multiple times, so buttons spawning tasks in `init`
+    /// must only do so once.
+    pub fn reinit(&self, key: &Key) -> Result<()> {
+        self.send_reinit(Reinit::Key(key.deck.serial().to_owned(), key.key))
+    }
+
+    /// Replaces the button at `key` with `button`, e.g. to switch pages.
+    /// Once the current button returned, the old button is deinitialized
+    /// unless other keys of its span still use it and the new button is
+    /// initialized. See [`ButtonWrapper::replace`].
+    pub fn rebind(&self, key: &Key, button: Box<dyn Button>) -> Result<()> {
+        let old = {
+            let mut buttons = self.buttons.write().unwrap();
+            match buttons.get_mut(&key.key) {
+                Some(wrapper) => {
+                    let old = wrapper.clone();
+                    wrapper.replace(button);
+                    Some(old)
+                }
+                None => {
+                    buttons.insert(key.key, ButtonWrapper::new(button));
+                    None
+                }
+            }
+        };
+
+        let serial = key.deck.serial().to_owned();
+        match old {
+            Some(old) => self.send_reinit(Reinit::Rebind(serial, key.key, old)),
+            None => self.send_reinit(Reinit::Key(serial, key.key)),
+        }
+    }
+
+    /// Calls the `init` method of all buttons on all decks again. See
+    /// [`State::reinit`].
+    pub fn reinit_all(&self) -> Result<()> {
+        self.send_reinit(Reinit::All)
+    }
+
+    /// Returns [`Error::NoResponse`] if the main loop is not running.
+    fn send_reinit(&self, reinit: Reinit) -> Result<()> {
+        let typemap = self.typemap.read().unwrap();
+        match typemap.get::<ReinitSender>() {
+            Some(tx) => tx.0.send(reinit).map_err(|_| Error::NoResponse),
+            None => Err(Error::NoResponse),
+        }
+    }
+}
+
+/// An input handled by the main loop.
+enum Input {
+    /// The button states read from the deck at the index.
+    Keys(usize, Vec<u8>),
+    Click(RemoteClick),
+    Reinit(Reinit),
+    /// The tick interval elapsed.
+    Tick,
+    /// The process received `SIGHUP`.
+    Reload,
+    /// The process received Ctrl-C.
+    Shutdown,
+}
+
+/// The sources of inputs of the main loop.
+struct Inputs {
+    events: UnboundedReceiver<(usize, Vec<u8>)>,
+    remote: UnboundedReceiver<RemoteClick>,
+    reinit: UnboundedReceiver<Reinit>,
+    /// Disabled when `None`.
+    ticks: Option<time::Interval>,
+    /// Disabled when `None`.
+    hangup: Option<Signal>,
+    /// Disabled when `None`.
+    interrupt: Option<Signal>,
+    /// Disabled when `None`.
+    terminate: Option<Signal>,
+}
+
+impl Inputs {
+    /// Wait for the next input. Returns `None` once all decks are
+    /// disconnected.
+    async fn next(&mut self) -> Option<Input> {
+        let ticks = &mut self.ticks;
+        let tick = async move {
+            match ticks {
+                Some(ticks) => ticks.tick().await,
+                None => future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            event = self.events.recv() => event.map(|(index, keys)| Input::Keys(index, keys)),
+            Some(click) = self.remote.recv() => Some(Input::Click(click)),
+            Some(reinit) = self.reinit.recv() => Some(Input::Reinit(reinit)),
+            _ = tick => Some(Input::Tick),
+            Some(()) = recv_signal(&mut self.hangup) => Some(Input::Reload),
+            Some(()) = recv_signal(&mut self.interrupt) => Some(Input::Shutdown),
+            Some(()) = recv_signal(&mut self.terminate) => Some(Input::Shutdown),
+        }
+    }
+}
+
+/// Listen for the signal `kind`. Logs an error and returns `None` if the
+/// signal handler could not be registered.
+fn listen_signal(kind: SignalKind, name: &str) -> Option<Signal> {
+    match signal(kind) {
+        Ok(signal) => Some(signal),
+        Err(err) => {
+            error!("Failed to listen for {}: {:?}", name, err);
+            None
+        }
+    }
+}
+
+/// Wait for the next signal of `signal`. Never completes if `signal` is
+/// `None`.
+async fn recv_signal(signal: &mut Option<Signal>) -> Option<()> {
+    match signal {
+        Some(signal) => signal.recv().await,
+        None => future::pending().await,
+    }
+}
+
+/// Play the sound file at `path` using `paplay` without waiting for it to
+/// finish.
+fn play_sound(path: &Path) {
+    let res = tokio::process::Command::new("paplay")
+        .arg(path)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .spawn();
+
+    // The child is reaped by tokio once it exits.
+    if let Err(err) = res {
+        warn!("Failed to play click sound {:?}: {:?}", path, err);
+    }
+}
+
+/// Returns `true` if `keys` is a plausible button report of the Streamdeck
+/// model `kind`: one state of either 0 or 1 per key. Some firmware versions
+/// occasionally send reports of other lengths or with garbage states.
+fn is_valid_report(model: Model, keys: &[u8]) -> bool {
+    keys.len() == model.keys() as usize && keys.iter().all(|state| *state <= 1)
+}
+
+/// Returns the keys that changed from released in `previous` to pressed in
+/// `current`. Swapping the arguments returns the released keys instead.
+fn pressed_keys(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    current
+        .iter()
+        .enumerate()
+        .filter(|&(i, &x)| x == 1 && previous.get(i).copied().unwrap_or(0) == 0)
+        .map(|(i, _)| i as u8)
+        .collect()
+}
+
+/// An optional channel the worker thread reports the result of a message
+/// back to. Errors of messages without a channel are logged.
+type Ack = Option<oneshot::Sender<Result<()>>>;
+
+enum Message {
+    SetColor(u8, Color, Ack),
+    SetImage(u8, DynamicImage, Ack),
+    SetBrightness(u8, Ack),
+    /// Enter the idle mode with the brightness, or leave it and restore the
+    /// brightness last set if `None`.
+    IdleBrightness(Option<u8>),
+    /// Show a color or image on a key without replacing its remembered
+    /// frame.
+    Overlay(u8, Frame, Ack),
+    /// Repaint the remembered frame of a key.
+    Restore(u8, Ack),
+    /// Show a darkened version of the remembered frame of a key.
+    Dim(u8),
+    /// Repaint the remembered frame of a key unless something else was
+    /// written to the key since it was dimmed.
+    Undim(u8),
+    /// Show every frame set on the first key on the second key as well,
+    /// starting with the current frame.
+    Mirror(u8, u8),
+    /// Stop showing the frames of the first key on the second key.
+    Unmirror(u8, u8),
+    /// Paint the outer ring of keys in a color, or repaint their remembered
+    /// frames if `None`. Writes to the covered keys are remembered but not
+    /// shown while the border is set.
+    Border(Option<Color>),
+    /// Show the tiles of a standby image on the keys, or repaint the
+    /// remembered frames if `None`. Writes to the keys are remembered but not
+    /// shown while the standby image is shown.
+    Standby(Option<Vec<DynamicImage>>),
+    /// Reset the device and clear all keys.
+    Reset(Ack),
+}
+
+impl Message {
+    /// Returns the channel the result of the message is reported to.
+    fn into_ack(self) -> Ack {
+        match self {
+            Self::SetColor(_, _, ack)
+            | Self::SetImage(_, _, ack)
+            | Self::SetBrightness(_, ack)
+            | Self::Overlay(_, _, ack)
+            | Self::Restore(_, ack)
+            | Self::Reset(ack) => ack,
+            _ => None,
+        }
+    }
+}
+
+/// The last color or image set on a key.
+#[derive(Clone)]
+enum Frame {
+    Color(Color),
+    Image(DynamicImage),
+}
+
+impl Frame {
+    /// Returns a hash of the content of the frame.
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        match self {
+            Self::Color(color) => (color.r, color.g, color.b).hash(&mut hasher),
+            Self::Image(image) => image_hash(image).hash(&mut hasher),
+        }
+
+        hasher.finish()
+    }
+
+    /// Returns the frame with every color channel halved.
+    fn darken(&self) -> Self {
+        let darken = |c: u8| c / 2;
+
+        match self {
+            Self::Color(color) => Self::Color(Color {
+                r: darken(color.r),
+                g: darken(color.g),
+                b: darken(color.b),
+            }),
+            Self::Image(image) => {
+                let mut image = image.to_rgb8();
+                image
+                    .pixels_mut()
+                    .for_each(|pixel| pixel.0 = pixel.0.map(darken));
+                Self::Image(DynamicImage::ImageRgb8(image))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StreamDeck {
+    tx: mpsc::Sender<Message>,
+    model: Model,
+    serial: Arc<str>,
+    /// The last time the worker thread was alive.
+    heartbeat: Arc<Mutex<Instant>>,
+    /// Whether the worker thread returned, e.g. after giving up
+    /// reconnecting.
+    exited: Arc<AtomicBool>,
+    /// The latest button states read by the worker thread.
+    keys: Arc<Mutex<Vec<u8>>>,
+    /// The images resized by `fit_image`, keyed by the hash of the original
+    /// image.
+    resized: Arc<Mutex<HashMap<u64, DynamicImage>>>,
+    /// The icons loaded from files by the keys.
+    icons: IconCache,
+    /// The tasks removing the images shown by [`Key::flash`], keyed by the
+    /// key index.
+    flashes: Arc<Mutex<HashMap<u8, task::JoinHandle<()>>>>,
+}
+
+impl StreamDeck {
+    /// Connects to the Streamdeck and spawns the worker thread owning the
+    /// device, reading input using `read_strategy`. The worker reconnects to
+    /// the device if it is lost until the attempts or the timeout of `retry`
+    /// are exhausted. Returns the `StreamDeck` handle and a receiver for the
+    /// button states read from the device, which is closed once the worker
+    /// gave up. Icons loaded by the keys are cached in `icons`.
+    pub fn connect(
+        vid: u16,
+        pid: u16,
+        serial: Option<String>,
+        read_strategy: ReadStrategy,
+        retry: Retry,
+        icons: IconCache,
+    ) -> Result<(Self, UnboundedReceiver<Vec<u8>>)> {
+        let res = match pid {
+            PEDAL_PID => {
+                let pedal = open_pedal(vid, pid, serial.as_deref())?;
+                let reopen = move |serial: Option<&str>| open_pedal(vid, pid, serial);
+                Self::spawn_worker(pedal, serial, reopen, read_strategy, retry, icons)
+            }
+            _ => {
+                let deck = open_deck(vid, pid, serial.as_deref())?;
+                let reopen = move |serial: Option<&str>| open_deck(vid, pid, serial);
+                Self::spawn_worker(deck, serial, reopen, read_strategy, retry, icons)
+            }
+        };
+
+        Ok(res)
+    }
+
+    /// Spawn the worker thread owning `device`. `reopen` opens the device
+    /// with the serial again when the connection is lost.
+    fn spawn_worker<D, F>(
+        mut device: D,
+        serial: Option<String>,
+        reopen: F,
+        read_strategy: ReadStrategy,
+        retry: Retry,
+        icons: IconCache,
+    ) -> (Self, UnboundedReceiver<Vec<u8>>)
+    where
+        D: Hardware,
+        F: Fn(Option<&str>) -> result::Result<D, streamdeck::Error> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let (events_tx, events_rx) = unbounded_channel();
+
+        let model = device.model();
+        let serial = match device.serial() {
+            Ok(serial) => serial,
+            Err(_) => serial.unwrap_or_default(),
+        };
+
+        let heartbeat = Arc::new(Mutex::new(Instant::now()));
+        let keys = Arc::new(Mutex::new(Vec::new()));
+
+        // Reconnect to the same device if the connection is lost.
+        let reconnect = {
+            let serial = Some(serial.clone()).filter(|serial| !serial.is_empty());
+            move || reopen(serial.as_deref())
+        };
+
+        let exited = Arc::new(AtomicBool::new(false));
+
+        {
+            let heartbeat = heartbeat.clone();
+            let keys = keys.clone();
+            let exited = exited.clone();
+            std::thread::spawn(move || {
+                worker(
+                    device,
+                    reconnect,
+                    read_strategy,
+                    retry,
+                    rx,
+                    events_tx,
+                    heartbeat,
+                    keys,
+                );
+                exited.store(true, Ordering::SeqCst);
+            });
+        }
+
+        (
+            Self {
+                tx,
+                model,
+                serial: serial.into(),
+                heartbeat,
+                exited,
+                keys,
+                resized: Arc::new(Mutex::new(HashMap::new())),
+                icons,
+                flashes: Arc::new(Mutex::new(HashMap::new())),
+            },
+            events_rx,
+        )
+    }
+
+    /// Returns the serial of the device.
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    /// Returns the time since the worker thread was last alive.
+    pub fn since_heartbeat(&self) -> Duration {
+        self.heartbeat.lock().unwrap().elapsed()
+    }
+
+    /// Returns `true` if the worker thread returned without panicking, e.g.
+    /// after giving up reconnecting to the device.
+    pub fn worker_exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if `key` was held down when the worker thread last
+    /// read the button states. See [`Key::is_pressed`].
+    pub fn is_pressed(&self, key: u8) -> bool {
+        let keys = self.keys.lock().unwrap();
+        keys.get(key as usize).is_some_and(|state| *state != 0)
+    }
+
+    /// Returns all connected Streamdeck devices. Returns an empty `Vec` if
+    /// no devices were found or the HID API is unavailable.
+    pub fn enumerate() -> Vec<DeviceInfo> {
+        let res = HID_API.with(HidApi::new, |api| {
+            // The shared `HidApi` only lists the devices connected when it
+            // was created.
+            api.refresh_devices()?;
+
+            let devices = api
+                .device_list()
+                .filter(|device| device.vendor_id() == ELGATO_VID)
+                .filter_map(|device| {
+                    let model = model_name(device.product_id())?;
+
+                    Some(DeviceInfo {
+                        vid: device.vendor_id(),
+                        pid: device.product_id(),
+                        serial: device.serial_number().map(String::from),
+                        model,
+                    })
+                })
+                .collect();
+
+            Ok(devices)
+        });
+
+        match res.and_then(|devices| devices) {
+            Ok(devices) => devices,
+            Err(err) => {
+                warn!("Failed to enumerate devices: {:?}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the size of a key image in pixels.
+    pub fn image_size(&self) -> (u32, u32) {
+        self.model.image_size()
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Set the brightness of the deck in percent.
+    pub fn set_brightness(&self, brightness: u8) -> Result<()> {
+        self.send(Message::SetBrightness(brightness, None))
+    }
+
+    /// Dim the deck to `brightness` for the idle mode, or restore the
+    /// brightness last set by [`StreamDeck::set_brightness`] if `None`.
+    /// Brightness changes while idle are applied once the idle mode ends.
+    fn set_idle_brightness(&self, brightness: Option<u8>) -> Result<()> {
+        self.send(Message::IdleBrightness(brightness))
+    }
+
+    /// Darken `key` for [`PRESS_FEEDBACK`] before repainting its current
+    /// frame.
+    fn flash(&self, key: u8) {
+        if let Err(err) = self.send(Message::Dim(key)) {
+            warn!("Failed to dim key {}: {:?}", key, err);
+            return;
+        }
+
+        let deck = self.clone();
+        task::spawn(async move {
+            time::sleep(PRESS_FEEDBACK).await;
+            let _ = deck.send(Message::Undim(key));
+        });
+    }
+
+    /// Reset the deck, clearing all keys. The brightness stays unchanged.
+    pub fn reset(&self) -> Result<()> {
+        self.send(Message::Reset(None))
+    }
+
+    /// Returns the number of keys on the device.
+    pub fn keys(&self) -> u8 {
+        self.model.keys()
+    }
+
+    /// Returns the number of key columns on the device.
+    pub fn columns(&self) -> u8 {
+        self.model.columns()
+    }
+
+    /// Returns the number of key rows on the device.
+    pub fn rows(&self) -> u8 {
+        self.keys() / self.columns()
+    }
+
+    /// Returns the approximate physical gap between two keys, measured in
+    /// key image pixels.
+    pub fn key_gap(&self) -> u32 {
+        match self.model {
+            Model::Deck(Kind::Mini) => 30,
+            Model::Deck(Kind::Original | Kind::OriginalV2) => 25,
+            Model::Deck(Kind::Xl) => 38,
+            Model::Pedal => 0,
+        }
+    }
+
+    /// Scale `image` to the key resolution, cropping the edges of
+    /// non-square images. Images that already have the key resolution are
+    /// returned unchanged. The resized images are cached, so setting the
+    /// same image repeatedly only resizes it once.
+    pub fn fit_image(&self, image: DynamicImage) -> DynamicImage {
+        let (width, height) = self.image_size();
+        if image.dimensions() == (width, height) {
+            return image;
+        }
+
+        let hash = image_hash(&image);
+        if let Some(resized) = self.resized.lock().unwrap().get(&hash) {
+            return resized.clone();
+        }
+
+        let resized = image.resize_to_fill(width, height, FilterType::Lanczos3);
+
+        let mut cache = self.resized.lock().unwrap();
+        if cache.len() >= RESIZE_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(hash, resized.clone());
+
+        resized
+    }
+
+    /// Spread a single image over all keys of the device. The image is
+    /// scaled to cover the whole panel, including the gaps between the keys,
+    /// and the part of the image under every key is sent to that key.
+    pub fn set_full_image(&self, image: DynamicImage) -> Result<()> {
+        for (key, tile) in self.full_image_tiles(image).into_iter().enumerate() {
+            self.send(Message::SetImage(key as u8, tile, None))?;
+        }
+
+        Ok(())
+    }
+
+    /// Show `image` spread over all keys like [`StreamDeck::set_full_image`]
+    /// without replacing the images of the keys, or show the images of the
+    /// keys again if `image` is `None`. Images set while the standby image
+    /// is shown appear once it is removed.
+    pub fn set_standby(&self, image: Option<DynamicImage>) -> Result<()> {
+        let tiles = image.map(|image| self.full_image_tiles(image));
+        self.send(Message::Standby(tiles))
+    }
+
+    /// Returns the parts of `image` under every key after scaling it to
+    /// cover the whole panel.
+    fn full_image_tiles(&self, image: DynamicImage) -> Vec<DynamicImage> {
+        let (width, height) = self.image_size();
+        let gap = self.key_gap();
+        let (columns, rows) = (self.columns() as u32, self.rows() as u32);
+
+        let image = image.resize_to_fill(
+            columns * width + (columns - 1) * gap,
+            rows * height + (rows - 1) * gap,
+            FilterType::Lanczos3,
+        );
+
+        // Only the keys of full rows exist physically, e.g. the Mini reports
+        // 8 keys in 3 columns but only has 6.
+        (0..rows * columns)
+            .map(|key| {
+                let (column, row) = (key % columns, key / columns);
+                image.crop_imm(column * (width + gap), row * (height + gap), width, height)
+            })
+            .collect()
+    }
+
+    /// Show everything set on the key `source` on `key` as well, starting
+    /// with the current color or image of `source`.
+    pub fn mirror(&self, source: u8, key: u8) -> Result<()> {
+        self.send(Message::Mirror(source, key))
+    }
+
+    /// Stop mirroring the key `source` on `key`. The last mirrored frame
+    /// stays on `key` until something else is written to it.
+    pub fn unmirror(&self, source: u8, key: u8) -> Result<()> {
+        self.send(Message::Unmirror(source, key))
+    }
+
+    /// Paint the outer ring of keys in `color`, or remove the border if
+    /// `color` is `None`. The images and colors set on the covered keys
+    /// while the border is shown are kept and repainted when it is removed.
+    pub fn set_border(&self, color: Option<Color>) -> Result<()> {
+        self.send(Message::Border(color))
+    }
+
+    /// Slowly fade the border between a dark shade of `color` and `color`
+    /// until the returned handle is stopped. The border stays in its last
+    /// shade after stopping, remove it using [`StreamDeck::set_border`].
+    pub fn pulse_border<T>(&self, color: T) -> AnimationHandle
+    where
+        T: Into<Color>,
+    {
+        let color = color.into();
+        let deck = self.clone();
+
+        let task = task::spawn(async move {
+            let delay = BORDER_PULSE_PERIOD / BORDER_PULSE_STEPS;
+
+            for step in (0..BORDER_PULSE_STEPS).cycle() {
+                // Fade between 25% and 100% of the color.
+                let phase = step as f32 / BORDER_PULSE_STEPS as f32 * std::f32::consts::TAU;
+                let intensity = 0.625 + 0.375 * phase.cos();
+                let scale = |c: u8| (c as f32 * intensity) as u8;
+
+                let shade = Color::from((scale(color.r), scale(color.g), scale(color.b)));
+                if deck.set_border(Some(shade)).is_err() {
+                    break;
+                }
+
+                time::sleep(delay).await;
+            }
+        });
+
+        AnimationHandle { task: Some(task) }
+    }
+
+    /// Queue `msg` for the worker thread. Returns an error if the worker
+    /// thread is no longer running.
+    fn send(&self, msg: Message) -> Result<()> {
+        self.tx.send(msg).map_err(|_| Error::NoResponse)
+    }
+
+    /// Queue the message created by `f` and wait until the worker thread
+    /// handled it. Returns the result of the message.
+    async fn request<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(Ack) -> Message,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.send(f(Some(tx)))?;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+}
+
+/// The state of the device remembered by the worker thread.
+#[derive(Default)]
+struct WorkerState {
+    /// The last frame set on every key.
+    frames: HashMap<u8, Frame>,
+    /// The hash of the content currently displayed on every key.
+    displayed: HashMap<u8, u64>,
+    /// The last brightness set by [`Message::SetBrightness`]. Restored when
+    /// the idle mode ends.
+    brightness: Option<u8>,
+    /// The brightness of the idle mode while the deck is idle.
+    idle_brightness: Option<u8>,
+    /// The keys currently dimmed by [`Message::Dim`].
+    dimmed: HashSet<u8>,
+    /// The color of the border set by [`Message::Border`].
+    border: Option<Color>,
+    /// The keys showing the frames of another key, keyed by the key they
+    /// mirror.
+    mirrors: HashMap<u8, HashSet<u8>>,
+    /// Whether a standby image set by [`Message::Standby`] covers the keys.
+    standby: bool,
+}
+
+impl WorkerState {
+    /// Returns the brightness the device should currently have.
+    fn effective_brightness(&self) -> Option<u8> {
+        self.idle_brightness.or(self.brightness)
+    }
+    /// Returns `true` if `key` is currently hidden under the border or the
+    /// standby image.
+    fn is_covered(&self, kind: Kind, key: u8) -> bool {
+        self.standby || (self.border.is_some() && perimeter_keys(kind).contains(&key))
+    }
+}
+
+/// Returns the number of key columns of the Streamdeck model `kind`.
+pub fn kind_columns(kind: Kind) -> u8 {
+    match kind {
+        Kind::Mini => 3,
+        Kind::Original | Kind::OriginalV2 => 5,
+        Kind::Xl => 8,
+    }
+}
+
+/// Returns the keys on the outer ring of the grid of the Streamdeck model
+/// `kind`, in ascending order.
+fn perimeter_keys(kind: Kind) -> Vec<u8> {
+    let columns = kind_columns(kind);
+    let rows = kind.keys() / columns;
+
+    // The Mini reports more keys than it has, so only the full rows count.
+    (0..rows * columns)
+        .filter(|key| {
+            let (row, col) = (key / columns, key % columns);
+            row == 0 || row == rows - 1 || col == 0 || col == columns - 1
+        })
+        .collect()
+}
+
+/// Write a message to the device and report the result back.
+fn handle_message(deck: &mut streamdeck::StreamDeck, state: &mut WorkerState, msg: Message) {
+    let kind = deck.kind();
+
+    // Writing anything else to a dimmed key ends the dimming.
+    match &msg {
+        Message::SetColor(key, ..)
+        | Message::SetImage(key, ..)
+        | Message::Overlay(key, ..)
+        | Message::Restore(key, ..) => {
+            state.dimmed.remove(key);
+        }
+        _ => (),
+    }
+
+    let msg_covered = match &msg {
+        Message::Overlay(key, ..)
+        | Message::Restore(key, ..)
+        | Message::Dim(key)
+        | Message::Undim(key) => state.is_covered(kind, *key),
+        _ => false,
+    };
+
+    let (res, ack) = match msg {
+        Message::SetColor(key, color, ack) => {
+            (set_frame(deck, state, key, Frame::Color(color)), ack)
+        }
+        Message::SetImage(key, image, ack) => {
+            let frame = Frame::Image(fit_to_key(kind, image));
+            (set_frame(deck, state, key, frame), ack)
+        }
+        Message::SetBrightness(brightness, ack) => {
+            state.brightness = Some(brightness);
+
+            // Brightness changes while idle take effect on wake up.
+            match state.idle_brightness {
+                Some(_) => (Ok(()), ack),
+                None => (deck.set_brightness(brightness), ack),
+            }
+        }
+        Message::IdleBrightness(idle_brightness) => {
+            state.idle_brightness = idle_brightness;
+
+            match state.effective_brightness() {
+                Some(brightness) => (deck.set_brightness(brightness), None),
+                None => (Ok(()), None),
+            }
+        }
+        // Overlays and press feedback are hidden by the border.
+        Message::Overlay(_, _, ack) | Message::Restore(_, ack) if msg_covered => (Ok(()), ack),
+        Message::Dim(_) | Message::Undim(_) if msg_covered => (Ok(()), None),
+        Message::Overlay(key, frame, ack) => {
+            let frame = match frame {
+                Frame::Image(image) => Frame::Image(fit_to_key(kind, image)),
+                frame => frame,
+            };
+            (show_frame(deck, &mut state.displayed, key, &frame), ack)
+        }
+        Message::Restore(key, ack) => {
+            let frame = match state.frames.get(&key) {
+                Some(frame) => frame.clone(),
+                None => Frame::Color(Color::from((0, 0, 0))),
+            };
+
+            (show_frame(deck, &mut state.displayed, key, &frame), ack)
+        }
+        Message::Dim(key) => {
+            let frame = match state.frames.get(&key) {
+                Some(frame) => frame.darken(),
+                None => Frame::Color(Color::from((0, 0, 0))),
+            };
+
+            state.dimmed.insert(key);
+            (show_frame(deck, &mut state.displayed, key, &frame), None)
+        }
+        Message::Undim(key) => {
+            let res = match (state.dimmed.remove(&key), state.frames.get(&key)) {
+                (true, Some(frame)) => show_frame(deck, &mut state.displayed, key, frame),
+                _ => Ok(()),
+            };
+
+            (res, None)
+        }
+        Message::Mirror(source, key) => {
+            state.mirrors.entry(source).or_default().insert(key);
+
+            let res = match state.frames.get(&source) {
+                Some(frame) => set_frame(deck, state, key, frame.clone()),
+                None => Ok(()),
+            };
+            (res, None)
+        }
+        Message::Unmirror(source, key) => {
+            if let Some(mirrors) = state.mirrors.get_mut(&source) {
+                mirrors.remove(&key);
+                if mirrors.is_empty() {
+                    state.mirrors.remove(&source);
+                }
+            }
+
+            (Ok(()), None)
+        }
+        Message::Border(color) => {
+            state.border = color;
+
+            // The border is painted when the standby image is removed.
+            match state.standby {
+                true => (Ok(()), None),
+                false => (paint_border(deck, state), None),
+            }
+        }
+        Message::Standby(tiles) => (show_standby(deck, state, tiles), None),
+        Message::Reset(ack) => (reset(deck, state), ack),
+    };
+
+    let res = res.map_err(Error::from);
+    match ack {
+        Some(ack) => {
+            let _ = ack.send(res);
+        }
+        None => {
+            if let Err(err) = res {
+                error!("Failed to write to Streamdeck: {:?}", err);
+            }
+        }
+    }
+}
+
+/// Remember `frame` as the frame of `key` and show it on `key` and all keys
+/// mirroring it. Keys covered by the border are only remembered.
+fn set_frame(
+    deck: &mut streamdeck::StreamDeck,
+    state: &mut WorkerState,
+    key: u8,
+    frame: Frame,
+) -> result::Result<(), streamdeck::Error> {
+    let mut keys = vec![key];
+    if let Some(mirrors) = state.mirrors.get(&key) {
+        keys.extend(mirrors.iter().filter(|mirror| **mirror != key));
+    }
+
+    let mut res = Ok(());
+    for key in keys {
+        if !state.is_covered(deck.kind(), key) {
+            res = res.and(show_frame(deck, &mut state.displayed, key, &frame));
+        }
+
+        state.frames.insert(key, frame.clone());
+    }
+
+    res
+}
+
+/// Paint the perimeter keys in the color of the border, or repaint their
+/// remembered frames if no border is set.
+fn paint_border(
+    deck: &mut streamdeck::StreamDeck,
+    state: &mut WorkerState,
+) -> result::Result<(), streamdeck::Error> {
+    for key in perimeter_keys(deck.kind()) {
+        let frame = match (&state.border, state.frames.get(&key)) {
+            (Some(color), _) => Frame::Color(color.clone()),
+            (None, Some(frame)) => frame.clone(),
+            (None, None) => Frame::Color(Color::from((0, 0, 0))),
+        };
+
+        show_frame(deck, &mut state.displayed, key, &frame)?;
+    }
+
+    Ok(())
+}
+
+/// Show the tiles of a standby image on the keys, or repaint the remembered
+/// frames and the border if `tiles` is `None`.
+fn show_standby(
+    deck: &mut streamdeck::StreamDeck,
+    state: &mut WorkerState,
+    tiles: Option<Vec<DynamicImage>>,
+) -> result::Result<(), streamdeck::Error> {
+    let kind = deck.kind();
+
+    match tiles {
+        Some(tiles) => {
+            state.standby = true;
+
+            for (key, tile) in tiles.into_iter().enumerate() {
+                let frame = Frame::Image(fit_to_key(kind, tile));
+                show_frame(deck, &mut state.displayed, key as u8, &frame)?;
+            }
+        }
+        None => {
+            state.standby = false;
+
+            for key in 0..kind.keys() {
+                if state.is_covered(kind, key) {
+                    continue;
+                }
+
+                let frame = match state.frames.get(&key) {
+                    Some(frame) => frame.clone(),
+                    None => Frame::Color(Color::from((0, 0, 0))),
+                };
+                show_frame(deck, &mut state.displayed, key, &frame)?;
+            }
+
+            if state.border.is_some() {
+                paint_border(deck, state)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `frame` to `key` unless the same content is already displayed on
+/// it. `displayed` holds the hashes of the displayed content.
+fn show_frame(
+    deck: &mut streamdeck::StreamDeck,
+    displayed: &mut HashMap<u8, u64>,
+    key: u8,
+    frame: &Frame,
+) -> result::Result<(), streamdeck::Error> {
+    let hash = frame.content_hash();
+    if displayed.get(&key) == Some(&hash) {
+        return Ok(());
+    }
+
+    // Forget the displayed content if the write fails midway.
+    displayed.remove(&key);
+
+    match frame {
+        Frame::Color(color) => deck.set_button_rgb(key, &color.clone().into())?,
+        Frame::Image(image) => deck.set_button_image(key, image.clone())?,
+    }
+
+    displayed.insert(key, hash);
+    Ok(())
+}
+
+/// Returns a hash of the dimensions and pixels of `image`.
+fn image_hash(image: &DynamicImage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (image.dimensions(), image.color()).hash(&mut hasher);
+    image.as_bytes().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Resize `image` to the key resolution of the model `kind` if it doesn't
+/// match already.
+///
+/// The per-model rotation and mirroring is applied by
+/// `streamdeck::StreamDeck::set_button_image` using the same `Kind`, so
+/// images must be passed upright and unmirrored.
+fn fit_to_key(kind: Kind, image: DynamicImage) -> DynamicImage {
+    let (width, height) = kind.image_size();
+    let (width, height) = (width as u32, height as u32);
+
+    match image.dimensions() == (width, height) {
+        true => image,
+        false => image.resize_exact(width, height, FilterType::Triangle),
+    }
+}
+
+/// Reset the device and clear all keys. The reset restores the default
+/// brightness of the device, so the last brightness set is applied again.
+fn reset(
+    deck: &mut streamdeck::StreamDeck,
+    state: &mut WorkerState,
+) -> result::Result<(), streamdeck::Error> {
+    deck.reset()?;
+    state.frames.clear();
+    state.displayed.clear();
+    state.dimmed.clear();
+    // Mirrors are set up again by the buttons when they are initialized.
+    state.mirrors.clear();
+    state.standby = false;
+
+    // The device shows the Elgato logo after a reset.
+    let black = Frame::Color(Color::from((0, 0, 0)));
+    for key in 0..deck.kind().keys() {
+        show_frame(deck, &mut state.displayed, key, &black)?;
+    }
+
+    if state.border.is_some() {
+        paint_border(deck, state)?;
+    }
+
+    if let Some(brightness) = state.effective_brightness() {
+        deck.set_brightness(brightness)?;
+    }
+
+    Ok(())
+}
+
+/// A Streamdeck device returned by [`StreamDeck::enumerate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial: Option<String>,
+    /// The human-readable model name.
+    pub model: &'static str,
+}
+
+/// Returns the first device in `devices` matching `vid`, `pid` and `serial`.
+/// Criteria that are `None` match any device.
+pub fn select_device<'a>(
+    devices: &'a [DeviceInfo],
+    vid: Option<u16>,
+    pid: Option<u16>,
+    serial: Option<&str>,
+) -> Option<&'a DeviceInfo> {
+    devices.iter().find(|device| {
+        vid.map_or(true, |vid| device.vid == vid)
+            && pid.map_or(true, |pid| device.pid == pid)
+            && serial.map_or(true, |serial| device.serial.as_deref() == Some(serial))
+    })
+}
+
+/// Returns the Streamdeck model with the product id `pid`, or `None` if the
+/// product id is not a supported Streamdeck.
+pub fn kind_from_pid(pid: u16) -> Option<Kind> {
+    match pid {
+        pids::ORIGINAL => Some(Kind::Original),
+        pids::ORIGINAL_V2 => Some(Kind::OriginalV2),
+        pids::MINI => Some(Kind::Mini),
+        pids::XL => Some(Kind::Xl),
+        _ => None,
+    }
+}
+
+/// Returns the model with the product id `pid`, or `None` if the product id
+/// is not a supported device.
+pub fn model_from_pid(pid: u16) -> Option<Model> {
+    match pid {
+        PEDAL_PID => Some(Model::Pedal),
+        pid => kind_from_pid(pid).map(Model::Deck),
+    }
+}
+
+/// A supported Elgato device.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Model {
+    /// A Streamdeck with a display in every key.
+    Deck(Kind),
+    /// The Stream Deck Pedal, a foot switch with three keys and no display.
+    Pedal,
+}
+
+impl Model {
+    /// Returns the number of keys.
+    pub fn keys(&self) -> u8 {
+        match self {
+            Self::Deck(kind) => kind.keys(),
+            Self::Pedal => 3,
+        }
+    }
+
+    /// Returns the number of key columns.
+    pub fn columns(&self) -> u8 {
+        match self {
+            Self::Deck(kind) => kind_columns(*kind),
+            Self::Pedal => 3,
+        }
+    }
+
+    /// Returns the size of a key image in pixels. The pedal has no display,
+    /// images for it are rendered at the size of the original Streamdeck
+    /// and discarded by the worker thread, so buttons work unchanged.
+    pub fn image_size(&self) -> (u32, u32) {
+        let (width, height) = match self {
+            Self::Deck(kind) => kind.image_size(),
+            Self::Pedal => Kind::Original.image_size(),
+        };
+
+        (width as u32, height as u32)
+    }
+}
+
+/// Returns the model name of the device with the product id `pid`, or
+/// `None` if the product id is not a supported device.
+fn model_name(pid: u16) -> Option<&'static str> {
+    match pid {
+        PEDAL_PID => Some("Stream Deck Pedal"),
+        pids::ORIGINAL => Some("Stream Deck"),
+        pids::ORIGINAL_V2 => Some("Stream Deck V2"),
+        pids::MINI => Some("Stream Deck Mini"),
+        pids::XL => Some("Stream Deck XL"),
+        _ => None,
+    }
+}
+
+/// A value created on first use and shared by all users afterwards.
+struct Shared<T> {
+    value: Mutex<Option<T>>,
+}
+
+impl<T> Shared<T> {
+    const fn new() -> Self {
+        Self {
+            value: Mutex::new(None),
+        }
+    }
+
+    /// Calls `f` with the value, creating it using `init` first if it
+    /// doesn't exist yet. If `init` fails it is tried again on the next call.
+    fn with<I, F, R, E>(&self, init: I, f: F) -> result::Result<R, E>
+    where
+        I: FnOnce() -> result::Result<T, E>,
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut value = self.value.lock().unwrap();
+        if value.is_none() {
+            *value = Some(init()?);
+        }
+
+        Ok(f(value.as_mut().unwrap()))
+    }
+}
+
+/// Open the Streamdeck matching `vid`, `pid` and `serial` using the shared
+/// [`HID_API`].
+fn open_deck(
+    vid: u16,
+    pid: u16,
+    serial: Option<&str>,
+) -> result::Result<streamdeck::StreamDeck, streamdeck::Error> {
+    HID_API.with(HidApi::new, |api| {
+        streamdeck::StreamDeck::connect_with_hid(api, vid, pid, serial.map(String::from))
+    })?
+}
+
+/// Open the Stream Deck Pedal matching `vid`, `pid` and `serial` using the
+/// shared [`HID_API`].
+fn open_pedal(
+    vid: u16,
+    pid: u16,
+    serial: Option<&str>,
+) -> result::Result<Pedal, streamdeck::Error> {
+    let device = HID_API.with(HidApi::new, |api| match serial {
+        Some(serial) => api.open_serial(vid, pid, serial),
+        None => api.open(vid, pid),
+    })??;
+
+    Ok(Pedal { device })
+}
+
+/// A device owned by the worker thread.
+trait Hardware: Send + 'static {
+    fn model(&self) -> Model;
+
+    fn serial(&mut self) -> result::Result<String, streamdeck::Error>;
+
+    /// Switch between blocking and non-blocking reads.
+    fn set_blocking(&mut self, blocking: bool) -> result::Result<(), streamdeck::Error>;
+
+    /// Read the button states. Returns [`streamdeck::Error::NoData`] if there
+    /// was no input.
+    fn read_buttons(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> result::Result<Vec<u8>, streamdeck::Error>;
+
+    /// Write `msg` to the device and report the result back.
+    fn handle(&mut self, state: &mut WorkerState, msg: Message);
+
+    /// Write the state remembered by the worker to a reconnected device.
+    fn restore(&mut self, state: &mut WorkerState);
+}
+
+impl Hardware for streamdeck::StreamDeck {
+    fn model(&self) -> Model {
+        Model::Deck(self.kind())
+    }
+
+    fn serial(&mut self) -> result::Result<String, streamdeck::Error> {
+        streamdeck::StreamDeck::serial(self)
+    }
+
+    fn set_blocking(&mut self, blocking: bool) -> result::Result<(), streamdeck::Error> {
+        streamdeck::StreamDeck::set_blocking(self, blocking)
+    }
+
+    fn read_buttons(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> result::Result<Vec<u8>, streamdeck::Error> {
+        streamdeck::StreamDeck::read_buttons(self, timeout)
+    }
+
+    fn handle(&mut self, state: &mut WorkerState, msg: Message) {
+        handle_message(self, state, msg);
+    }
+
+    fn restore(&mut self, state: &mut WorkerState) {
+        restore_device(self, state);
+    }
+}
+
+/// The Stream Deck Pedal. It has no display, so all messages writing to the
+/// keys succeed without doing anything.
+struct Pedal {
+    device: hidapi::HidDevice,
+}
+
+impl Hardware for Pedal {
+    fn model(&self) -> Model {
+        Model::Pedal
+    }
+
+    fn serial(&mut self) -> result::Result<String, streamdeck::Error> {
+        let serial = self.device.get_serial_number_string()?;
+        Ok(serial.unwrap_or_default())
+    }
+
+    fn set_blocking(&mut self, blocking: bool) -> result::Result<(), streamdeck::Error> {
+        Ok(self.device.set_blocking_mode(blocking)?)
+    }
+
+    fn read_buttons(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> result::Result<Vec<u8>, streamdeck::Error> {
+        let mut report = [0; PEDAL_REPORT_LEN];
+
+        let len = match timeout {
+            Some(timeout) => self
+                .device
+                .read_timeout(&mut report, timeout.as_millis() as i32)?,
+            None => self.device.read(&mut report)?,
+        };
+
+        match len {
+            0 => Err(streamdeck::Error::NoData),
+            len => Ok(pedal_keys(&report[..len])),
+        }
+    }
+
+    fn handle(&mut self, _: &mut WorkerState, msg: Message) {
+        debug!("Ignoring write to the Stream Deck Pedal without display");
+
+        if let Some(ack) = msg.into_ack() {
+            let _ = ack.send(Ok(()));
+        }
+    }
+
+    fn restore(&mut self, _: &mut WorkerState) {}
+}
+
+/// Returns the key states of an input report of the pedal. Reports that are
+/// too short return fewer states and are rejected by [`is_valid_report`].
+fn pedal_keys(report: &[u8]) -> Vec<u8> {
+    report.get(4..).unwrap_or_default().to_vec()
+}
+
+/// The worker thread owning the device. Alternates between handling all
+/// queued messages and reading button input using `read_strategy`. Button
+/// states are forwarded to `events` as soon as they differ from the last
+/// states read and published to `snapshot`. The worker updates `heartbeat` on
+/// every iteration and reconnects using `reconnect` if reading from the
+/// device fails. The worker exits if reconnecting fails within `retry`.
+#[allow(clippy::too_many_arguments)]
+fn worker<D, F>(
+    mut deck: D,
+    mut reconnect: F,
+    read_strategy: ReadStrategy,
+    retry: Retry,
+    rx: mpsc::Receiver<Message>,
+    events: UnboundedSender<Vec<u8>>,
+    heartbeat: Arc<Mutex<Instant>>,
+    snapshot: Arc<Mutex<Vec<u8>>>,
+) where
+    D: Hardware,
+    F: FnMut() -> result::Result<D, streamdeck::Error>,
+{
+    let mut state = WorkerState::default();
+    let mut last_keys = Vec::new();
+
+    if let Err(err) = read_strategy.configure(&mut deck) {
+        error!("Failed to set the read mode of the Streamdeck: {:?}", err);
+    }
+
+    loop {
+        *heartbeat.lock().unwrap() = Instant::now();
+
+        let mut batch = Vec::new();
+        let disconnected = loop {
+            match rx.try_recv() {
+                Ok(msg) => batch.push(msg),
+                Err(TryRecvError::Empty) => break false,
+                // All `StreamDeck` handles are dropped.
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        for msg in coalesce(batch) {
+            deck.handle(&mut state, msg);
+        }
+        if disconnected {
+            return;
+        }
+
+        match read_strategy.read(&mut deck) {
+            Ok(Some(keys)) => {
+                if !is_valid_report(deck.model(), &keys) {
+                    warn!("Ignoring malformed button report {:?}", keys);
+                    continue;
+                }
+
+                if keys == last_keys {
+                    continue;
+                }
+
+                last_keys = keys.clone();
+                *snapshot.lock().unwrap() = keys.clone();
+                if events.send(keys).is_err() {
+                    return;
+                }
+            }
+            // No input within the timeout.
+            Ok(None) => (),
+            Err(err) => {
+                error!("Failed to read from Streamdeck, reconnecting: {:?}", err);
+
+                // No buttons can be held on a disconnected device.
+                snapshot.lock().unwrap().clear();
+
+                // Close the lost device before opening it again.
+                drop(deck);
+
+                deck = match reconnect_device(&mut reconnect, &retry, &heartbeat) {
+                    Some(deck) => deck,
+                    None => {
+                        error!("Giving up reconnecting to Streamdeck");
+                        return;
+                    }
+                };
+                if let Err(err) = read_strategy.configure(&mut deck) {
+                    error!("Failed to set the read mode of the Streamdeck: {:?}", err);
+                }
+                deck.restore(&mut state);
+
+                info!("Reconnected to Streamdeck");
+            }
+        }
+    }
+}
+
+/// Drop the writes of colors and images in `batch` that are replaced by a
+/// later write to the same key, so a burst of updates doesn't back up
+/// behind slow USB writes. Writes waiting for an acknowledgement are always
+/// kept.
+fn coalesce(batch: Vec<Message>) -> Vec<Message> {
+    let mut replaced = HashSet::new();
+    let mut messages = Vec::with_capacity(batch.len());
+
+    for msg in batch.into_iter().rev() {
+        match &msg {
+            Message::SetColor(key, _, ack) | Message::SetImage(key, _, ack)
+                if !replaced.insert(*key) && ack.is_none() =>
+            {
+                continue
+            }
+            _ => messages.push(msg),
+        }
+    }
+
+    messages.reverse();
+    messages
+}
+
+/// Call `reconnect` with an exponential backoff until it succeeds. Returns
+/// `None` once the attempts or the timeout of `retry` are exhausted. Keeps
+/// the `heartbeat` alive while waiting between the attempts, so the
+/// watchdog doesn't exit while retrying.
+fn reconnect_device<D, F>(reconnect: &mut F, retry: &Retry, heartbeat: &Mutex<Instant>) -> Option<D>
+where
+    F: FnMut() -> result::Result<D, streamdeck::Error>,
+{
+    let start = Instant::now();
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        *heartbeat.lock().unwrap() = Instant::now();
+
+        match reconnect() {
+            Ok(deck) => return Some(deck),
+            Err(err) => debug!(
+                "Failed to reconnect to Streamdeck (attempt {}/{}): {:?}",
+                attempt, retry.max_attempts, err
+            ),
+        }
+
+        if attempt >= retry.max_attempts || start.elapsed() + delay > retry.timeout {
+            return None;
+        }
+
+        sleep_alive(delay, heartbeat);
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+        attempt += 1;
+    }
+}
+
+/// Sleep for `duration`, refreshing `heartbeat` at least every
+/// [`WATCHDOG_INTERVAL`].
+fn sleep_alive(duration: Duration, heartbeat: &Mutex<Instant>) {
+    let end = Instant::now() + duration;
+
+    loop {
+        *heartbeat.lock().unwrap() = Instant::now();
+
+        let remaining = end.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+        std::thread::sleep(remaining.min(WATCHDOG_INTERVAL));
+    }
+}
+
+/// Write the remembered brightness and key frames to a reconnected device.
+fn restore_device(deck: &mut streamdeck::StreamDeck, state: &mut WorkerState) {
+    let mut res = Ok(());
+
+    if let Some(brightness) = state.effective_brightness() {
+        res = res.and(deck.set_brightness(brightness));
+    }
+
+    // Nothing is displayed on the new device yet.
+    state.displayed.clear();
+    for (key, frame) in &state.frames {
+        res = res.and(show_frame(deck, &mut state.displayed, *key, frame));
+    }
+    if state.border.is_some() {
+        res = res.and(paint_border(deck, state));
+    }
+
+    if let Err(err) = res {
+        error!("Failed to restore Streamdeck: {:?}", err);
+    }
+}
+
+#[async_trait::async_trait]
+pub trait Button: Send + Sync {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()>;
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()>;
+
+    /// Called when the key is released after being held down for at least
+    /// [`LONG_PRESS`]. `on_click` was already called when the key was
+    /// pressed. Does nothing by default.
+    async fn on_long_press(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called whenever the key is released with the time it was held down,
+    /// e.g. to seek further the longer the key is held. Called before
+    /// `on_long_press`. Does nothing by default.
+    async fn on_hold_release(
+        &mut self,
+        _state: &mut State,
+        _key: Key,
+        _held: Duration,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called on every button in the interval configured in [`State::tick`].
+    /// Runs for every button on the main loop, so it must be cheap. Does
+    /// nothing by default.
+    async fn on_tick(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the button is removed from the key, i.e. when it is
+    /// replaced using [`State::rebind`], the buttons are reloaded or the
+    /// main loop shuts down. Buttons spawning tasks in `init` should stop
+    /// them here. On shutdown all buttons are deinitialized before the
+    /// services are stopped. Does nothing by default.
+    async fn deinit(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the name of the button used in logs. Defaults to the name of
+    /// the type without its module path, e.g. `SaveReplayBufferButton`.
+    fn name(&self) -> &str {
+        let name = std::any::type_name::<Self>();
+
+        // Keep the module paths in generic parameters.
+        let end = name.find('<').unwrap_or(name.len());
+        let start = name[..end].rfind("::").map_or(0, |index| index + 2);
+        &name[start..]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Key {
+    key: u8,
+    deck: StreamDeck,
+    /// The span the key belongs to if its button is shared.
+    span: Option<Span>,
+}
+
+impl Key {
+    fn new(key: u8, deck: StreamDeck) -> Self {
+        Self {
+            key,
+            deck,
+            span: None,
+        }
+    }
+
+    /// Returns the span of keys sharing the button, or `None` if the button
+    /// is only placed on this key.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Returns the handles of all keys sharing the button, including this
+    /// one. Returns only this key if the button is not shared.
+    pub fn span_keys(&self) -> Vec<Key> {
+        match self.span {
+            Some(span) => span
+                .keys(self.deck.columns())
+                .into_iter()
+                .map(|key| Key {
+                    key,
+                    deck: self.deck.clone(),
+                    span: Some(span),
+                })
+                .collect(),
+            None => vec![self.clone()],
+        }
+    }
+
+    /// Show the slice of `image` covering this key when `image` is
+    /// stretched over the whole span. The gaps between the physical keys
+    /// are not accounted for. Shows the whole image if the button is not
+    /// shared.
+    pub fn image_slice(&self, image: &DynamicImage) -> Result<()> {
+        let (width, height) = self.deck.image_size();
+
+        let span = match self.span {
+            Some(span) => span,
+            None => return self.image(image.resize_exact(width, height, FilterType::Triangle)),
+        };
+
+        let row = (self.key / self.deck.columns()).saturating_sub(span.row) as u32;
+        let col = (self.key % self.deck.columns()).saturating_sub(span.col) as u32;
+
+        let image = image.resize_exact(
+            width * span.columns as u32,
+            height * span.rows as u32,
+            FilterType::Triangle,
+        );
+        self.image(image.crop_imm(col * width, row * height, width, height))
+    }
+
+    /// Returns `true` if the key is currently held down.
+    ///
+    /// The button states are read by the worker thread of the device, so
+    /// the result lags behind the device by up to one read of the
+    /// [`ReadStrategy`]: the timeout of a blocking read (25ms by default) or
+    /// the interval of polling. A button repeating an action while the key
+    /// is held should sleep between checks rather than spin. Note that no
+    /// other key is dispatched until `on_click` returns:
+    ///
+    /// ```ignore
+    /// while key.is_pressed() {
+    ///     client.volume_up().await?;
+    ///     tokio::time::sleep(Duration::from_millis(100)).await;
+    /// }
+    /// ```
+    pub fn is_pressed(&self) -> bool {
+        self.deck.is_pressed(self.key)
+    }
+
+    /// Returns the deck the key belongs to.
+    pub fn deck(&self) -> &StreamDeck {
+        &self.deck
+    }
+
+    /// Set the brightness of the deck the key belongs to in percent.
+    pub fn set_brightness(&self, brightness: u8) -> Result<()> {
+        self.deck.set_brightness(brightness)
+    }
+
+    /// Returns the resolution of the key image in pixels.
+    pub fn image_size(&self) -> (u32, u32) {
+        self.deck.image_size()
+    }
+
+    /// Set the key to a constant color.
+    pub fn color<T>(&self, color: T) -> Result<()>
+    where
+        T: Into<Color>,
+    {
+        self.deck
+            .send(Message::SetColor(self.key, color.into(), None))
+    }
+
+    /// Set the key to a constant color and wait until it was written to
+    /// the device.
+    pub async fn color_confirmed<T>(&self, color: T) -> Result<()>
+    where
+        T: Into<Color>,
+    {
+        let color = color.into();
+        self.deck
+            .request(|ack| Message::SetColor(self.key, color, ack))
+            .await
+    }
+
+    /// Temporarily set the key to a constant color. The previous color or
+    /// image of the key is repainted by [`Key::restore`]. Replaces an image
+    /// shown by [`Key::flash`].
+    pub fn overlay<T>(&self, color: T) -> Result<()>
+    where
+        T: Into<Color>,
+    {
+        self.cancel_flash();
+
+        let frame = Frame::Color(color.into());
+        self.deck.send(Message::Overlay(self.key, frame, None))
+    }
+
+    /// Show `image` on the key for `duration`, then repaint the color or
+    /// image set on the key, e.g. to confirm an action with a checkmark.
+    /// Colors and images set while the image is shown replace it right away.
+    /// Flashing the key again or setting an overlay cancels the repaint.
+    pub fn flash(&self, image: DynamicImage, duration: Duration) -> Result<()> {
+        self.cancel_flash();
+
+        let frame = Frame::Image(self.deck.fit_image(image));
+        self.deck.send(Message::Overlay(self.key, frame, None))?;
+
+        let deck = self.deck.clone();
+        let key = self.key;
+        let task = task::spawn(async move {
+            time::sleep(duration).await;
+            let _ = deck.send(Message::Restore(key, None));
+        });
+
+        self.deck.flashes.lock().unwrap().insert(self.key, task);
+        Ok(())
+    }
+
+    /// Repaint the last color or image set on the key, removing an overlay.
+    /// The key is cleared if nothing was set on it yet.
+    pub fn restore(&self) -> Result<()> {
+        self.cancel_flash();
+        self.deck.send(Message::Restore(self.key, None))
+    }
+
+    /// Abort the pending repaint of an image shown by [`Key::flash`].
+    fn cancel_flash(&self) {
+        if let Some(task) = self.deck.flashes.lock().unwrap().remove(&self.key) {
+            task.abort();
+        }
+    }
+
+    /// Set the key image. Images of any size are scaled to the key
+    /// resolution, see [`StreamDeck::fit_image`].
+    pub fn image(&self, image: DynamicImage) -> Result<()> {
+        let image = self.deck.fit_image(image);
+        self.deck.send(Message::SetImage(self.key, image, None))
+    }
+
+    /// Set the key image from a buffer of `width`x`height` pixels, every
+    /// pixel made of 4 bytes in RGBA order. The image is scaled to the key
+    /// resolution like in [`Key::image`]. Returns an [`InvalidBuffer`] error
+    /// if the image is empty or the length of `bytes` doesn't match the
+    /// dimensions.
+    pub fn image_rgba(&self, width: u32, height: u32, bytes: &[u8]) -> Result<()> {
+        let expected = width as usize * height as usize * 4;
+        if expected == 0 || bytes.len() != expected {
+            return Err(InvalidBuffer {
+                width,
+                height,
+                len: bytes.len(),
+            }
+            .into());
+        }
+
+        // The length is checked above.
+        let image = RgbaImage::from_raw(width, height, bytes.to_vec()).unwrap();
+        self.image(DynamicImage::ImageRgba8(image))
+    }
+
+    /// Set the key image and wait until it was written to the device.
+    pub async fn image_confirmed(&self, image: DynamicImage) -> Result<()> {
+        let image = self.deck.fit_image(image);
+        self.deck
+            .request(|ack| Message::SetImage(self.key, image, ack))
+            .await
+    }
+
+    /// Load the image at `path` and set it as the key image. The image is
+    /// scaled to the key resolution, cropping the edges of non-square
+    /// images. Loaded images are cached, see [`State::get_icon`].
+    pub fn image_from_path<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let image = self
+            .deck
+            .icons
+            .image(path.as_ref(), self.deck.image_size())?;
+        self.image(image)
+    }
+
+    /// Cycle through `frames`, showing every frame for `frame_delay`. The
+    /// animation runs until the returned handle is dropped or stopped.
+    pub fn animate(&self, frames: Vec<DynamicImage>, frame_delay: Duration) -> AnimationHandle {
+        let frames = frames
+            .into_iter()
+            .map(|frame| (frame, frame_delay))
+            .collect();
+
+        AnimationHandle::spawn(self.clone(), frames)
+    }
+
+    /// Play the animated GIF at `path` in a loop using the frame delays of
+    /// the GIF. The frames are scaled to the key resolution like in
+    /// [`Key::image_from_path`] and cached.
+    pub fn animate_from_gif<P>(&self, path: P) -> Result<AnimationHandle>
+    where
+        P: AsRef<Path>,
+    {
+        let frames = self.deck.icons.gif(path.as_ref(), self.deck.image_size())?;
+        Ok(AnimationHandle::spawn(self.clone(), frames))
+    }
+
+    /// Render `text` in the color `fg` on the background `bg`. Long text is
+    /// wrapped and cut off if it doesn't fit on the key.
+    pub fn text<T, U>(&self, text: &str, fg: T, bg: U) -> Result<()>
+    where
+        T: Into<Color>,
+        U: Into<Color>,
+    {
+        let (width, height) = self.deck.image_size();
+
+        self.image(render_text(width, height, text, fg.into(), bg.into()))
+    }
+
+    /// Render the single character `ch` in the color `color` as large as
+    /// possible onto the key. Only the symbols of the bundled DejaVu Sans
+    /// font are available, see [`render_glyph`]. Returns an error if the
+    /// font has no glyph for `ch`, which includes most emoji.
+    pub fn glyph<T>(&self, ch: char, color: T) -> Result<()>
+    where
+        T: Into<Color>,
+    {
+        let (width, height) = self.deck.image_size();
+
+        self.image(render_glyph(
+            width,
+            height,
+            ch,
+            color.into(),
+            Color::from((0, 0, 0)),
+        )?)
+    }
+
+    /// Render a horizontal bar filled to `percent` (clamped to 0-100) with
+    /// the color `fg` on the background `bg`.
+    pub fn progress_bar<T, U>(&self, percent: u8, fg: T, bg: U) -> Result<()>
+    where
+        T: Into<Color>,
+        U: Into<Color>,
+    {
+        let (width, height) = self.deck.image_size();
+
+        self.image(render_progress_bar(
+            width,
+            height,
+            percent,
+            fg.into(),
+            bg.into(),
+        ))
+    }
+}
+
+/// A running animation started by [`Key::animate`] or
+/// [`StreamDeck::pulse_border`]. Dropping the handle stops the animation,
+/// leaving the current frame on the keys.
+#[derive(Debug)]
+pub struct AnimationHandle {
+    task: Option<task::JoinHandle<()>>,
+}
+
+impl AnimationHandle {
+    fn spawn(key: Key, frames: Vec<(DynamicImage, Duration)>) -> Self {
+        let task = task::spawn(async move {
+            if frames.is_empty() {
+                return;
+            }
+
+            for (frame, delay) in frames.iter().cycle() {
+                if key.image(frame.clone()).is_err() {
+                    break;
+                }
+
+                time::sleep(*delay).await;
+            }
+        });
+
+        Self { task: Some(task) }
+    }
+
+    /// Stop the animation and wait until it stopped. No more frames are set
+    /// once this returns.
+    pub async fn stop(mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for AnimationHandle {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Render a progress bar image of the size `width`x`height`. The bar is
+/// vertically centered, has a thin border in the color `fg` and is filled
+/// from the left to `percent`.
+fn render_progress_bar(width: u32, height: u32, percent: u8, fg: Color, bg: Color) -> DynamicImage {
+    const BORDER: u32 = 2;
+
+    let percent = percent.min(100) as u32;
+    let (fg, bg) = (Rgb([fg.r, fg.g, fg.b]), Rgb([bg.r, bg.g, bg.b]));
+
+    // The outer bounds of the bar including the border.
+    let (left, right) = (width / 8, width - width / 8);
+    let (top, bottom) = (height / 3, height - height / 3);
+
+    // The end of the filled area of the bar.
+    let inner_width = right - left - 2 * BORDER;
+    let filled = left + BORDER + inner_width * percent / 100;
+
+    let image = RgbImage::from_fn(width, height, |x, y| {
+        if x < left || x >= right || y < top || y >= bottom {
+            return bg;
+        }
+
+        let is_border =
+            x < left + BORDER || x >= right - BORDER || y < top + BORDER || y >= bottom - BORDER;
+
+        if is_border || x < filled {
+            fg
+        } else {
+            bg
+        }
+    });
+
+    DynamicImage::ImageRgb8(image)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from(t: (u8, u8, u8)) -> Self {
+        Self {
+            r: t.0,
+            g: t.1,
+            b: t.2,
+        }
+    }
+}
+
+impl Color {
+    /// Returns one of the 16 basic HTML colors by its case-insensitive
+    /// name, e.g. `red` or `Teal`. Returns `None` for other names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let value = match name.to_ascii_lowercase().as_str() {
+            "white" => 0xffffff,
+            "silver" => 0xc0c0c0,
+            "gray" | "grey" => 0x808080,
+            "black" => 0x000000,
+            "red" => 0xff0000,
+            "maroon" => 0x800000,
+            "yellow" => 0xffff00,
+            "olive" => 0x808000,
+            "lime" => 0x00ff00,
+            "green" => 0x008000,
+            "aqua" => 0x00ffff,
+            "teal" => 0x008080,
+            "blue" => 0x0000ff,
+            "navy" => 0x000080,
+            "fuchsia" => 0xff00ff,
+            "purple" => 0x800080,
+            _ => return None,
+        };
+
+        Some(Self::from(value))
+    }
+}
+
+/// Converts a color in the `0xRRGGBB` form. The highest byte is ignored.
+impl From<u32> for Color {
+    fn from(t: u32) -> Self {
+        Self {
+            r: (t >> 16) as u8,
+            g: (t >> 8) as u8,
+            b: t as u8,
+        }
+    }
+}
+
+/// The length of a raw image buffer passed to [`Key::image_rgba`] doesn't
+/// match the dimensions of the image, or the image has no pixels.
+#[derive(Debug)]
+pub struct InvalidBuffer {
+    width: u32,
+    height: u32,
+    len: usize,
+}
+
+impl Display for InvalidBuffer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid image buffer of {} bytes for a {}x{} RGBA image",
+            self.len, self.width, self.height
+        )
+    }
+}
+
+impl error::Error for InvalidBuffer {}
+
+/// The string is not a color in the `#RRGGBB` or `#RGB` form or the name of
+/// a basic color.
+#[derive(Debug)]
+pub struct InvalidColor(String);
+
+impl Display for InvalidColor {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid color {:?}, expected #RRGGBB, #RGB or a basic color name like red",
+            self.0
+        )
+    }
+}
+
+impl error::Error for InvalidColor {}
+
+/// Parses a color in the `#RRGGBB` or `#RGB` form, e.g. `#1e90ff`, or a
+/// name accepted by [`Color::from_name`].
+impl FromStr for Color {
+    type Err = InvalidColor;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let err = || InvalidColor(s.to_owned());
+
+        if let Some(color) = Self::from_name(s) {
+            return Ok(color);
+        }
+
+        let hex = s.strip_prefix('#').ok_or_else(err)?;
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+
+        let value = u32::from_str_radix(hex, 16).map_err(|_| err())?;
+        match hex.len() {
+            6 => Ok(Self::from(value)),
+            // Every digit is repeated, #1ef is #11eeff.
+            3 => {
+                let digit = |shift: u32| ((value >> shift) & 0xf) as u8 * 0x11;
+                Ok(Self::from((digit(8), digit(4), digit(0))))
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+impl TryFrom<&str> for Color {
+    type Error = InvalidColor;
+
+    fn try_from(s: &str) -> result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for streamdeck::Colour {
+    fn from(t: Color) -> Self {
+        Self {
+            r: t.r,
+            g: t.g,
+            b: t.b,
+        }
+    }
+}
+
+/// A button that does nothing.
+#[derive(Debug)]
+pub struct NullButton;
+
+impl Default for NullButton {
+    fn default() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl Button for NullButton {
+    async fn init(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A button that does nothing but show a color or an image, so unused keys
+/// can be styled uniformly.
+///
+/// Placed on a span using [`Layout::span`], the image is stretched over all
+/// keys of the span like a wallpaper.
+///
+/// [`Layout::span`]: crate::layout::Layout::span
+#[derive(Debug)]
+pub enum FillerButton {
+    Color(Color),
+    Image(DynamicImage),
+}
+
+impl FillerButton {
+    /// Creates a `FillerButton` showing `color`.
+    pub fn color<T>(color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        Self::Color(color.into())
+    }
+
+    /// Creates a `FillerButton` showing `image`.
+    pub fn image(image: DynamicImage) -> Self {
+        Self::Image(image)
+    }
+}
+
+#[async_trait::async_trait]
+impl Button for FillerButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        match self {
+            Self::Color(color) => key.color(color.clone()),
+            Self::Image(image) => key.image_slice(image),
+        }
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Shows the same color or image as another key of the deck, e.g. a large
+/// indicator for a small button. Pressing the key does nothing.
+#[derive(Debug)]
+pub struct MirrorButton {
+    source: u8,
+}
+
+impl MirrorButton {
+    /// Creates a new `MirrorButton` mirroring the key `source`.
+    pub fn new(source: u8) -> Self {
+        Self { source }
+    }
+}
+
+#[async_trait::async_trait]
+impl Button for MirrorButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.deck.mirror(self.source, key.key)
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.deck.unmirror(self.source, key.key)
+    }
+}
+
+/// The `on_click` closure of a [`FnButton`].
+type ClickFn = dyn for<'a> Fn(&'a mut State, Key) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>
+    + Send
+    + Sync;
+
+/// A button showing an icon and running a closure when pressed, for one-off
+/// actions that don't need their own [`Button`] type. The closure returns a
+/// boxed future so it can borrow the [`State`].
+///
+/// ```ignore
+/// let name = String::from("World");
+/// FnButton::new(icon, move |_, key| {
+///     let name = name.clone();
+///     Box::pin(async move {
+///         key.text(&format!("Hello {}", name), (255, 255, 255), (0, 0, 0))
+///     })
+/// });
+/// ```
+pub struct FnButton {
+    icon: DynamicImage,
+    on_click: Box<ClickFn>,
+}
+
+impl FnButton {
+    /// Creates a new `FnButton` showing `icon` and calling `on_click` when
+    /// pressed.
+    pub fn new<F>(icon: DynamicImage, on_click: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut State, Key) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            icon,
+            on_click: Box::new(on_click),
+        }
+    }
+}
+
+impl fmt::Debug for FnButton {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("FnButton").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl Button for FnButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        (self.on_click)(state, key).await
+    }
+}
+
+/// A [`StreamDeck`] without hardware for testing buttons.
+#[cfg(test)]
+pub mod mock {
+    use super::{Color, Frame, IconCache, Key, Message, Model, StreamDeck};
+    use image::DynamicImage;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use streamdeck::Kind;
+
+    /// A message written to a [`MockStreamDeck`].
+    #[derive(Clone, Debug)]
+    pub enum Written {
+        Color(u8, Color),
+        Image(u8, DynamicImage),
+        Brightness(u8),
+        IdleBrightness(Option<u8>),
+        Overlay(u8, Color),
+        OverlayImage(u8, DynamicImage),
+        Restore(u8),
+        Dim(u8),
+        Undim(u8),
+        Mirror(u8, u8),
+        Unmirror(u8, u8),
+        Border(Option<Color>),
+        Standby(bool),
+        Reset,
+    }
+
+    /// Records the messages sent to the deck instead of writing them to a
+    /// device. All messages are acknowledged as successful.
+    ///
+    /// ```ignore
+    /// let deck = MockStreamDeck::new(Kind::Original);
+    /// button.on_click(&mut State::new(), deck.key(0)).await?;
+    /// assert_eq!(deck.color(0), Some(Color::from((255, 0, 0))));
+    /// ```
+    pub struct MockStreamDeck {
+        deck: StreamDeck,
+        recorder: Arc<Recorder>,
+    }
+
+    impl MockStreamDeck {
+        /// Creates a new mock of the Streamdeck model `kind` with all keys
+        /// released.
+        pub fn new(kind: Kind) -> Self {
+            let (tx, rx) = mpsc::channel();
+            let recorder = Arc::new(Recorder {
+                rx: Mutex::new(rx),
+                written: Mutex::new(Vec::new()),
+            });
+
+            let deck = StreamDeck {
+                tx,
+                model: Model::Deck(kind),
+                serial: "MOCK".into(),
+                heartbeat: Arc::new(Mutex::new(Instant::now())),
+                exited: Arc::new(AtomicBool::new(false)),
+                keys: Arc::new(Mutex::new(vec![0; kind.keys() as usize])),
+                resized: Arc::new(Mutex::new(HashMap::new())),
+                icons: IconCache::default(),
+                flashes: Arc::new(Mutex::new(HashMap::new())),
+            };
+
+            // Acknowledge messages in the background for buttons waiting on
+            // confirmed writes.
+            {
+                let recorder = recorder.clone();
+                std::thread::spawn(move || while recorder.drain(Duration::from_millis(10)) {});
+            }
+
+            Self { deck, recorder }
+        }
+
+        /// Returns the handle of `key` passed to buttons.
+        pub fn key(&self, key: u8) -> Key {
+            Key::new(key, self.deck.clone())
+        }
+
+        /// Marks `key` as held down or released for [`Key::is_pressed`].
+        pub fn set_pressed(&self, key: u8, pressed: bool) {
+            self.deck.keys.lock().unwrap()[key as usize] = pressed as u8;
+        }
+
+        /// Returns all messages written so far.
+        pub fn written(&self) -> Vec<Written> {
+            self.recorder.drain(Duration::ZERO);
+            self.recorder.written.lock().unwrap().clone()
+        }
+
+        /// Returns the last color set on `key`, or `None` if the last write
+        /// to the key was not a color.
+        pub fn color(&self, key: u8) -> Option<Color> {
+            match self.last(key)? {
+                Written::Color(_, color) => Some(color),
+                _ => None,
+            }
+        }
+
+        /// Returns the last image set on `key`, or `None` if the last write
+        /// to the key was not an image.
+        pub fn image(&self, key: u8) -> Option<DynamicImage> {
+            match self.last(key)? {
+                Written::Image(_, image) => Some(image),
+                _ => None,
+            }
+        }
+
+        /// Returns the last brightness set.
+        pub fn brightness(&self) -> Option<u8> {
+            self.written().into_iter().rev().find_map(|msg| match msg {
+                Written::Brightness(brightness) => Some(brightness),
+                _ => None,
+            })
+        }
+
+        fn last(&self, key: u8) -> Option<Written> {
+            self.written().into_iter().rev().find(|msg| match msg {
+                Written::Color(k, _) | Written::Image(k, _) => *k == key,
+                _ => false,
+            })
+        }
+    }
+
+    struct Recorder {
+        rx: Mutex<mpsc::Receiver<Message>>,
+        written: Mutex<Vec<Written>>,
+    }
+
+    impl Recorder {
+        /// Record all queued messages, waiting up to `timeout` for the
+        /// first one. Returns `false` once all senders are dropped.
+        ///
+        /// Holding the receiver lock while recording guarantees that all
+        /// messages sent before the call are recorded when it returns.
+        fn drain(&self, timeout: Duration) -> bool {
+            let rx = self.rx.lock().unwrap();
+
+            match rx.recv_timeout(timeout) {
+                Ok(msg) => self.record(msg),
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+
+            while let Ok(msg) = rx.try_recv() {
+                self.record(msg);
+            }
+
+            true
+        }
+
+        fn record(&self, msg: Message) {
+            let (msg, ack) = match msg {
+                Message::SetColor(key, color, ack) => (Written::Color(key, color), ack),
+                Message::SetImage(key, image, ack) => (Written::Image(key, image), ack),
+                Message::SetBrightness(brightness, ack) => (Written::Brightness(brightness), ack),
+                Message::IdleBrightness(brightness) => (Written::IdleBrightness(brightness), None),
+                Message::Overlay(key, Frame::Color(color), ack) => {
+                    (Written::Overlay(key, color), ack)
+                }
+                Message::Overlay(key, Frame::Image(image), ack) => {
+                    (Written::OverlayImage(key, image), ack)
+                }
+                Message::Restore(key, ack) => (Written::Restore(key), ack),
+                Message::Dim(key) => (Written::Dim(key), None),
+                Message::Undim(key) => (Written::Undim(key), None),
+                Message::Mirror(source, key) => (Written::Mirror(source, key), None),
+                Message::Unmirror(source, key) => (Written::Unmirror(source, key), None),
+                Message::Border(color) => (Written::Border(color), None),
+                Message::Standby(tiles) => (Written::Standby(tiles.is_some()), None),
+                Message::Reset(ack) => (Written::Reset, ack),
+            };
+
+            self.written.lock().unwrap().push(msg);
+            if let Some(ack) = ack {
+                let _ = ack.send(Ok(()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockStreamDeck, Written};
+    use super::{
+        coalesce, fit_to_key, is_valid_report, pedal_keys, perimeter_keys, pressed_keys,
+        reconnect_device, select_device, sleep_alive, watchdog, Button, ButtonWrapper, Color,
+        DeviceInfo, FillerButton, FnButton, IconCache, Key, Message, MirrorButton, Model,
+        NullButton, ReadStrategy, Result, Retry, Shared, Span, State, ICON_CACHE_SIZE,
+    };
+    use image::{DynamicImage, GenericImageView};
+    use std::convert::TryFrom;
+    use std::result;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use streamdeck::Kind;
+    use tokio::time;
+
+    #[test]
+    fn test_button_name() {
+        assert_eq!(NullButton.name(), "NullButton");
+
+        struct Wrapper<T>(T);
+
+        #[async_trait::async_trait]
+        impl<T: Send + Sync> Button for Wrapper<T> {
+            async fn init(&mut self, _: &mut State, _: Key) -> Result<()> {
+                Ok(())
+            }
+
+            async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        assert_eq!(
+            Wrapper(NullButton).name(),
+            "Wrapper<streamdeck::core::NullButton>"
+        );
+    }
+
+    #[test]
+    fn test_button_wrapper_replace() {
+        let mut wrapper = ButtonWrapper::new(Box::new(NullButton));
+        let other = wrapper.clone();
+
+        wrapper.replace(Box::new(FillerButton::color((255, 0, 0))));
+        assert_eq!(wrapper.name(), "FillerButton");
+        assert_eq!(other.name(), "NullButton");
+    }
+
+    #[test]
+    fn test_shared_two_devices() {
+        // Like `HidApi`, only a single `Api` may exist at a time.
+        static EXISTS: AtomicBool = AtomicBool::new(false);
+
+        struct Api;
+
+        impl Api {
+            fn new() -> result::Result<Self, &'static str> {
+                match EXISTS.swap(true, Ordering::SeqCst) {
+                    false => Ok(Self),
+                    true => Err("already initialized"),
+                }
+            }
+        }
+
+        let shared = Shared::new();
+        let mut inits = 0;
+
+        // Open two devices through the same `Api`.
+        for device in ["first", "second"] {
+            let opened = shared.with(
+                || {
+                    inits += 1;
+                    Api::new()
+                },
+                |_| device,
+            );
+            assert_eq!(opened, Ok(device));
+        }
+
+        assert_eq!(inits, 1);
+    }
+
+    #[test]
+    fn test_reconnect_gives_up() {
+        let retry = Retry {
+            max_attempts: 2,
+            timeout: Duration::from_secs(60),
+        };
+        let heartbeat = Mutex::new(Instant::now());
+
+        let mut attempts = 0;
+        let mut reconnect = || {
+            attempts += 1;
+            Err(streamdeck::Error::NoData)
+        };
+
+        assert!(
+            reconnect_device::<streamdeck::StreamDeck, _>(&mut reconnect, &retry, &heartbeat)
+                .is_none()
+        );
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_sleep_alive() {
+        let heartbeat = Mutex::new(Instant::now() - Duration::from_secs(60));
+        sleep_alive(Duration::from_millis(10), &heartbeat);
+        assert!(heartbeat.lock().unwrap().elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_stops_after_worker_exit() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let deck = deck.key(0).deck().clone();
+        *deck.heartbeat.lock().unwrap() = Instant::now() - Duration::from_secs(60);
+        deck.exited.store(true, Ordering::SeqCst);
+
+        let res = time::timeout(
+            Duration::from_secs(5),
+            watchdog(deck, ReadStrategy::default()),
+        )
+        .await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let (tx, _rx) = tokio::sync::oneshot::channel();
+        let batch = vec![
+            Message::SetColor(0, Color::from(1), None),
+            Message::SetImage(1, DynamicImage::new_rgb8(1, 1), None),
+            Message::SetImage(0, DynamicImage::new_rgb8(1, 1), None),
+            Message::SetBrightness(50, None),
+            Message::SetColor(1, Color::from(2), Some(tx)),
+            Message::SetColor(0, Color::from(3), None),
+        ];
+
+        // Describe the messages as (kind, key or value, acknowledged).
+        let batch: Vec<_> = coalesce(batch)
+            .into_iter()
+            .map(|msg| match msg {
+                Message::SetColor(key, _, ack) => ("color", key, ack.is_some()),
+                Message::SetImage(key, _, ack) => ("image", key, ack.is_some()),
+                Message::SetBrightness(brightness, ack) => {
+                    ("brightness", brightness, ack.is_some())
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+
+        // The image of key 1 is replaced by the acknowledged color.
+        assert_eq!(
+            batch,
+            [
+                ("brightness", 50, false),
+                ("color", 1, true),
+                ("color", 0, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fit_to_key() {
+        let image = DynamicImage::new_rgb8(10, 20);
+        assert_eq!(fit_to_key(Kind::Original, image).dimensions(), (72, 72));
+
+        let image = DynamicImage::new_rgb8(96, 96);
+        assert_eq!(fit_to_key(Kind::Xl, image).dimensions(), (96, 96));
+    }
+
+    #[test]
+    fn test_key_glyph() {
+        let deck = MockStreamDeck::new(Kind::Original);
+
+        deck.key(0).glyph('☀', (255, 200, 0)).unwrap();
+        assert_eq!(deck.image(0).unwrap().dimensions(), (72, 72));
+
+        // Emoji outside of the bundled font are not rendered.
+        assert!(deck.key(0).glyph('🎵', (255, 255, 255)).is_err());
+    }
+
+    #[test]
+    fn test_key_image_resize() {
+        let deck = MockStreamDeck::new(Kind::Original);
+
+        deck.key(0).image(DynamicImage::new_rgb8(200, 100)).unwrap();
+        assert_eq!(deck.image(0).unwrap().dimensions(), (72, 72));
+
+        deck.key(1).image_rgba(2, 2, &[255; 16]).unwrap();
+        assert_eq!(deck.image(1).unwrap().dimensions(), (72, 72));
+        assert!(deck.key(1).image_rgba(2, 2, &[255; 12]).is_err());
+        assert!(deck.key(1).image_rgba(0, 2, &[]).is_err());
+        assert!(deck.key(1).image_rgba(2, 0, &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fn_button() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        let color = Color::from((255, 0, 0));
+        let mut button = FnButton::new(DynamicImage::new_rgb8(72, 72), move |state, key| {
+            let color = color.clone();
+            Box::pin(async move {
+                state.brightness = 50;
+                key.color(color)
+            })
+        });
+
+        button.init(&mut state, deck.key(0)).await.unwrap();
+        assert!(deck.image(0).is_some());
+
+        button.on_click(&mut state, deck.key(0)).await.unwrap();
+        assert_eq!(state.brightness, 50);
+        assert_eq!(deck.color(0), Some(Color::from((255, 0, 0))));
+    }
+
+    #[tokio::test]
+    async fn test_mirror_button_deinit() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        let mut button = MirrorButton::new(0);
+        button.init(&mut state, deck.key(3)).await.unwrap();
+        button.deinit(&mut state, deck.key(3)).await.unwrap();
+
+        let written = deck.written();
+        assert!(matches!(
+            written[..],
+            [Written::Mirror(0, 3), Written::Unmirror(0, 3)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flash() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let image = DynamicImage::new_rgb8(72, 72);
+
+        deck.key(0)
+            .flash(image.clone(), Duration::from_millis(50))
+            .unwrap();
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(matches!(
+            deck.written()[..],
+            [Written::OverlayImage(0, _), Written::Restore(0)]
+        ));
+
+        // Flashing again or setting an overlay cancels the pending restore.
+        deck.key(1)
+            .flash(image.clone(), Duration::from_millis(50))
+            .unwrap();
+        deck.key(1)
+            .flash(image, Duration::from_millis(200))
+            .unwrap();
+        deck.key(1).overlay((255, 0, 0)).unwrap();
+        time::sleep(Duration::from_millis(300)).await;
+        assert!(matches!(
+            deck.written()[2..],
+            [
+                Written::OverlayImage(1, _),
+                Written::OverlayImage(1, _),
+                Written::Overlay(1, _)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_full_image_mini() {
+        let deck = MockStreamDeck::new(Kind::Mini);
+        deck.key(0)
+            .deck()
+            .set_full_image(DynamicImage::new_rgb8(300, 200))
+            .unwrap();
+
+        let written = deck.written();
+        assert_eq!(written.len(), 6);
+        for (key, written) in written.iter().enumerate() {
+            match written {
+                Written::Image(k, image) => {
+                    assert_eq!(*k as usize, key);
+                    assert_eq!(image.dimensions(), Model::Deck(Kind::Mini).image_size());
+                }
+                _ => panic!("unexpected write {:?}", written),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_icon() {
+        let path = std::env::temp_dir().join("streamdeck-test-get-icon.png");
+        DynamicImage::new_rgb8(100, 50).save(&path).unwrap();
+
+        let state = State::new();
+        let icon = state.get_icon(&path, (72, 72)).unwrap();
+        assert_eq!(icon.dimensions(), (72, 72));
+
+        // Cached icons are not read again.
+        std::fs::remove_file(&path).unwrap();
+        assert!(state.get_icon(&path, (72, 72)).is_ok());
+        assert!(state.get_icon(&path, (96, 96)).is_err());
+
+        state.icons.clear();
+        assert!(state.get_icon(&path, (72, 72)).is_err());
+    }
+
+    #[test]
+    fn test_icon_cache_size() {
+        let path = std::env::temp_dir().join("streamdeck-test-icon-cache-size.png");
+        DynamicImage::new_rgb8(10, 10).save(&path).unwrap();
+
+        let icons = IconCache::default();
+        for size in 1..=ICON_CACHE_SIZE as u32 + 1 {
+            icons.image(&path, (size, size)).unwrap();
+            assert!(icons.images.lock().unwrap().len() <= ICON_CACHE_SIZE);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_image_from_path() {
+        let path = std::env::temp_dir().join("streamdeck-test-image-from-path.png");
+        DynamicImage::new_rgb8(100, 50).save(&path).unwrap();
+
+        let mock = MockStreamDeck::new(Kind::Original);
+        mock.key(0).image_from_path(&path).unwrap();
+
+        // The second call uses the cached icon.
+        std::fs::remove_file(&path).unwrap();
+        mock.key(0).image_from_path(&path).unwrap();
+        assert!(matches!(
+            mock.written()[..],
+            [Written::Image(0, _), Written::Image(0, _)]
+        ));
+    }
+
+    #[test]
+    fn test_is_valid_report() {
+        let original = Model::Deck(Kind::Original);
+        assert!(is_valid_report(original, &[0; 15]));
+        assert!(is_valid_report(original, &[1; 15]));
+        assert!(!is_valid_report(original, &[0; 14]));
+        assert!(!is_valid_report(original, &[0; 32]));
+        assert!(!is_valid_report(Model::Deck(Kind::Xl), &[7; 32]));
+        assert!(is_valid_report(Model::Pedal, &[0, 1, 0]));
+    }
+
+    #[test]
+    fn test_pedal_keys() {
+        // The key states follow the 4 byte header of the report.
+        let keys = pedal_keys(&[1, 0, 3, 0, 0, 1, 1]);
+        assert_eq!(keys, vec![0, 1, 1]);
+        assert!(is_valid_report(Model::Pedal, &keys));
+
+        // Truncated reports are rejected.
+        assert!(!is_valid_report(Model::Pedal, &pedal_keys(&[1, 0, 3])));
+        assert!(!is_valid_report(
+            Model::Pedal,
+            &pedal_keys(&[1, 0, 3, 0, 1])
+        ));
+    }
+
+    #[test]
+    fn test_pressed_keys() {
+        // Keys pressed or released in the same report are all reported in
+        // the order of their index.
+        let previous = [0, 1, 0, 0, 1];
+        let current = [1, 1, 0, 1, 0];
+        assert_eq!(pressed_keys(&previous, &current), vec![0, 3]);
+        assert_eq!(pressed_keys(&current, &previous), vec![4]);
+
+        // Held keys are only reported once.
+        assert_eq!(pressed_keys(&current, &current), Vec::<u8>::new());
+        assert_eq!(pressed_keys(&[], &[0, 1, 1]), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_perimeter_keys() {
+        assert_eq!(perimeter_keys(Kind::Mini), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            perimeter_keys(Kind::Original),
+            vec![0, 1, 2, 3, 4, 5, 9, 10, 11, 12, 13, 14]
+        );
+        assert_eq!(perimeter_keys(Kind::Xl).len(), 20);
+    }
+
+    #[test]
+    fn test_span_keys() {
+        let span = Span {
+            row: 1,
+            col: 2,
+            rows: 2,
+            columns: 3,
+        };
+        assert_eq!(span.keys(8), vec![10, 11, 12, 18, 19, 20]);
+        // Columns past the edge don't wrap around to the next row.
+        assert_eq!(span.keys(4), vec![6, 7, 10, 11]);
+
+        // Spans past the largest key index don't overflow.
+        let span = Span {
+            row: 254,
+            col: 250,
+            rows: 10,
+            columns: 10,
+        };
+        assert!(span.keys(255).is_empty());
+        assert!(span.keys(1).is_empty());
+    }
+
+    #[test]
+    fn test_select_device() {
+        let device = |pid, serial: &str| DeviceInfo {
+            vid: 0x0fd9,
+            pid,
+            serial: Some(serial.to_owned()),
+            model: "",
+        };
+        let devices = [device(0x006c, "A"), device(0x0063, "B")];
+
+        assert_eq!(select_device(&devices, None, None, None), Some(&devices[0]));
+        assert_eq!(
+            select_device(&devices, None, Some(0x0063), None),
+            Some(&devices[1])
+        );
+        assert_eq!(
+            select_device(&devices, Some(0x0fd9), None, Some("B")),
+            Some(&devices[1])
+        );
+        assert_eq!(select_device(&devices, None, Some(0x006c), Some("B")), None);
+        assert_eq!(select_device(&devices, None, None, Some("C")), None);
+        assert_eq!(select_device(&[], None, None, None), None);
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(
+            Color::from((32, 65, 128)),
+            Color {
+                r: 32,
+                g: 65,
+                b: 128
+            }
+        );
+        assert_eq!(Color::from(0x1e90ff), Color::from((0x1e, 0x90, 0xff)));
+    }
+
+    #[test]
+    fn test_color_from_str() {
+        assert_eq!(
+            "#1e90ff".parse::<Color>().unwrap(),
+            Color::from((0x1e, 0x90, 0xff))
+        );
+        assert_eq!(
+            "#1E90FF".parse::<Color>().unwrap(),
+            Color::from((0x1e, 0x90, 0xff))
+        );
+        assert_eq!(
+            Color::try_from("#1ef").unwrap(),
+            Color::from((0x11, 0xee, 0xff))
+        );
+
+        assert!("1e90ff".parse::<Color>().is_err());
+        assert!("#1e90f".parse::<Color>().is_err());
+        assert!("#1e90ffa".parse::<Color>().is_err());
+        assert!("#1e90fg".parse::<Color>().is_err());
+        assert!("#+1e90f".parse::<Color>().is_err());
+        assert!("#".parse::<Color>().is_err());
+
+        assert_eq!("red".parse::<Color>().unwrap(), Color::from((255, 0, 0)));
+        assert_eq!(Color::from_name("Teal"), Some(Color::from((0, 0x80, 0x80))));
+        assert!("dodgerblue".parse::<Color>().is_err());
     }
 }
@@ -0,0 +1,205 @@
+//! A minimal HTTP API to click buttons remotely.
+//!
+//! Routes:
+//! - `POST /keys/{key}/click`: Click the button at `key` on the first deck.
+//! - `POST /decks/{deck}/keys/{key}/click`: Click the button at `key` on
+//!   `deck`.
+//! - `PUT /counters/{id}/{count}`: Set the counter `id` shown by
+//!   `CounterButton`s to `count`. Only counters shown by a `CounterButton`
+//!   can be set.
+
+use crate::core::{RemoteClick, State};
+use crate::{debug, error, info};
+
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tokio::task;
+
+/// The maximum size of a request head in bytes.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// The maximum number of headers accepted in a request.
+const MAX_HEADERS: usize = 32;
+
+/// A request handled by the HTTP API.
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Click { deck: usize, key: u8 },
+    SetCounter { id: String, count: u32 },
+}
+
+/// Spawn a task serving the HTTP API on `addr`. Clicks are forwarded to the
+/// main loop through `clicks`.
+pub fn spawn(addr: SocketAddr, clicks: UnboundedSender<RemoteClick>, state: State) {
+    task::spawn(async move {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to bind HTTP API to {}: {:?}", addr, err);
+                return;
+            }
+        };
+
+        info!("HTTP API listening on {}", addr);
+
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    error!("Failed to accept HTTP connection: {:?}", err);
+                    continue;
+                }
+            };
+
+            debug!("Accepted HTTP connection from {}", peer);
+
+            let clicks = clicks.clone();
+            let state = state.clone();
+            task::spawn(async move {
+                if let Err(err) = handle_connection(stream, clicks, state).await {
+                    debug!("HTTP connection from {} failed: {:?}", peer, err);
+                }
+            });
+        }
+    });
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    clicks: UnboundedSender<RemoteClick>,
+    state: State,
+) -> std::io::Result<()> {
+    let mut buf = vec![0; MAX_REQUEST_SIZE];
+    let mut len = 0;
+
+    // Read until the request head is complete. The request body is ignored.
+    let (method, path) = loop {
+        if len == buf.len() {
+            return respond(&mut stream, 431, "Request Header Fields Too Large").await;
+        }
+
+        let n = stream.read(&mut buf[len..]).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        len += n;
+
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut req = httparse::Request::new(&mut headers);
+
+        match req.parse(&buf[..len]) {
+            Ok(httparse::Status::Complete(_)) => {
+                break (
+                    req.method.unwrap_or_default().to_owned(),
+                    req.path.unwrap_or_default().to_owned(),
+                )
+            }
+            Ok(httparse::Status::Partial) => continue,
+            Err(_) => return respond(&mut stream, 400, "Bad Request").await,
+        }
+    };
+
+    let (deck, key) = match parse_route(&path) {
+        Some(Route::Click { deck, key }) if method == "POST" => (deck, key),
+        Some(Route::SetCounter { id, count }) if method == "PUT" => {
+            return match state.set_counter(&id, count) {
+                true => respond(&mut stream, 200, "OK").await,
+                false => respond(&mut stream, 404, "Not Found").await,
+            };
+        }
+        Some(_) => return respond(&mut stream, 405, "Method Not Allowed").await,
+        None => return respond(&mut stream, 404, "Not Found").await,
+    };
+
+    let (tx, rx) = oneshot::channel();
+    if clicks.send(RemoteClick { deck, key, tx }).is_err() {
+        return respond(&mut stream, 503, "Service Unavailable").await;
+    }
+
+    match rx.await {
+        Ok(Some(Ok(()))) => respond(&mut stream, 200, "OK").await,
+        Ok(Some(Err(err))) => {
+            let body = format!("{:?}", err);
+            respond(&mut stream, 500, &body).await
+        }
+        Ok(None) => respond(&mut stream, 404, "Not Found").await,
+        Err(_) => respond(&mut stream, 503, "Service Unavailable").await,
+    }
+}
+
+/// Parse the path of a request into a [`Route`].
+fn parse_route(path: &str) -> Option<Route> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["keys", key, "click"] => Some(Route::Click {
+            deck: 0,
+            key: key.parse().ok()?,
+        }),
+        ["decks", deck, "keys", key, "click"] => Some(Route::Click {
+            deck: deck.parse().ok()?,
+            key: key.parse().ok()?,
+        }),
+        ["counters", id, count] => Some(Route::SetCounter {
+            id: id.to_string(),
+            count: count.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: u16, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_route, Route};
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            parse_route("/keys/3/click"),
+            Some(Route::Click { deck: 0, key: 3 })
+        );
+        assert_eq!(
+            parse_route("/decks/1/keys/15/click"),
+            Some(Route::Click { deck: 1, key: 15 })
+        );
+        assert_eq!(parse_route("/keys/256/click"), None);
+        assert_eq!(parse_route("/keys/a/click"), None);
+        assert_eq!(parse_route("/keys/3"), None);
+        assert_eq!(
+            parse_route("/counters/mail/4"),
+            Some(Route::SetCounter {
+                id: String::from("mail"),
+                count: 4
+            })
+        );
+        assert_eq!(parse_route("/counters/mail/-1"), None);
+    }
+}
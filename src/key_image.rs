@@ -0,0 +1,101 @@
+use crate::core::{Color, Key};
+use crate::text::{draw_line, FONT_SIZE};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// The height of a bar drawn by [`KeyImage::bar`] in pixels.
+const BAR_HEIGHT: u32 = 6;
+
+/// The vertical position of text drawn by [`KeyImage::text`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Position {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// A builder composing an image for a key, e.g. an icon with a value drawn
+/// over it. The canvas has the image size of the key it was created for.
+///
+/// ```ignore
+/// let image = KeyImage::from_image(&key, &icon)
+///     .text("42%", Position::Bottom, (255, 255, 255))
+///     .build();
+///
+/// key.image(image)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyImage {
+    image: RgbImage,
+}
+
+impl KeyImage {
+    /// Start with a canvas filled with `color`.
+    pub fn from_color<T>(key: &Key, color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        let (width, height) = key.image_size();
+        let color = color.into();
+
+        Self {
+            image: RgbImage::from_pixel(width, height, Rgb([color.r, color.g, color.b])),
+        }
+    }
+
+    /// Start with `image` scaled to the size of the key.
+    pub fn from_image(key: &Key, image: &DynamicImage) -> Self {
+        let (width, height) = key.image_size();
+
+        Self {
+            image: image
+                .resize_exact(width, height, FilterType::Triangle)
+                .to_rgb8(),
+        }
+    }
+
+    /// Draw a single line of `text` in `color` at `position`. The text is
+    /// horizontally centered.
+    pub fn text<T>(mut self, text: &str, position: Position, color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        let height = self.image.height();
+        let line_height = FONT_SIZE as u32;
+
+        let y = match position {
+            Position::Top => 2,
+            Position::Center => height.saturating_sub(line_height) / 2,
+            Position::Bottom => height.saturating_sub(line_height + 2),
+        };
+
+        draw_line(&mut self.image, text, y, color.into());
+        self
+    }
+
+    /// Draw a horizontal bar filled to `percent` (clamped to 0-100) in
+    /// `color` along the bottom edge.
+    pub fn bar<T>(mut self, percent: u8, color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        let (width, height) = self.image.dimensions();
+        let color = color.into();
+        let color = Rgb([color.r, color.g, color.b]);
+
+        let filled = width * percent.min(100) as u32 / 100;
+        for y in height.saturating_sub(BAR_HEIGHT)..height {
+            for x in 0..filled {
+                self.image.put_pixel(x, y, color);
+            }
+        }
+
+        self
+    }
+
+    /// Returns the composed image, ready to be passed to [`Key::image`].
+    pub fn build(self) -> DynamicImage {
+        DynamicImage::ImageRgb8(self.image)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::core::{Button, ButtonWrapper, Model, Span};
+use crate::warn;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use streamdeck::Kind;
+
+/// Builds the buttons of a deck by placing them at row and column
+/// coordinates instead of key indices.
+///
+/// ```ignore
+/// let mut layout = Layout::for_kind(Kind::Xl);
+/// layout.set(0, 0, audio::MuteButton::default());
+/// state.buttons = layout.into_buttons();
+/// ```
+pub struct Layout {
+    rows: u8,
+    columns: u8,
+    buttons: HashMap<u8, ButtonWrapper>,
+}
+
+impl Layout {
+    /// Creates an empty layout for a grid of `rows`x`columns` keys.
+    pub fn new(rows: u8, columns: u8) -> Self {
+        Self {
+            rows,
+            columns,
+            buttons: HashMap::new(),
+        }
+    }
+
+    /// Creates an empty layout matching the grid of the Streamdeck model
+    /// `kind`.
+    pub fn for_kind(kind: Kind) -> Self {
+        Self::for_model(Model::Deck(kind))
+    }
+
+    /// Creates an empty layout matching the grid of `model`.
+    pub fn for_model(model: Model) -> Self {
+        let columns = model.columns();
+        Self::new(model.keys() / columns, columns)
+    }
+
+    /// Place `button` at `row` and `col`, replacing any button already
+    /// placed there. Rows and columns start at 0 in the top left corner.
+    ///
+    /// Buttons outside of the grid are ignored with a warning, so a layout
+    /// made for a larger model still works on smaller ones.
+    pub fn set<B>(&mut self, row: u8, col: u8, button: B) -> &mut Self
+    where
+        B: Button + 'static,
+    {
+        if row >= self.rows || col >= self.columns {
+            warn!(
+                "Ignoring button at row {} col {} outside of the {}x{} grid",
+                row, col, self.rows, self.columns
+            );
+            return self;
+        }
+
+        let key = row * self.columns + col;
+        self.buttons
+            .insert(key, ButtonWrapper::new(Box::new(button)));
+        self
+    }
+
+    /// Place a single `button` on the `rows`x`columns` rectangle of keys
+    /// with its top left key at `row` and `col`. Presses of any of the keys
+    /// go to the same button instance. `init` and `on_tick` are called once
+    /// per key, see [`Key::image_slice`] to show one image across the span.
+    ///
+    /// Keys of the span outside of the grid are ignored with a warning.
+    ///
+    /// [`Key::image_slice`]: crate::core::Key::image_slice
+    pub fn span<B>(&mut self, row: u8, col: u8, rows: u8, columns: u8, button: B) -> &mut Self
+    where
+        B: Button + 'static,
+    {
+        let span = Span {
+            row,
+            col,
+            rows,
+            columns,
+        };
+        let button: Box<dyn Button> = Box::new(button);
+        let button = Arc::new(tokio::sync::Mutex::new(button));
+
+        if u16::from(row) + u16::from(rows) > u16::from(self.rows)
+            || u16::from(col) + u16::from(columns) > u16::from(self.columns)
+        {
+            warn!(
+                "Span at row {} col {} of {}x{} keys exceeds the {}x{} grid",
+                row, col, rows, columns, self.rows, self.columns
+            );
+        }
+
+        for key in span.keys(self.columns) {
+            if key < self.rows * self.columns {
+                self.buttons
+                    .insert(key, ButtonWrapper::shared(button.clone(), span));
+            }
+        }
+
+        self
+    }
+
+    /// Returns the buttons to be used as [`State::buttons`].
+    ///
+    /// [`State::buttons`]: crate::core::State::buttons
+    pub fn into_buttons(self) -> Arc<RwLock<HashMap<u8, ButtonWrapper>>> {
+        Arc::new(RwLock::new(self.buttons))
+    }
+
+    /// Returns the buttons keyed by their key index, e.g. to return them
+    /// from [`State::reload`].
+    ///
+    /// [`State::reload`]: crate::core::State::reload
+    pub fn into_button_map(self) -> HashMap<u8, ButtonWrapper> {
+        self.buttons
+    }
+}
@@ -1,13 +1,81 @@
+use std::env;
+use std::sync::OnceLock;
+
+/// The environment variable used to set the maximum log level.
+const LOG_ENV: &str = "STREAMDECK_LOG";
+
+/// The log level of a message. Levels are ordered from the most to the least
+/// severe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn from_str(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the maximum level of messages that are logged. Read once from the
+/// `STREAMDECK_LOG` environment variable and defaults to [`Level::Info`].
+pub fn max_level() -> Level {
+    static LEVEL: OnceLock<Level> = OnceLock::new();
+
+    *LEVEL.get_or_init(|| {
+        env::var(LOG_ENV)
+            .ok()
+            .and_then(|s| Level::from_str(&s))
+            .unwrap_or(Level::Info)
+    })
+}
+
+/// Returns `true` if messages with the given `level` are logged.
+pub fn enabled(level: Level) -> bool {
+    level <= max_level()
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        if $crate::log::enabled($crate::log::Level::Error) {
+            eprintln!("[{}] [ERROR] {}", ::chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), std::format_args!($($arg)+));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        if $crate::log::enabled($crate::log::Level::Warn) {
+            eprintln!("[{}] [WARN] {}", ::chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), std::format_args!($($arg)+));
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)+) => {
-        println!("[{}] [INFO] {}", ::chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), std::format_args!($($arg)+));
+        if $crate::log::enabled($crate::log::Level::Info) {
+            println!("[{}] [INFO] {}", ::chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), std::format_args!($($arg)+));
+        }
     };
 }
 
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)+) => {
-        println!("[DEBUG] {}", ::std::format_args!($($arg)+))
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            println!("[{}] [DEBUG] {}", ::chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), std::format_args!($($arg)+));
+        }
     };
 }
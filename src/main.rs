@@ -1,87 +1,226 @@
+// The buttons and the API to write them are a library to build the layout
+// in `main` from, most of it is unused in any given layout.
+#[allow(dead_code)]
 mod core;
+#[cfg(feature = "http")]
+mod http;
+#[allow(dead_code)]
+mod key_image;
+#[allow(dead_code)]
+mod layout;
 mod log;
 mod macros;
+#[allow(dead_code)]
 mod plugins;
+#[allow(dead_code)]
+mod store;
+#[allow(dead_code)]
+mod text;
+#[allow(dead_code)]
 mod typemap;
+#[cfg(feature = "audio")]
+mod wpctl;
 
-use crate::core::NullButton;
-use crate::plugins::{audio, obs, screenshot, vlc};
+use crate::core::Model;
+use crate::layout::Layout;
+#[cfg(feature = "audio")]
+use crate::plugins::audio;
+#[cfg(feature = "obs")]
+use crate::plugins::obs;
+#[cfg(feature = "screenshot")]
+use crate::plugins::screenshot;
+#[cfg(feature = "vlc")]
+use crate::plugins::vlc;
+
+use std::env;
+use std::process;
+use std::sync::Arc;
 
 const VID: u16 = 0x0fd9;
 const PID: u16 = 0x006c;
 
+/// The environment variables selecting the deck to connect to. The first
+/// connected deck is used if none are set.
+const ENV_VID: &str = "STREAMDECK_VID";
+const ENV_PID: &str = "STREAMDECK_PID";
+const ENV_SERIAL: &str = "STREAMDECK_SERIAL";
+
+/// Read a USB id in hex (e.g. `0x006c` or `006c`) from the environment
+/// variable `name`. Exits the process if the value is malformed.
+fn env_id(name: &str) -> Option<u16> {
+    let value = env::var(name).ok().filter(|value| !value.is_empty())?;
+
+    let hex = value.strip_prefix("0x").unwrap_or(&value);
+    match u16::from_str_radix(hex, 16) {
+        Ok(id) => Some(id),
+        Err(_) => {
+            error!("Invalid {}: {:?} is not a hex USB id", name, value);
+            process::exit(1);
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let mut state = core::State::new();
 
-    state.buttons = buttons! {
-        audio::DeafenButton,
-        audio::MuteButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        screenshot::FlameshotButton,
-        screenshot::FullScreenshotButton,
-        obs::SaveReplayBufferButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        vlc::PreviousButton,
-        vlc::PlayPauseButton,
-        vlc::NextButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
-        NullButton,
+    let vid = env_id(ENV_VID);
+    let pid = env_id(ENV_PID);
+    let serial = env::var(ENV_SERIAL)
+        .ok()
+        .filter(|serial| !serial.is_empty());
+
+    // Use the first connected deck matching the environment variables. The
+    // main loop waits for the deck if it is not connected yet.
+    let devices = core::StreamDeck::enumerate();
+    let (vid, pid, serial) = match core::select_device(&devices, vid, pid, serial.as_deref()) {
+        Some(device) => (device.vid, device.pid, device.serial.clone()),
+        None if serial.is_some() => {
+            let connected: Vec<_> = devices.iter().filter_map(|d| d.serial.as_deref()).collect();
+            error!(
+                "No Streamdeck with serial {:?} is connected (connected: {:?})",
+                serial.unwrap(),
+                connected
+            );
+            process::exit(1);
+        }
+        None => (vid.unwrap_or(VID), pid.unwrap_or(PID), None),
+    };
+
+    // Lay out the buttons for the grid of the selected deck.
+    let model = match core::model_from_pid(pid) {
+        Some(model) => model,
+        None => {
+            error!("Unsupported Streamdeck PID {:#06x}", pid);
+            process::exit(1);
+        }
     };
 
-    core::main_loop(VID, PID, None, state).await;
+    #[cfg(feature = "obs")]
+    state.register_service::<obs::OBSClient>();
+
+    state.buttons = layout(model).into_buttons();
+
+    // Rebuild the layout on SIGHUP.
+    state.reload = Some(Arc::new(|_, model| Ok(layout(model).into_button_map())));
+
+    core::main_loop(vid, pid, serial, state).await;
 }
 
+/// Lay out the buttons for the grid of `model`. The layout stays empty when
+/// all plugins are disabled.
+fn layout(model: Model) -> Layout {
+    #[allow(unused_mut)]
+    let mut layout = Layout::for_model(model);
+
+    #[cfg(feature = "audio")]
+    layout
+        .set(0, 0, audio::DeafenButton::default())
+        .set(0, 1, audio::MuteButton::default());
+    #[cfg(feature = "screenshot")]
+    layout
+        .set(0, 6, screenshot::FlameshotButton::default())
+        .set(0, 7, screenshot::FullScreenshotButton::default());
+    #[cfg(feature = "obs")]
+    layout.set(1, 0, obs::SaveReplayBufferButton::default());
+    #[cfg(feature = "vlc")]
+    layout
+        .set(2, 0, vlc::PreviousButton::default())
+        .set(2, 1, vlc::PlayPauseButton::default())
+        .set(2, 2, vlc::NextButton::default());
+
+    layout
+}
+
+#[cfg(feature = "audio")]
 mod pactl {
+    use serde_json::Value;
     use std::error;
     use std::fmt::{self, Display, Formatter};
-    use std::io::{BufRead, BufReader};
-    use std::process::{ChildStdout, Command, Stdio};
+    use std::io::{self, BufRead, BufReader, ErrorKind};
+    use std::process::{Child, ChildStdout, Command, Stdio};
     use std::result;
+    use std::sync::{Arc, Mutex};
 
     #[derive(Debug)]
+    #[allow(clippy::enum_variant_names)]
     pub enum Error {
-        DeserializeError,
+        /// The `pactl` binary is not installed.
+        NotInstalled,
+        /// `pactl` could not be run or exited with an error. Contains the
+        /// error message.
+        CommandFailed(String),
+        /// The output of `pactl` could not be parsed. Contains the
+        /// offending output.
+        ParseError(String),
+        /// There is no sink with the name.
+        SinkNotFound(String),
     }
 
     type Result<T> = result::Result<T, Error>;
 
     impl Display for Error {
         fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-            write!(
-                f,
-                "{}",
-                match self {
-                    Self::DeserializeError => "DeserializeError",
+            match self {
+                Self::NotInstalled => write!(f, "pactl is not installed"),
+                Self::CommandFailed(msg) => write!(f, "pactl failed: {}", msg),
+                Self::ParseError(output) => {
+                    write!(f, "failed to parse pactl output: {:?}", output)
                 }
-            )
+                Self::SinkNotFound(name) => write!(f, "no sink named {:?}", name),
+            }
         }
     }
 
     impl error::Error for Error {}
 
+    impl From<io::Error> for Error {
+        fn from(err: io::Error) -> Self {
+            match err.kind() {
+                ErrorKind::NotFound => Self::NotInstalled,
+                _ => Self::CommandFailed(err.to_string()),
+            }
+        }
+    }
+
+    impl Error {
+        fn parse(buf: &[u8]) -> Self {
+            Self::ParseError(String::from_utf8_lossy(buf).into_owned())
+        }
+    }
+
+    /// Returns a `pactl` command. The locale is forced to `C` because the
+    /// field names and values of the text output are translated otherwise
+    /// (e.g. "Stumm: ja" instead of "Mute: yes"). All invocations must use
+    /// this command.
     fn new_pactl() -> Command {
-        Command::new("pactl")
+        let mut cmd = Command::new("pactl");
+        cmd.env("LC_ALL", "C").env("LANG", "C");
+        cmd
+    }
+
+    /// Run `pactl` with the given arguments and return its stdout.
+    fn run(args: &[&str]) -> Result<Vec<u8>> {
+        let output = new_pactl().args(args).output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::CommandFailed(stderr.trim().to_owned()));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Run `pactl` with the given arguments operating on the sink `sink`.
+    /// Returns [`Error::SinkNotFound`] if the sink doesn't exist.
+    fn run_on_sink(args: &[&str], sink: &str) -> Result<()> {
+        match run(args) {
+            Ok(_) => Ok(()),
+            Err(Error::CommandFailed(msg)) if msg.contains("No such entity") => {
+                Err(Error::SinkNotFound(sink.to_owned()))
+            }
+            Err(err) => Err(err),
+        }
     }
 
     fn string_from_slice(buf: &[u8]) -> String {
@@ -106,7 +245,7 @@ mod pactl {
         }
     }
 
-    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
     pub enum EventDst {
         Sink(u32),
         Source(u32),
@@ -118,10 +257,8 @@ mod pactl {
 
     impl EventDst {
         fn deserialize(buf: [&[u8]; 2]) -> Option<Self> {
-            let id = match buf[1].strip_prefix(b"#") {
-                Some(id) => std::str::from_utf8(id).unwrap().parse().unwrap(),
-                None => return None,
-            };
+            let id = buf[1].strip_prefix(b"#")?;
+            let id = std::str::from_utf8(id).ok()?.parse().ok()?;
 
             match buf[0] {
                 b"sink" => Some(Self::Sink(id)),
@@ -135,71 +272,99 @@ mod pactl {
         }
     }
 
-    /// A subscription to `pactl` events using `pactl subscribe`.
-    /// Use
+    /// A subscription to `pactl` events using `pactl subscribe`. The `pactl`
+    /// process is killed when the `Subscription` is dropped.
+    ///
     /// # Example
     /// ```
     /// let mut subscription = Subscription::new();
-    /// let event = subscription.read_event().expect("Failed to read event");
+    /// let event = subscription.read_event().expect("Failed to read event").unwrap();
     /// println!("Event {:?} on {:?}", event.0, event.1);
     /// ```
-    // TODO: impl Drop for child spawned by Command.
     pub struct Subscription {
+        child: Arc<Mutex<Child>>,
         reader: BufReader<ChildStdout>,
     }
 
     impl Subscription {
         /// Create a new `Subscription`.
-        pub fn new() -> Self {
-            let child = Command::new("pactl")
+        pub fn new() -> Result<Self> {
+            let mut child = new_pactl()
                 .arg("subscribe")
                 .stdout(Stdio::piped())
-                .spawn()
-                .unwrap();
+                .spawn()?;
 
-            let stdout = child.stdout.unwrap();
+            let stdout = child.stdout.take().unwrap();
 
-            Self {
+            Ok(Self {
+                child: Arc::new(Mutex::new(child)),
                 reader: BufReader::new(stdout),
-            }
+            })
+        }
+
+        /// Returns a function that kills the `pactl` process. A blocked
+        /// [`read_event`] returns `None` afterwards. The function can be
+        /// called from any thread.
+        ///
+        /// [`read_event`]: Self::read_event
+        pub fn killer(&self) -> impl FnOnce() + Send + 'static {
+            let child = self.child.clone();
+            move || kill(&child)
         }
 
         /// Read a single event from the `Subscription`. This method
-        /// blocks until a single event was read (or failed).
-        pub fn read_event(&mut self) -> Result<(Event, EventDst)> {
+        /// blocks until a single event was read (or failed). Returns `None`
+        /// once `pactl subscribe` exited.
+        pub fn read_event(&mut self) -> Result<Option<(Event, EventDst)>> {
             let mut buf = Vec::new();
-            self.reader.read_until(b'\n', &mut buf).unwrap();
-
-            // Cut '\n' at the end.
-            buf.truncate(buf.len() - 1);
-
-            let parts: Vec<&[u8]> = buf.split(|b| *b == b' ').collect();
-            assert_eq!(parts[0], b"Event");
-            let event = Event::deserialize(parts[1]).unwrap();
-            assert_eq!(parts[2], b"on");
-            let dst = match EventDst::deserialize([parts[3], parts[4]]) {
-                Some(ev) => ev,
-                None => return Err(Error::DeserializeError),
-            };
+            if self.reader.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(None);
+            }
 
-            Ok((event, dst))
+            parse_event_line(&buf).map(Some)
         }
     }
 
-    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    impl Drop for Subscription {
+        fn drop(&mut self) {
+            kill(&self.child);
+        }
+    }
+
+    /// Kill and reap `child`. Killing a process that already exited is not an
+    /// error.
+    fn kill(child: &Mutex<Child>) {
+        let mut child = child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    /// Parse a single line of `pactl subscribe` output in the form
+    /// "Event '{event}' on {dst} #{id}". A trailing '\n' is ignored.
+    pub fn parse_event_line(line: &[u8]) -> Result<(Event, EventDst)> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+
+        let parts: Vec<&[u8]> = line.split(|b| *b == b' ').collect();
+        match parts.as_slice() {
+            [b"Event", event, b"on", dst, id] => {
+                let event = Event::deserialize(event).ok_or_else(|| Error::parse(line))?;
+                let dst = EventDst::deserialize([dst, id]).ok_or_else(|| Error::parse(line))?;
+
+                Ok((event, dst))
+            }
+            _ => Err(Error::parse(line)),
+        }
+    }
+
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
     pub enum SinkState {
         Idle,
         Running,
         Suspended,
+        #[default]
         Undefined,
     }
 
-    impl Default for SinkState {
-        fn default() -> Self {
-            Self::Undefined
-        }
-    }
-
     impl SinkState {
         fn deserialize(buf: &[u8]) -> Option<Self> {
             match buf {
@@ -209,6 +374,13 @@ mod pactl {
                 _ => None,
             }
         }
+
+        fn from_json(value: &Value) -> Self {
+            value
+                .as_str()
+                .and_then(|state| Self::deserialize(state.as_bytes()))
+                .unwrap_or_default()
+        }
     }
 
     #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -236,83 +408,348 @@ mod pactl {
             let mut sink = Self::default();
 
             for part in buf {
-                match part.strip_prefix(b"\t") {
-                    Some(part) => {
-                        let mut parts: Vec<&[u8]> = part.splitn(2, |b| *b == b':').collect();
+                match parse_field(part) {
+                    Some((key, value)) => match key {
+                        b"State" => sink.state = SinkState::deserialize(value).unwrap_or_default(),
+                        b"Monitor Source" => sink.monitor_source = string_from_slice(value),
+                        _ => {
+                            let fields = CommonFields {
+                                name: &mut sink.name,
+                                description: &mut sink.description,
+                                driver: &mut sink.driver,
+                                sample_specification: &mut sink.sample_specification,
+                                channel_map: &mut sink.channel_map,
+                                owner_module: &mut sink.owner_module,
+                                mute: &mut sink.mute,
+                                volume: &mut sink.volume,
+                                base_volume: &mut sink.base_volume,
+                                latency: &mut sink.latency,
+                                flags: &mut sink.flags,
+                            };
 
-                        if parts.len() < 2 {
-                            continue;
+                            fields.set(key, value);
                         }
+                    },
+                    // Start of sink section: "Sink #{id}"
+                    None => sink.id = parse_header(part, b"Sink")?,
+                }
+            }
+
+            Some(sink)
+        }
+
+        /// Create a `Sink` from an object of `pactl --format=json list sinks`.
+        fn from_json(value: &Value) -> Option<Self> {
+            let mut sink = Self {
+                id: value["index"].as_u64()? as u32,
+                state: SinkState::from_json(&value["state"]),
+                monitor_source: json_string(&value["monitor_source"]),
+                ..Default::default()
+            };
+
+            let fields = CommonFields {
+                name: &mut sink.name,
+                description: &mut sink.description,
+                driver: &mut sink.driver,
+                sample_specification: &mut sink.sample_specification,
+                channel_map: &mut sink.channel_map,
+                owner_module: &mut sink.owner_module,
+                mute: &mut sink.mute,
+                volume: &mut sink.volume,
+                base_volume: &mut sink.base_volume,
+                latency: &mut sink.latency,
+                flags: &mut sink.flags,
+            };
+            fields.set_json(value);
+
+            Some(sink)
+        }
+    }
+
+    /// Returns all sinks. Uses the JSON output of `pactl` if supported
+    /// (pactl 16.0 and later) and falls back to parsing the text output.
+    pub fn list_sinks() -> Result<Vec<Sink>> {
+        match run(&["--format=json", "list", "sinks"]) {
+            Ok(output) => parse_json_list(&output, Sink::from_json),
+            Err(Error::CommandFailed(_)) => list_sinks_text(),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn list_sinks_text() -> Result<Vec<Sink>> {
+        let output = run(&["list", "sinks"])?;
+
+        let mut sinks = Vec::new();
+        for sink_raw in split_sections(&output) {
+            let sink =
+                Sink::deserialize(&sink_raw).ok_or_else(|| Error::parse(&sink_raw.join(&b'\n')))?;
+            sinks.push(sink);
+        }
+
+        Ok(sinks)
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Source {
+        pub id: u32,
+        pub state: SinkState,
+        pub name: String,
+        pub description: String,
+        pub driver: String,
+        pub sample_specification: String,
+        pub channel_map: String,
+        pub owner_module: String,
+        pub mute: bool,
+        pub volume: String,
+        pub base_volume: String,
+        pub monitor_of_sink: String,
+        pub latency: String,
+        pub flags: String,
+        pub properties: String,
+        pub formats: String,
+    }
+
+    impl Source {
+        fn deserialize(buf: &[&[u8]]) -> Option<Self> {
+            let mut source = Self::default();
 
-                        if parts[1].starts_with(b" ") {
-                            parts[1] = &parts[1][1..];
+            for part in buf {
+                match parse_field(part) {
+                    Some((key, value)) => match key {
+                        b"State" => {
+                            source.state = SinkState::deserialize(value).unwrap_or_default()
                         }
+                        b"Monitor of Sink" => source.monitor_of_sink = string_from_slice(value),
+                        _ => {
+                            let fields = CommonFields {
+                                name: &mut source.name,
+                                description: &mut source.description,
+                                driver: &mut source.driver,
+                                sample_specification: &mut source.sample_specification,
+                                channel_map: &mut source.channel_map,
+                                owner_module: &mut source.owner_module,
+                                mute: &mut source.mute,
+                                volume: &mut source.volume,
+                                base_volume: &mut source.base_volume,
+                                latency: &mut source.latency,
+                                flags: &mut source.flags,
+                            };
 
-                        match parts[0] {
-                            b"State" => sink.state = SinkState::deserialize(parts[1]).unwrap(),
-                            b"Name" => sink.name = string_from_slice(parts[1]),
-                            b"Description" => sink.description = string_from_slice(parts[1]),
-                            b"Driver" => sink.driver = string_from_slice(parts[1]),
-                            b"Sample Specification" => sink.driver = string_from_slice(parts[1]),
-                            b"Channel Map" => sink.channel_map = string_from_slice(parts[1]),
-                            b"Owner Module" => sink.owner_module = string_from_slice(parts[1]),
-                            b"Mute" => sink.mute = matches!(parts[1], b"yes"),
-                            b"Volume" => sink.volume = string_from_slice(parts[1]),
-                            b"Monitor Source" => sink.monitor_source = string_from_slice(parts[1]),
-                            b"Latency" => sink.latency = string_from_slice(parts[1]),
-                            b"Flags" => sink.flags = string_from_slice(parts[1]),
-                            b"Properties" => (),
-                            b"Formats" => (),
-                            _ => (),
+                            fields.set(key, value);
                         }
-                    }
-                    // Start of sink section: "Sink #{id}"
-                    None => {
-                        let parts: Vec<&[u8]> = part.split(|b| *b == b' ').collect();
-                        assert_eq!(parts[0], b"Sink");
-                        assert_eq!(parts[1][0], b'#');
-                        let id = std::str::from_utf8(&parts[1][1..])
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-
-                        sink.id = id;
-                    }
+                    },
+                    // Start of source section: "Source #{id}"
+                    None => source.id = parse_header(part, b"Source")?,
                 }
             }
 
-            Some(sink)
+            Some(source)
+        }
+
+        /// Create a `Source` from an object of
+        /// `pactl --format=json list sources`.
+        fn from_json(value: &Value) -> Option<Self> {
+            let mut source = Self {
+                id: value["index"].as_u64()? as u32,
+                state: SinkState::from_json(&value["state"]),
+                monitor_of_sink: json_string(&value["monitor_of_sink"]),
+                ..Default::default()
+            };
+
+            let fields = CommonFields {
+                name: &mut source.name,
+                description: &mut source.description,
+                driver: &mut source.driver,
+                sample_specification: &mut source.sample_specification,
+                channel_map: &mut source.channel_map,
+                owner_module: &mut source.owner_module,
+                mute: &mut source.mute,
+                volume: &mut source.volume,
+                base_volume: &mut source.base_volume,
+                latency: &mut source.latency,
+                flags: &mut source.flags,
+            };
+            fields.set_json(value);
+
+            Some(source)
         }
     }
 
-    pub fn list_sinks() -> Result<Vec<Sink>> {
-        let output = new_pactl().args(&["list", "sinks"]).output().unwrap();
+    /// Returns all sources. Uses the JSON output of `pactl` if supported
+    /// (pactl 16.0 and later) and falls back to parsing the text output.
+    pub fn list_sources() -> Result<Vec<Source>> {
+        match run(&["--format=json", "list", "sources"]) {
+            Ok(output) => parse_json_list(&output, Source::from_json),
+            Err(Error::CommandFailed(_)) => list_sources_text(),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn list_sources_text() -> Result<Vec<Source>> {
+        let output = run(&["list", "sources"])?;
+
+        let mut sources = Vec::new();
+        for source_raw in split_sections(&output) {
+            let source = Source::deserialize(&source_raw)
+                .ok_or_else(|| Error::parse(&source_raw.join(&b'\n')))?;
+            sources.push(source);
+        }
 
-        let output = output.stdout;
+        Ok(sources)
+    }
+
+    /// The fields shared between the sections of `pactl list sinks` and
+    /// `pactl list sources`.
+    struct CommonFields<'a> {
+        name: &'a mut String,
+        description: &'a mut String,
+        driver: &'a mut String,
+        sample_specification: &'a mut String,
+        channel_map: &'a mut String,
+        owner_module: &'a mut String,
+        mute: &'a mut bool,
+        volume: &'a mut String,
+        base_volume: &'a mut String,
+        latency: &'a mut String,
+        flags: &'a mut String,
+    }
+
+    impl<'a> CommonFields<'a> {
+        /// Set the field named `key` to `value`. Unknown fields are ignored.
+        fn set(self, key: &[u8], value: &[u8]) {
+            match key {
+                b"Name" => *self.name = string_from_slice(value),
+                b"Description" => *self.description = string_from_slice(value),
+                b"Driver" => *self.driver = string_from_slice(value),
+                b"Sample Specification" => *self.sample_specification = string_from_slice(value),
+                b"Channel Map" => *self.channel_map = string_from_slice(value),
+                b"Owner Module" => *self.owner_module = string_from_slice(value),
+                b"Mute" => *self.mute = matches!(value, b"yes"),
+                b"Volume" => *self.volume = string_from_slice(value),
+                b"Base Volume" => *self.base_volume = string_from_slice(value),
+                b"Latency" => *self.latency = string_from_slice(value),
+                b"Flags" => *self.flags = string_from_slice(value),
+                b"Properties" => (),
+                b"Formats" => (),
+                _ => (),
+            }
+        }
 
-        let parts: Vec<&[u8]> = output.split(|b| *b == b'\n').collect();
+        /// Set the fields from an object of the JSON output of `pactl`.
+        /// Volumes are formatted as "{channel}: {percent}" pairs.
+        fn set_json(self, value: &Value) {
+            *self.name = json_string(&value["name"]);
+            *self.description = json_string(&value["description"]);
+            *self.driver = json_string(&value["driver"]);
+            *self.sample_specification = json_string(&value["sample_specification"]);
+            *self.channel_map = json_string(&value["channel_map"]);
+            *self.owner_module = json_string(&value["owner_module"]);
+            *self.mute = value["mute"].as_bool().unwrap_or_default();
+            *self.base_volume = json_string(&value["base_volume"]["value_percent"]);
+            *self.latency = json_string(&value["latency"]["actual"]);
 
-        let mut sinks_raw = Vec::new();
-        {
-            // Split sinks
-            let mut sink_raw = Vec::new();
-            for part in parts {
-                match part {
-                    b"" => {
-                        sinks_raw.push(sink_raw.clone());
-                        sink_raw.clear();
+            if let Some(volume) = value["volume"].as_object() {
+                *self.volume = volume
+                    .iter()
+                    .map(|(channel, volume)| {
+                        format!("{}: {}", channel, json_string(&volume["value_percent"]))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+            }
+
+            if let Some(flags) = value["flags"].as_array() {
+                *self.flags = flags.iter().map(json_string).collect::<Vec<_>>().join(" ");
+            }
+        }
+    }
+
+    /// Parse the JSON array output of `pactl --format=json list` using
+    /// `parse` for each element.
+    fn parse_json_list<T>(output: &[u8], parse: fn(&Value) -> Option<T>) -> Result<Vec<T>> {
+        let value: Value = serde_json::from_slice(output).map_err(|_| Error::parse(output))?;
+        let elements = value.as_array().ok_or_else(|| Error::parse(output))?;
+
+        elements
+            .iter()
+            .map(|element| parse(element).ok_or_else(|| Error::parse(output)))
+            .collect()
+    }
+
+    /// Returns a JSON string or number as a `String`. Returns an empty
+    /// `String` for all other values.
+    fn json_string(value: &Value) -> String {
+        match value {
+            Value::String(string) => string.clone(),
+            Value::Number(number) => number.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Split the output of `pactl list` into the lines of the
+    /// individual sections. Sections are separated by an empty line.
+    fn split_sections(output: &[u8]) -> Vec<Vec<&[u8]>> {
+        let mut sections = Vec::new();
+
+        let mut section = Vec::new();
+        for line in output.split(|b| *b == b'\n') {
+            match line {
+                b"" => {
+                    if !section.is_empty() {
+                        sections.push(section.clone());
+                        section.clear();
                     }
-                    _ => sink_raw.push(part),
                 }
+                _ => section.push(line),
             }
         }
 
-        let mut sinks = Vec::new();
-        for sink_raw in sinks_raw {
-            sinks.push(Sink::deserialize(&sink_raw).unwrap());
+        sections
+    }
+
+    /// Parse an indented "\t{key}: {value}" line of a section. Returns
+    /// `None` if the line is not indented.
+    fn parse_field(line: &[u8]) -> Option<(&[u8], &[u8])> {
+        let line = line.strip_prefix(b"\t")?;
+
+        let mut parts = line.splitn(2, |b| *b == b':');
+        let key = parts.next()?;
+        // Lines without a value are treated as a field with an empty value.
+        let value = parts.next().unwrap_or_default();
+
+        Some((key, value.strip_prefix(b" ").unwrap_or(value)))
+    }
+
+    /// Parse the "{name} #{id}" header line of a section and returns the
+    /// id.
+    fn parse_header(line: &[u8], name: &[u8]) -> Option<u32> {
+        let mut parts = line.split(|b| *b == b' ');
+        if parts.next()? != name {
+            return None;
         }
 
-        Ok(sinks)
+        let id = parts.next()?.strip_prefix(b"#")?;
+        std::str::from_utf8(id).ok()?.parse().ok()
+    }
+
+    /// Returns the name of the default sink.
+    pub fn get_default_sink() -> Result<String> {
+        let output = run(&["info"])?;
+        parse_info_field(&output, b"Default Sink").ok_or_else(|| Error::parse(&output))
+    }
+
+    /// Returns the name of the default source.
+    pub fn get_default_source() -> Result<String> {
+        let output = run(&["info"])?;
+        parse_info_field(&output, b"Default Source").ok_or_else(|| Error::parse(&output))
+    }
+
+    /// Returns the value of the "{name}: {value}" line of `pactl info`.
+    fn parse_info_field(output: &[u8], name: &[u8]) -> Option<String> {
+        output.split(|b| *b == b'\n').find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(b": ")?;
+            Some(string_from_slice(value))
+        })
     }
 
     #[allow(dead_code)]
@@ -322,24 +759,41 @@ mod pactl {
         Toggle,
     }
 
+    impl MuteAction {
+        /// Returns the argument for the action understood by `pactl` and
+        /// `wpctl`.
+        pub fn as_arg(&self) -> &'static str {
+            match self {
+                Self::On => "1",
+                Self::Off => "0",
+                Self::Toggle => "toggle",
+            }
+        }
+    }
+
     /// Change the mute state of a sink.
     pub fn set_sink_mute<'life0, T>(sink: T, action: MuteAction) -> Result<()>
     where
         T: Into<&'life0 str>,
     {
-        Command::new("pactl")
-            .args(&[
-                "set-sink-mute",
-                sink.into(),
-                match action {
-                    MuteAction::On => "1",
-                    MuteAction::Off => "0",
-                    MuteAction::Toggle => "toggle",
-                },
-            ])
-            .output()
-            .unwrap();
-        Ok(())
+        let sink = sink.into();
+        run_on_sink(&["set-sink-mute", sink, action.as_arg()], sink)
+    }
+
+    /// Change the volume of a sink. A `volume` of `1.0` is 100%.
+    pub fn set_sink_volume<'life0, T>(sink: T, volume: f32) -> Result<()>
+    where
+        T: Into<&'life0 str>,
+    {
+        let sink = sink.into();
+        run_on_sink(
+            &[
+                "set-sink-volume",
+                sink,
+                &format!("{}%", (volume * 100.0).round()),
+            ],
+            sink,
+        )
     }
 
     /// Change the mut state of a source.
@@ -347,24 +801,237 @@ mod pactl {
     where
         T: Into<&'life0 str>,
     {
-        Command::new("pactl")
-            .args(&[
-                "set-source-mute",
-                sink.into(),
-                match action {
-                    MuteAction::On => "1",
-                    MuteAction::Off => "0",
-                    MuteAction::Toggle => "toggle",
-                },
-            ])
-            .output()
-            .unwrap();
+        run(&["set-source-mute", sink.into(), action.as_arg()])?;
         Ok(())
     }
+
+    /// Make the sink `name` the default sink.
+    pub fn set_default_sink(name: &str) -> Result<()> {
+        run_on_sink(&["set-default-sink", name], name)
+    }
+
+    /// Returns the ids of all sink inputs (playing streams).
+    pub fn list_sink_inputs() -> Result<Vec<u32>> {
+        let output = run(&["list", "short", "sink-inputs"])?;
+
+        Ok(parse_short_ids(&output))
+    }
+
+    /// Parse the ids in the first column of `pactl list short` output.
+    fn parse_short_ids(buf: &[u8]) -> Vec<u32> {
+        buf.split(|b| *b == b'\n')
+            .filter_map(|line| line.split(|b| *b == b'\t').next())
+            .filter_map(|id| std::str::from_utf8(id).ok()?.parse().ok())
+            .collect()
+    }
+
+    /// Move the sink input `id` to the sink `sink`.
+    pub fn move_sink_input(id: u32, sink: &str) -> Result<()> {
+        run_on_sink(&["move-sink-input", &id.to_string(), sink], sink)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            parse_event_line, parse_info_field, parse_json_list, parse_short_ids, split_sections,
+            Event, EventDst, Sink, SinkState,
+        };
+
+        #[test]
+        fn test_parse_event_line() {
+            assert_eq!(
+                parse_event_line(b"Event 'change' on sink #1\n").unwrap(),
+                (Event::Change, EventDst::Sink(1))
+            );
+            assert_eq!(
+                parse_event_line(b"Event 'remove' on sink-input #42\n").unwrap(),
+                (Event::Remove, EventDst::SinkInput(42))
+            );
+            assert_eq!(
+                parse_event_line(b"Event 'new' on source-output #7").unwrap(),
+                (Event::New, EventDst::SourceOutput(7))
+            );
+            assert_eq!(
+                parse_event_line(b"Event 'change' on client #180\n").unwrap(),
+                (Event::Change, EventDst::Client(180))
+            );
+        }
+
+        #[test]
+        fn test_parse_event_line_malformed() {
+            assert!(parse_event_line(b"").is_err());
+            assert!(parse_event_line(b"\n").is_err());
+            assert!(parse_event_line(b"Event 'change' on server\n").is_err());
+            assert!(parse_event_line(b"Event 'change' on module #3\n").is_err());
+            assert!(parse_event_line(b"Event 'unknown' on sink #1\n").is_err());
+            assert!(parse_event_line(b"Event 'change' on sink 1\n").is_err());
+            assert!(parse_event_line(b"Event 'change' on sink #abc\n").is_err());
+            assert!(parse_event_line(b"Event 'change' at sink #1\n").is_err());
+            assert!(parse_event_line(b"Event 'change' on sink #1 extra\n").is_err());
+        }
+
+        /// The text output of `pactl list sinks` in the `C` locale. The
+        /// parser only understands these untranslated field names.
+        const SINKS: &[u8] = b"Sink #56
+\tState: RUNNING
+\tName: alsa_output.analog-stereo
+\tDescription: Built-in Audio Analog Stereo
+\tDriver: PipeWire
+\tSample Specification: s32le 2ch 48000Hz
+\tChannel Map: front-left,front-right
+\tOwner Module: 4294967295
+\tMute: yes
+\tVolume: front-left: 26214 /  40% / -23.88 dB,   front-right: 26214 /  40% / -23.88 dB
+\tBase Volume: 65536 / 100% / 0.00 dB
+\tMonitor Source: alsa_output.analog-stereo.monitor
+\tLatency: 0 usec, configured 0 usec
+\tFlags: HARDWARE LATENCY
+\tProperties:
+\t\tdevice.api = \"alsa\"
+\tFormats:
+\t\tpcm
+";
+
+        #[test]
+        fn test_parse_text_sinks() {
+            let sections = split_sections(SINKS);
+            let sink = Sink::deserialize(&sections[0]).unwrap();
+
+            assert_eq!(sink.id, 56);
+            assert_eq!(sink.state, SinkState::Running);
+            assert_eq!(sink.name, "alsa_output.analog-stereo");
+            assert_eq!(sink.description, "Built-in Audio Analog Stereo");
+            assert!(sink.mute);
+            assert_eq!(sink.base_volume, "65536 / 100% / 0.00 dB");
+            assert_eq!(sink.monitor_source, "alsa_output.analog-stereo.monitor");
+            assert_eq!(sink.flags, "HARDWARE LATENCY");
+
+            // Translated output is not understood.
+            let sections = split_sections(b"Sink #56\n\tName: foo\n\tStumm: ja\n");
+            assert!(!Sink::deserialize(&sections[0]).unwrap().mute);
+
+            // Unknown states don't fail the sink.
+            let sections = split_sections(b"Sink #56\n\tState: UNKNOWN\n\tName: foo\n");
+            let sink = Sink::deserialize(&sections[0]).unwrap();
+            assert_eq!(sink.state, SinkState::Undefined);
+        }
+
+        #[test]
+        fn test_parse_json_sinks() {
+            let output = br#"[{"index":56,"state":"SUSPENDED","name":"alsa_output.analog-stereo",
+                "description":"Built-in Audio Analog Stereo","driver":"PipeWire",
+                "sample_specification":"s32le 2ch 48000Hz","channel_map":"front-left,front-right",
+                "owner_module":4294967295,"mute":true,
+                "volume":{"front-left":{"value":26214,"value_percent":"40%","db":"-23.88 dB"},
+                    "front-right":{"value":26214,"value_percent":"40%","db":"-23.88 dB"}},
+                "balance":0,"base_volume":{"value":65536,"value_percent":"100%","db":"0.00 dB"},
+                "monitor_source":"alsa_output.analog-stereo.monitor",
+                "latency":{"actual":0,"configured":0},"flags":["HARDWARE","LATENCY"],
+                "properties":{},"formats":["pcm"]}]"#;
+
+            let sinks = parse_json_list(output, Sink::from_json).unwrap();
+            assert_eq!(
+                sinks,
+                vec![Sink {
+                    id: 56,
+                    state: SinkState::Suspended,
+                    name: "alsa_output.analog-stereo".into(),
+                    description: "Built-in Audio Analog Stereo".into(),
+                    driver: "PipeWire".into(),
+                    sample_specification: "s32le 2ch 48000Hz".into(),
+                    channel_map: "front-left,front-right".into(),
+                    owner_module: "4294967295".into(),
+                    mute: true,
+                    volume: "front-left: 40%, front-right: 40%".into(),
+                    base_volume: "100%".into(),
+                    monitor_source: "alsa_output.analog-stereo.monitor".into(),
+                    latency: "0".into(),
+                    flags: "HARDWARE LATENCY".into(),
+                    ..Default::default()
+                }]
+            );
+
+            assert!(parse_json_list(b"[{}]", Sink::from_json).is_err());
+            assert!(parse_json_list(b"Sink #1", Sink::from_json).is_err());
+        }
+
+        #[test]
+        fn test_parse_info_field() {
+            let info = b"Server String: /run/user/1000/pulse/native
+Library Protocol Version: 35
+Server Protocol Version: 35
+Is Local: yes
+Client Index: 42
+Tile Size: 65472
+User Name: user
+Host Name: host
+Server Name: PulseAudio (on PipeWire 0.3.48)
+Server Version: 15.0.0
+Default Sample Specification: float32le 2ch 48000Hz
+Default Channel Map: front-left,front-right
+Default Sink: alsa_output.pci-0000_0a_00.4.analog-stereo
+Default Source: alsa_input.pci-0000_0a_00.4.analog-stereo
+Cookie: 1234:abcd
+";
+
+            assert_eq!(
+                parse_info_field(info, b"Default Sink").as_deref(),
+                Some("alsa_output.pci-0000_0a_00.4.analog-stereo")
+            );
+            assert_eq!(
+                parse_info_field(info, b"Default Source").as_deref(),
+                Some("alsa_input.pci-0000_0a_00.4.analog-stereo")
+            );
+            // "Default Sample Specification" does not match "Default S".
+            assert_eq!(parse_info_field(info, b"Default S"), None);
+            assert_eq!(parse_info_field(b"", b"Default Sink"), None);
+        }
+
+        #[test]
+        fn test_parse_short_ids() {
+            assert_eq!(
+                parse_short_ids(
+                    b"63\t0\t45\tprotocol-native.c\ts16le 2ch 44100Hz\n\
+                      71\t1\t52\tprotocol-native.c\tfloat32le 2ch 48000Hz\n"
+                ),
+                vec![63, 71]
+            );
+            assert_eq!(parse_short_ids(b""), Vec::<u32>::new());
+        }
+    }
 }
 
+/// Creates the button layout for a [`State`].
+///
+/// Buttons can either be listed as types, in which case they are
+/// created using their `Default` implementation and assigned to
+/// consecutive keys starting at 0, or as `key => expr` pairs of
+/// already constructed buttons. Keys not listed in the second form
+/// are left empty. Pairs can be annotated with `#[cfg]` attributes.
+///
+/// # Example
+/// ```
+/// let buttons = buttons![audio::DeafenButton, audio::MuteButton];
+/// let buttons = buttons![0 => audio::DeafenButton::default(), 7 => audio::MuteButton::default()];
+/// ```
+///
+/// [`State`]: crate::core::State
 #[macro_export]
 macro_rules! buttons {
+    ($($(#[$attr:meta])* $key:literal => $button:expr),*$(,)?) => {{
+        let mut buttons = ::std::collections::HashMap::new();
+
+        $(
+            $(#[$attr])*
+            {
+                debug!("Key {} is {:?}", $key, stringify!($button));
+
+                buttons.insert($key, $crate::core::ButtonWrapper::new(Box::new($button)));
+            }
+        )*
+
+        ::std::sync::Arc::new(::std::sync::RwLock::new(buttons))
+    }};
     ($($button:ty),*$(,)?) => {{
         let mut buttons = ::std::collections::HashMap::new();
 
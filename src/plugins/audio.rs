@@ -1,22 +1,491 @@
-use crate::pactl::{
-    list_sinks, set_sink_mute, set_source_mute, Event, EventDst, MuteAction, Subscription,
-};
+use crate::pactl::{self, Event, EventDst, MuteAction, Sink, Source};
+use crate::wpctl;
 use crate::{
     core::{Button, Error, Key, Result, State},
-    load_icon,
+    debug, load_icon, warn,
 };
 use async_trait::async_trait;
 use image::DynamicImage;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The interval in which the [`WpctlBackend`] polls for changes.
+const WPCTL_POLLING_RATE: Duration = Duration::from_millis(500);
+
+/// The delay before subscribing again after an [`EventStream`] ended. It
+/// doubles with every attempt up to [`MAX_RESUBSCRIBE_DELAY`].
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(60);
+
+/// A sink or source of an [`AudioBackend`].
+#[derive(Copy, Clone, Debug)]
+pub enum Device<'a> {
+    /// The default sink or source.
+    Default,
+    /// The sink or source with the given name or id.
+    Name(&'a str),
+}
+
+/// A sound server controlled by the audio buttons.
+pub trait AudioBackend: Send + Sync {
+    fn list_sinks(&self) -> Result<Vec<Sink>>;
+    fn list_sources(&self) -> Result<Vec<Source>>;
+    /// Returns the name of the default sink.
+    fn default_sink(&self) -> Result<String>;
+    /// Returns the name of the default source.
+    fn default_source(&self) -> Result<String>;
+    fn set_sink_mute(&self, sink: Device, action: MuteAction) -> Result<()>;
+    fn set_source_mute(&self, source: Device, action: MuteAction) -> Result<()>;
+    /// Change the volume of a sink. A `volume` of `1.0` is 100%.
+    fn set_sink_volume(&self, sink: Device, volume: f32) -> Result<()>;
+    /// Make the sink `name` the default sink and move all playing streams to
+    /// it.
+    fn set_default_sink(&self, name: &str) -> Result<()>;
+    /// Subscribe to changes of the sinks and sources.
+    fn subscribe(&self) -> Result<Box<dyn EventStream>>;
+}
+
+/// A stream of events returned by [`AudioBackend::subscribe`].
+pub trait EventStream: Send {
+    /// Read a single event. This method blocks until an event was read
+    /// (or failed). Returns `None` once the stream ended.
+    fn read_event(&mut self) -> Result<Option<(Event, EventDst)>>;
+
+    /// Returns a function ending the stream from another thread. A blocked
+    /// `read_event` returns `None` afterwards.
+    fn closer(&self) -> Box<dyn FnOnce() + Send>;
+}
+
+/// The function ending the stream of a [`Listener`], or `None` if the
+/// listener was stopped.
+type Closer = Arc<Mutex<Option<Box<dyn FnOnce() + Send>>>>;
+
+/// A thread started by [`spawn_listener`].
+#[derive(Clone)]
+struct Listener {
+    closer: Closer,
+}
+
+impl Listener {
+    /// Stop the thread and end its event stream.
+    fn stop(&self) {
+        if let Some(close) = self.closer.lock().unwrap().take() {
+            close();
+        }
+    }
+}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Listener").finish_non_exhaustive()
+    }
+}
+
+/// Spawn a thread calling `handle` with every event read from
+/// `subscription`. When the stream ends, e.g. because the sound server was
+/// restarted, a new subscription is created from `backend` after
+/// [`RESUBSCRIBE_DELAY`]. The thread runs until [`Listener::stop`] is
+/// called.
+fn spawn_listener<F>(
+    backend: Arc<dyn AudioBackend>,
+    mut subscription: Box<dyn EventStream>,
+    mut handle: F,
+) -> Listener
+where
+    F: FnMut(&dyn AudioBackend, Event, EventDst) + Send + 'static,
+{
+    let closer: Closer = Arc::new(Mutex::new(Some(subscription.closer())));
+    let is_stopped = {
+        let closer = closer.clone();
+        move || closer.lock().unwrap().is_none()
+    };
+
+    {
+        let closer = closer.clone();
+        thread::spawn(move || {
+            let mut delay = RESUBSCRIBE_DELAY;
+
+            loop {
+                match subscription.read_event() {
+                    Ok(_) if is_stopped() => return,
+                    Ok(Some((event, dst))) => {
+                        delay = RESUBSCRIBE_DELAY;
+                        handle(&*backend, event, dst);
+                    }
+                    Ok(None) => {
+                        warn!("Audio event stream ended, subscribing again in {:?}", delay);
+                        thread::sleep(delay);
+                        delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+
+                        let stream = match backend.subscribe() {
+                            Ok(stream) => stream,
+                            Err(err) => {
+                                warn!("Failed to subscribe to audio events: {:?}", err);
+                                continue;
+                            }
+                        };
+
+                        // Replace the closer unless the listener was stopped
+                        // in the meantime, which drops the new stream.
+                        let mut closer = closer.lock().unwrap();
+                        match closer.as_mut() {
+                            Some(close) => *close = stream.closer(),
+                            None => return,
+                        }
+                        subscription = stream;
+                    }
+                    // Skip events that failed to parse.
+                    Err(_) => (),
+                }
+            }
+        });
+    }
+
+    Listener { closer }
+}
+
+/// The available [`AudioBackend`]s.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// PulseAudio using `pactl`.
+    Pactl,
+    /// PipeWire using `wpctl`.
+    Wpctl,
+}
+
+impl Backend {
+    /// Detect the backend from the available binaries. `wpctl` is preferred
+    /// over `pactl` since `pactl` may just be the compatibility shim of
+    /// PipeWire.
+    pub fn detect() -> Self {
+        match wpctl::is_available() {
+            true => Self::Wpctl,
+            false => Self::Pactl,
+        }
+    }
+
+    pub fn into_backend(self) -> Arc<dyn AudioBackend> {
+        match self {
+            Self::Pactl => Arc::new(PactlBackend),
+            Self::Wpctl => Arc::new(WpctlBackend::default()),
+        }
+    }
+}
+
+/// The [`AudioBackend`] shared between all audio buttons. Insert one into
+/// the typemap before the buttons are initialized to override the detected
+/// backend.
+#[derive(Clone)]
+pub struct AudioClient(pub Arc<dyn AudioBackend>);
+
+impl AudioClient {
+    /// Returns the `AudioClient` from the typemap, inserting a new one using
+    /// the detected [`Backend`] if none exists.
+    pub(crate) fn get_or_insert(state: &State) -> Arc<dyn AudioBackend> {
+        let mut typemap = state.typemap.write().unwrap();
+        if let Some(client) = typemap.get::<Self>() {
+            return client.0.clone();
+        }
+
+        let backend = Backend::detect();
+        debug!("Using audio backend {:?}", backend);
+
+        let client = Self(backend.into_backend());
+        typemap.insert(client.clone());
+        client.0
+    }
+}
+
+/// An [`AudioBackend`] for PulseAudio using `pactl`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PactlBackend;
+
+impl PactlBackend {
+    fn sink_name<'a>(sink: Device<'a>) -> &'a str {
+        match sink {
+            Device::Default => "@DEFAULT_SINK@",
+            Device::Name(name) => name,
+        }
+    }
+
+    fn source_name<'a>(source: Device<'a>) -> &'a str {
+        match source {
+            Device::Default => "@DEFAULT_SOURCE@",
+            Device::Name(name) => name,
+        }
+    }
+}
+
+impl AudioBackend for PactlBackend {
+    fn list_sinks(&self) -> Result<Vec<Sink>> {
+        Ok(pactl::list_sinks()?)
+    }
+
+    fn list_sources(&self) -> Result<Vec<Source>> {
+        Ok(pactl::list_sources()?)
+    }
+
+    fn default_sink(&self) -> Result<String> {
+        Ok(pactl::get_default_sink()?)
+    }
+
+    fn default_source(&self) -> Result<String> {
+        Ok(pactl::get_default_source()?)
+    }
+
+    fn set_sink_mute(&self, sink: Device, action: MuteAction) -> Result<()> {
+        Ok(pactl::set_sink_mute(Self::sink_name(sink), action)?)
+    }
+
+    fn set_source_mute(&self, source: Device, action: MuteAction) -> Result<()> {
+        Ok(pactl::set_source_mute(Self::source_name(source), action)?)
+    }
+
+    fn set_sink_volume(&self, sink: Device, volume: f32) -> Result<()> {
+        Ok(pactl::set_sink_volume(Self::sink_name(sink), volume)?)
+    }
+
+    fn set_default_sink(&self, name: &str) -> Result<()> {
+        pactl::set_default_sink(name)?;
+
+        // Changing the default sink doesn't affect streams that are already
+        // playing.
+        for id in pactl::list_sink_inputs()? {
+            pactl::move_sink_input(id, name)?;
+        }
+
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Result<Box<dyn EventStream>> {
+        Ok(Box::new(pactl::Subscription::new()?))
+    }
+}
+
+impl EventStream for pactl::Subscription {
+    fn read_event(&mut self) -> Result<Option<(Event, EventDst)>> {
+        Ok(pactl::Subscription::read_event(self)?)
+    }
+
+    fn closer(&self) -> Box<dyn FnOnce() + Send> {
+        Box::new(self.killer())
+    }
+}
+
+/// An [`AudioBackend`] for PipeWire using `wpctl`.
+#[derive(Debug, Default)]
+pub struct WpctlBackend {
+    names: wpctl::NodeNames,
+    poller: WpctlPoller,
+}
+
+impl WpctlBackend {
+    fn sink_id<'a>(sink: Device<'a>) -> &'a str {
+        match sink {
+            Device::Default => wpctl::DEFAULT_SINK,
+            Device::Name(name) => name,
+        }
+    }
+
+    fn source_id<'a>(source: Device<'a>) -> &'a str {
+        match source {
+            Device::Default => wpctl::DEFAULT_SOURCE,
+            Device::Name(name) => name,
+        }
+    }
+}
+
+impl AudioBackend for WpctlBackend {
+    fn list_sinks(&self) -> Result<Vec<Sink>> {
+        Ok(wpctl::list(&self.names)?.0)
+    }
+
+    fn list_sources(&self) -> Result<Vec<Source>> {
+        Ok(wpctl::list(&self.names)?.1)
+    }
+
+    fn default_sink(&self) -> Result<String> {
+        Ok(wpctl::node_name(wpctl::DEFAULT_SINK)?)
+    }
+
+    fn default_source(&self) -> Result<String> {
+        Ok(wpctl::node_name(wpctl::DEFAULT_SOURCE)?)
+    }
+
+    fn set_sink_mute(&self, sink: Device, action: MuteAction) -> Result<()> {
+        Ok(wpctl::set_mute(Self::sink_id(sink), action.as_arg())?)
+    }
+
+    fn set_source_mute(&self, source: Device, action: MuteAction) -> Result<()> {
+        Ok(wpctl::set_mute(Self::source_id(source), action.as_arg())?)
+    }
 
-const DEFAULT_SINK: &str = "alsa_output.pci-0000_0a_00.4.analog-stereo";
+    fn set_sink_volume(&self, sink: Device, volume: f32) -> Result<()> {
+        Ok(wpctl::set_volume(Self::sink_id(sink), volume)?)
+    }
+
+    fn set_default_sink(&self, name: &str) -> Result<()> {
+        // WirePlumber moves streams following the default sink on its own.
+        let sinks = self.list_sinks()?;
+        match sinks.iter().find(|sink| sink.name == name) {
+            Some(sink) => Ok(wpctl::set_default(&sink.id.to_string())?),
+            None => Err(wpctl::Error::SinkNotFound(name.to_owned()).into()),
+        }
+    }
+
+    fn subscribe(&self) -> Result<Box<dyn EventStream>> {
+        Ok(Box::new(self.poller.subscribe()))
+    }
+}
+
+/// The mute state and volume of all sinks and sources.
+type Devices = HashMap<EventDst, (bool, f32)>;
+
+/// A subscriber of a [`WpctlPoller`]. The subscriber is removed once
+/// `closed` is set.
+#[derive(Debug)]
+struct Subscriber {
+    tx: mpsc::Sender<(Event, EventDst)>,
+    closed: Arc<AtomicBool>,
+}
+
+/// Polls the state of all sinks and sources on a single thread shared by
+/// all subscriptions of a [`WpctlBackend`]. The thread runs while there are
+/// subscribers.
+#[derive(Debug, Default)]
+struct WpctlPoller {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl WpctlPoller {
+    fn subscribe(&self) -> PolledEvents {
+        let (tx, rx) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            let subscribers = self.subscribers.clone();
+            thread::spawn(move || poll_wpctl(&subscribers));
+        }
+
+        subscribers.push(Subscriber {
+            tx,
+            closed: closed.clone(),
+        });
+
+        PolledEvents { rx, closed }
+    }
+}
+
+/// Read the mute state and volume of all sinks and sources.
+fn read_devices() -> Result<Devices> {
+    let (sinks, sources) = wpctl::status()?;
+
+    let sinks = sinks
+        .into_iter()
+        .map(|node| (EventDst::Sink(node.id), (node.mute, node.volume)));
+    let sources = sources
+        .into_iter()
+        .map(|node| (EventDst::Source(node.id), (node.mute, node.volume)));
+
+    Ok(sinks.chain(sources).collect())
+}
+
+/// Returns the events for all changes from `old` to `new`.
+fn diff_devices(old: &Devices, new: &Devices) -> Vec<(Event, EventDst)> {
+    let mut events = Vec::new();
+    for (dst, value) in new {
+        match old.get(dst) {
+            Some(old) if old == value => (),
+            Some(_) => events.push((Event::Change, *dst)),
+            None => events.push((Event::New, *dst)),
+        }
+    }
+
+    for dst in old.keys() {
+        if !new.contains_key(dst) {
+            events.push((Event::Remove, *dst));
+        }
+    }
+
+    events
+}
+
+/// Poll `wpctl` and send every change to all `subscribers` until none are
+/// left.
+fn poll_wpctl(subscribers: &Mutex<Vec<Subscriber>>) {
+    // Read the initial state without emitting events for it.
+    let mut devices = read_devices().unwrap_or_default();
+
+    loop {
+        thread::sleep(WPCTL_POLLING_RATE);
+
+        let events = match read_devices() {
+            Ok(new) => {
+                let events = diff_devices(&devices, &new);
+                devices = new;
+                events
+            }
+            Err(err) => {
+                debug!("Failed to poll wpctl: {:?}", err);
+                Vec::new()
+            }
+        };
+
+        let mut subscribers = subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| {
+            !subscriber.closed.load(Ordering::SeqCst)
+                && events
+                    .iter()
+                    .all(|event| subscriber.tx.send(*event).is_ok())
+        });
+
+        if subscribers.is_empty() {
+            return;
+        }
+    }
+}
+
+/// The [`EventStream`] of a [`WpctlPoller`] subscription.
+struct PolledEvents {
+    rx: mpsc::Receiver<(Event, EventDst)>,
+    /// Set by the closer to end the stream.
+    closed: Arc<AtomicBool>,
+}
+
+impl EventStream for PolledEvents {
+    fn read_event(&mut self) -> Result<Option<(Event, EventDst)>> {
+        loop {
+            if self.closed.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+
+            match self.rx.recv_timeout(WPCTL_POLLING_RATE) {
+                Ok(event) => return Ok(Some(event)),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            }
+        }
+    }
+
+    fn closer(&self) -> Box<dyn FnOnce() + Send> {
+        let closed = self.closed.clone();
+        Box::new(move || closed.store(true, Ordering::SeqCst))
+    }
+}
 
 /// Deafen/Undeafen the system-wide audio output stream.
 #[derive(Clone, Debug)]
 pub struct DeafenButton {
     mute: Arc<AtomicBool>,
     icons: [DynamicImage; 2],
+    /// The thread listening on backend events, if started.
+    listener: Option<Listener>,
 }
 
 impl DeafenButton {
@@ -45,72 +514,104 @@ impl Default for DeafenButton {
         Self {
             mute: Arc::new(AtomicBool::new(false)),
             icons: [icon_mute_off, icon_mute_on],
+            listener: None,
         }
     }
 }
 
 #[async_trait]
 impl Button for DeafenButton {
-    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        if self.listener.is_some() {
+            return self.render(self.mute.load(Ordering::SeqCst), key);
+        }
+
+        let backend = AudioClient::get_or_insert(state);
+
         // Create a new `Arc` pointing to `self` to allow the task listening
-        // on pactl events to mutate data.
+        // on backend events to mutate data.
         let self_ref = Arc::new(self.clone());
 
-        // The id of the default sink.
-        let default_sink = DEFAULT_SINK;
+        // Create a new event subscription and read all events. Only proceed
+        // when the event changes a property on the default sink.
+        let subscription = backend.subscribe()?;
 
         {
             let key = key.clone();
-            std::thread::spawn(move || {
-                // Create a new pactl event subscription and read all events. Only proceed
-                // when the event changes a property on the default sink.
-                let mut pactl_subscription = Subscription::new();
-                loop {
-                    while let Ok(event) = pactl_subscription.read_event() {
-                        // Ony listen on sink changes.
-                        if event.0 == Event::Change && matches!(event.1, EventDst::Sink(_)) {
-                            // Get all sinks.
-                            let sinks = list_sinks().unwrap();
-                            // Find the default sink.
-                            let sink = match sinks.iter().find(|s| s.name == default_sink) {
-                                Some(sink) => sink,
-                                None => continue,
-                            };
-                            // If the data from the actual sink missmatches the current state
-                            // swap the bool and rerender the key.
-                            if sink.mute != self_ref.mute.load(Ordering::SeqCst) {
-                                self_ref.toggle(key.clone()).unwrap();
-                            }
-                        }
-                    }
+            let listener = spawn_listener(backend, subscription, move |backend, event, dst| {
+                // Ony listen on sink changes.
+                if event != Event::Change || !matches!(dst, EventDst::Sink(_)) {
+                    return;
+                }
+
+                // Get all sinks. Skip the event if the backend is not
+                // reachable right now.
+                let sinks = match backend.list_sinks() {
+                    Ok(sinks) => sinks,
+                    Err(_) => return,
+                };
+                // Find the default sink. It is looked up on every
+                // event to follow changes of the default.
+                let default_sink = match backend.default_sink() {
+                    Ok(name) => name,
+                    Err(_) => return,
+                };
+                let sink = match sinks.iter().find(|s| s.name == default_sink) {
+                    Some(sink) => sink,
+                    None => return,
+                };
+                // If the data from the actual sink missmatches the current state
+                // swap the bool and rerender the key.
+                if sink.mute != self_ref.mute.load(Ordering::SeqCst) {
+                    self_ref.toggle(key.clone()).unwrap();
                 }
             });
+            self.listener = Some(listener);
         }
 
         self.render(false, key)
     }
 
-    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
-        match set_sink_mute("@DEFAULT_SINK@", MuteAction::Toggle) {
-            Ok(()) => Ok(()),
-            Err(err) => Err(Error::BoxError(Box::new(err))),
+    async fn on_click(&mut self, state: &mut State, _: Key) -> Result<()> {
+        let backend = AudioClient::get_or_insert(state);
+
+        backend.set_sink_mute(Device::Default, MuteAction::Toggle)
+    }
+
+    async fn deinit(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        if let Some(listener) = self.listener.take() {
+            listener.stop();
         }
+
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+/// Mute/Unmute the default audio input stream.
+#[derive(Clone, Debug)]
 pub struct MuteButton {
-    mute: bool,
+    mute: Arc<AtomicBool>,
     icons: [DynamicImage; 2],
+    /// The thread listening on backend events, if started.
+    listener: Option<Listener>,
 }
 
 impl MuteButton {
-    fn render(&self, key: Key) -> Result<()> {
-        match self.mute {
+    /// Rerender the button based on the value `value`.
+    fn render(&self, value: bool, key: Key) -> Result<()> {
+        match value {
             false => key.image(self.icons[0].clone()),
             true => key.image(self.icons[1].clone()),
         }
     }
+
+    /// Invert the value of the `mute` field and rerender the button.
+    /// This does not change the audio stream itself.
+    fn toggle(&self, key: Key) -> Result<()> {
+        let value = self.mute.load(Ordering::SeqCst);
+        self.mute.store(!value, Ordering::SeqCst);
+        self.render(!value, key)
+    }
 }
 
 impl Default for MuteButton {
@@ -119,22 +620,187 @@ impl Default for MuteButton {
         let icon_mute_on = load_icon!("../../icons/audio/audio_mute_on.png");
 
         Self {
-            mute: false,
+            mute: Arc::new(AtomicBool::new(false)),
             icons: [icon_mute_off, icon_mute_on],
+            listener: None,
         }
     }
 }
 
 #[async_trait]
 impl Button for MuteButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        if self.listener.is_some() {
+            return self.render(self.mute.load(Ordering::SeqCst), key);
+        }
+
+        let backend = AudioClient::get_or_insert(state);
+
+        // Read the initial mute state of the default source.
+        let mute = default_source_mute(&*backend)?.unwrap_or(false);
+        self.mute.store(mute, Ordering::SeqCst);
+
+        // Create a new `Arc` pointing to `self` to allow the task listening
+        // on backend events to mutate data.
+        let self_ref = Arc::new(self.clone());
+
+        // Create a new event subscription and read all events. Only proceed
+        // when the event changes a property on the default source.
+        let subscription = backend.subscribe()?;
+
+        {
+            let key = key.clone();
+            let listener = spawn_listener(backend, subscription, move |backend, event, dst| {
+                // Only listen on source changes.
+                if event != Event::Change || !matches!(dst, EventDst::Source(_)) {
+                    return;
+                }
+
+                // Find the default source. It is looked up on every event to
+                // follow changes of the default. Skip the event if the
+                // backend is not reachable right now.
+                let mute = match default_source_mute(backend) {
+                    Ok(Some(mute)) => mute,
+                    _ => return,
+                };
+                // If the data from the actual source missmatches the current state
+                // swap the bool and rerender the key.
+                if mute != self_ref.mute.load(Ordering::SeqCst) {
+                    self_ref.toggle(key.clone()).unwrap();
+                }
+            });
+            self.listener = Some(listener);
+        }
+
+        self.render(mute, key)
+    }
+
+    async fn on_click(&mut self, state: &mut State, _: Key) -> Result<()> {
+        let backend = AudioClient::get_or_insert(state);
+
+        backend.set_source_mute(Device::Default, MuteAction::Toggle)
+    }
+
+    async fn deinit(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        if let Some(listener) = self.listener.take() {
+            listener.stop();
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the mute state of the default source, or `None` if the default
+/// source is not listed.
+fn default_source_mute(backend: &dyn AudioBackend) -> Result<Option<bool>> {
+    let default_source = backend.default_source()?;
+
+    Ok(backend
+        .list_sources()?
+        .iter()
+        .find(|s| s.name == default_source)
+        .map(|s| s.mute))
+}
+
+/// Switch the default audio output to a sink and move all playing streams to
+/// it.
+#[derive(Clone, Debug)]
+pub struct OutputSwitchButton {
+    /// The name of the target sink.
+    sink: String,
+}
+
+impl OutputSwitchButton {
+    pub fn new<T>(sink: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self { sink: sink.into() }
+    }
+}
+
+#[async_trait]
+impl Button for OutputSwitchButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let backend = AudioClient::get_or_insert(state);
+
+        // Show the description of the target sink, falling back to its name
+        // if the sink is not available right now.
+        let label = backend
+            .list_sinks()
+            .ok()
+            .and_then(|sinks| sinks.into_iter().find(|sink| sink.name == self.sink))
+            .map(|sink| sink.description)
+            .unwrap_or_else(|| self.sink.clone());
+
+        key.text(&label, (255, 255, 255), (0, 0, 0))
+    }
+
+    async fn on_click(&mut self, state: &mut State, _: Key) -> Result<()> {
+        let backend = AudioClient::get_or_insert(state);
+
+        backend.set_default_sink(&self.sink)
+    }
+}
+
+/// Some steps of [`AudioResetButton`] failed. Contains the description and
+/// error of every failed step.
+#[derive(Debug)]
+pub struct ResetFailed(Vec<(&'static str, Error)>);
+
+impl Display for ResetFailed {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "failed to reset audio:")?;
+        for (step, err) in &self.0 {
+            write!(f, " {}: {:?};", step, err)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResetFailed {}
+
+/// Resets the audio to sane defaults: unmutes the default source and sink
+/// and sets the volume of the default sink to 100%. The [`MuteButton`] and
+/// [`DeafenButton`] pick up the changes on their own.
+#[derive(Clone, Debug, Default)]
+pub struct AudioResetButton {}
+
+#[async_trait]
+impl Button for AudioResetButton {
     async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
-        self.render(key)
+        key.text("Reset Audio", (255, 255, 255), (0, 0, 0))
     }
 
-    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
-        match set_source_mute("@DEFAULT_SOURCE@", MuteAction::Toggle) {
-            Ok(()) => Ok(()),
-            Err(err) => Err(Error::BoxError(Box::new(err))),
+    async fn on_click(&mut self, state: &mut State, _: Key) -> Result<()> {
+        let backend = AudioClient::get_or_insert(state);
+
+        // Run all steps even if one fails to get as close to the defaults as
+        // possible.
+        let steps = [
+            (
+                "unmute source",
+                backend.set_source_mute(Device::Default, MuteAction::Off),
+            ),
+            (
+                "unmute sink",
+                backend.set_sink_mute(Device::Default, MuteAction::Off),
+            ),
+            (
+                "set sink volume",
+                backend.set_sink_volume(Device::Default, 1.0),
+            ),
+        ];
+
+        let errors: Vec<_> = steps
+            .into_iter()
+            .filter_map(|(step, res)| res.err().map(|err| (step, err)))
+            .collect();
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ResetFailed(errors).into()),
         }
     }
 }
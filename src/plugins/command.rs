@@ -0,0 +1,274 @@
+use crate::{
+    core::{Button, Key, Result, State},
+    debug, error,
+};
+use async_trait::async_trait;
+use image::{DynamicImage, Rgb, RgbImage};
+use imageproc::drawing::draw_filled_circle_mut;
+use std::f32::consts::PI;
+use std::process::Stdio;
+use std::time::Instant;
+use tokio::{process::Command, task, time::Duration};
+
+/// The number of dots of the busy indicator.
+const SPINNER_DOTS: u32 = 8;
+
+/// The time every frame of the busy indicator is shown.
+const SPINNER_DELAY: Duration = Duration::from_millis(100);
+
+/// The maximum number of characters of output shown by
+/// [`CommandStatusButton`].
+const MAX_STATUS_LEN: usize = 24;
+
+/// Runs a command. A busy indicator is shown on the key while the command
+/// is running.
+#[derive(Debug)]
+pub struct CommandButton {
+    program: String,
+    args: Vec<String>,
+    icon: Option<DynamicImage>,
+}
+
+impl CommandButton {
+    pub fn new<T, I, A>(program: T, args: I) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            icon: None,
+        }
+    }
+
+    /// Show `icon` on the key. The name of the program is shown if no icon
+    /// is set.
+    pub fn icon(mut self, icon: DynamicImage) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    fn render(program: &str, icon: Option<&DynamicImage>, key: &Key) -> Result<()> {
+        match icon {
+            Some(icon) => key.image(icon.clone()),
+            None => key.text(program, (255, 255, 255), (0, 0, 0)),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for CommandButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        Self::render(&self.program, self.icon.as_ref(), &key)
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let mut child = Command::new(&self.program).args(&self.args).spawn()?;
+
+        let (width, height) = key.image_size();
+        let frames = (0..SPINNER_DOTS)
+            .map(|step| render_spinner(width, height, step))
+            .collect();
+        let animation = key.animate(frames, SPINNER_DELAY);
+
+        // Wait for the command in the background to not block other keys.
+        let program = self.program.clone();
+        let icon = self.icon.clone();
+        task::spawn(async move {
+            match child.wait().await {
+                Ok(status) => debug!("[Command] {} exited with {}", program, status),
+                Err(err) => error!("[Command] Failed to wait for {}: {:?}", program, err),
+            }
+
+            animation.stop().await;
+            let _ = Self::render(&program, icon.as_ref(), &key);
+        });
+
+        Ok(())
+    }
+}
+
+/// Periodically runs a command and shows the first line of its output on
+/// the key, e.g. the current git branch. The key turns red if the command
+/// exits with an error. Pressing the key runs the command immediately.
+///
+/// The command is run from [`Button::on_tick`], so [`State::tick`] must be
+/// set to a duration shorter than the interval.
+///
+/// [`State::tick`]: crate::core::State::tick
+#[derive(Debug)]
+pub struct CommandStatusButton {
+    program: String,
+    args: Vec<String>,
+    interval: Duration,
+    /// The time the command was last started.
+    last_run: Option<Instant>,
+    /// The task waiting for the running command.
+    running: Option<task::JoinHandle<()>>,
+}
+
+impl CommandStatusButton {
+    /// Creates a new `CommandStatusButton` running `program` with `args`
+    /// every 10 seconds.
+    pub fn new<T, I, A>(program: T, args: I) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            interval: Duration::from_secs(10),
+            last_run: None,
+            running: None,
+        }
+    }
+
+    /// Sets the interval in which the command is run.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run the command in the background and render its output once it
+    /// exits. Does nothing if the command is still running.
+    fn run(&mut self, key: Key) {
+        if self
+            .running
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+        {
+            return;
+        }
+
+        self.last_run = Some(Instant::now());
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let program = self.program.clone();
+        self.running = Some(task::spawn(async move {
+            let res = match command.output().await {
+                Ok(output) if output.status.success() => key.text(
+                    &status_line(&String::from_utf8_lossy(&output.stdout)),
+                    (255, 255, 255),
+                    (0, 0, 0),
+                ),
+                Ok(output) => {
+                    debug!("[CommandStatus] {} exited with {}", program, output.status);
+
+                    // Prefer the error message over the exit code.
+                    let text = match status_line(&String::from_utf8_lossy(&output.stderr)) {
+                        line if line.is_empty() => output.status.to_string(),
+                        line => line,
+                    };
+                    key.text(&text, (255, 255, 255), (180, 0, 0))
+                }
+                Err(err) => {
+                    error!("[CommandStatus] Failed to run {}: {:?}", program, err);
+                    key.text(&program, (255, 255, 255), (180, 0, 0))
+                }
+            };
+
+            if let Err(err) = res {
+                error!("[CommandStatus] Failed to render output: {:?}", err);
+            }
+        }));
+    }
+}
+
+#[async_trait]
+impl Button for CommandStatusButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.text(&self.program, (120, 120, 120), (0, 0, 0))?;
+
+        self.run(key);
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.run(key);
+        Ok(())
+    }
+
+    async fn on_tick(&mut self, _: &mut State, key: Key) -> Result<()> {
+        if self
+            .last_run
+            .map_or(true, |last_run| last_run.elapsed() >= self.interval)
+        {
+            self.run(key);
+        }
+
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        // Aborting the task kills the running command.
+        if let Some(task) = self.running.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the first non-empty line of `output`, cut off after
+/// [`MAX_STATUS_LEN`] characters.
+fn status_line(output: &str) -> String {
+    let line = output
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+
+    match line.char_indices().nth(MAX_STATUS_LEN) {
+        Some((index, _)) => format!("{}..", &line[..index]),
+        None => line.to_owned(),
+    }
+}
+
+/// Render a frame of the busy indicator: A circle of dots with the dot at
+/// `step` highlighted.
+fn render_spinner(width: u32, height: u32, step: u32) -> DynamicImage {
+    let mut image = RgbImage::new(width, height);
+
+    let center = (width as f32 / 2.0, height as f32 / 2.0);
+    let radius = width.min(height) as f32 / 3.0;
+    let dot_radius = (radius / 5.0) as i32;
+
+    for dot in 0..SPINNER_DOTS {
+        let angle = 2.0 * PI * dot as f32 / SPINNER_DOTS as f32;
+        let x = center.0 + radius * angle.sin();
+        let y = center.1 - radius * angle.cos();
+
+        let color = match dot == step % SPINNER_DOTS {
+            true => Rgb([255, 255, 255]),
+            false => Rgb([80, 80, 80]),
+        };
+
+        draw_filled_circle_mut(&mut image, (x as i32, y as i32), dot_radius, color);
+    }
+
+    DynamicImage::ImageRgb8(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::status_line;
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(status_line("\n  main  \nsecond\n"), "main");
+        assert_eq!(status_line(""), "");
+        assert_eq!(
+            status_line("connected to vpn-gateway.example.org"),
+            "connected to vpn-gateway.."
+        );
+    }
+}
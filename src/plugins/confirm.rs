@@ -0,0 +1,99 @@
+use crate::core::{Button, Key, Result, State};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::{task, time};
+
+/// The color of an armed key.
+const ARMED_COLOR: (u8, u8, u8) = (255, 140, 0);
+
+/// Guards a button against accidental presses. The first press arms the
+/// button and paints the key orange, only a second press within the timeout
+/// is forwarded to the wrapped button. Otherwise the button disarms and the
+/// previous image is restored.
+pub struct ConfirmButton {
+    inner: Box<dyn Button>,
+    timeout: Duration,
+    /// The generation of the current arming, `None` while disarmed.
+    armed: Arc<Mutex<Option<u64>>>,
+    generation: u64,
+}
+
+impl ConfirmButton {
+    /// Wrap `inner`. The confirmation timeout defaults to 3 seconds.
+    pub fn new(inner: Box<dyn Button>) -> Self {
+        Self {
+            inner,
+            timeout: Duration::from_secs(3),
+            armed: Arc::new(Mutex::new(None)),
+            generation: 0,
+        }
+    }
+
+    /// Set the time in which the second press must follow the first one.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Arm the button and spawn a task disarming it after the timeout.
+    fn arm(&mut self, key: Key) -> Result<()> {
+        self.generation += 1;
+        let generation = self.generation;
+
+        *self.armed.lock().unwrap() = Some(generation);
+        key.overlay(ARMED_COLOR)?;
+
+        let armed = self.armed.clone();
+        let timeout = self.timeout;
+        task::spawn(async move {
+            time::sleep(timeout).await;
+
+            let mut armed = armed.lock().unwrap();
+            if *armed == Some(generation) {
+                *armed = None;
+                let _ = key.restore();
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Button for ConfirmButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.init(state, key).await
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let confirmed = self.armed.lock().unwrap().take().is_some();
+
+        if !confirmed {
+            return self.arm(key);
+        }
+
+        key.restore()?;
+        self.inner.on_click(state, key).await
+    }
+
+    async fn on_long_press(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.on_long_press(state, key).await
+    }
+
+    async fn on_hold_release(&mut self, state: &mut State, key: Key, held: Duration) -> Result<()> {
+        self.inner.on_hold_release(state, key, held).await
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.on_tick(state, key).await
+    }
+
+    async fn deinit(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.deinit(state, key).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
@@ -0,0 +1,151 @@
+use crate::core::{Button, Color, Key, Result, State};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::watch;
+use tokio::task::{self, JoinHandle};
+
+/// The counts shown by [`CounterButton`]s, keyed by the id of the counter.
+#[derive(Default)]
+struct Counters(HashMap<String, watch::Sender<u32>>);
+
+impl Counters {
+    /// Calls `f` with the sender of the counter `id`, registering the
+    /// counter with a count of 0 if it doesn't exist.
+    fn with<F, R>(state: &State, id: &str, f: F) -> R
+    where
+        F: FnOnce(&watch::Sender<u32>) -> R,
+    {
+        state.with_typemap(|typemap| {
+            if !typemap.contains_key::<Self>() {
+                typemap.insert(Self::default());
+            }
+
+            let counters = typemap.get_mut::<Self>().unwrap();
+            f(counters
+                .0
+                .entry(id.to_owned())
+                .or_insert_with(|| watch::channel(0).0))
+        })
+    }
+}
+
+impl State {
+    /// Sets the counter `id` to `count`, rerendering all [`CounterButton`]s
+    /// showing it. Only counters registered by a [`CounterButton`] can be
+    /// set. Returns `false` if the counter is not registered.
+    pub fn set_counter(&self, id: &str, count: u32) -> bool {
+        self.with_typemap(|typemap| {
+            let tx = typemap
+                .get::<Counters>()
+                .and_then(|counters| counters.0.get(id));
+            match tx {
+                Some(tx) => {
+                    tx.send_replace(count);
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+}
+
+/// Shows a count pushed from elsewhere using [`State::set_counter`], e.g.
+/// the number of unread messages. The background is colored while the count
+/// is not zero.
+#[derive(Debug)]
+pub struct CounterButton {
+    id: String,
+    color: Color,
+    /// The task rerendering the key once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl CounterButton {
+    /// Creates a new `CounterButton` showing the counter `id`.
+    pub fn new<T>(id: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            id: id.to_string(),
+            color: Color::from((200, 0, 0)),
+            task: None,
+        }
+    }
+
+    /// Sets the background color used while the count is not zero.
+    pub fn color<T>(mut self, color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        self.color = color.into();
+        self
+    }
+}
+
+fn render(count: u32, color: &Color, key: &Key) -> Result<()> {
+    match count {
+        0 => key.text("0", (120, 120, 120), (0, 0, 0)),
+        count => key.text(&count.to_string(), (255, 255, 255), color.clone()),
+    }
+}
+
+#[async_trait]
+impl Button for CounterButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut counter = Counters::with(state, &self.id, |tx| tx.subscribe());
+        let count = *counter.borrow_and_update();
+        render(count, &self.color, &key)?;
+
+        if self.task.is_some() {
+            return Ok(());
+        }
+
+        // Rerender the key whenever the count changes.
+        let color = self.color.clone();
+        self.task = Some(task::spawn(async move {
+            while counter.changed().await.is_ok() {
+                let count = *counter.borrow_and_update();
+                let _ = render(count, &color, &key);
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CounterButton;
+    use crate::core::mock::MockStreamDeck;
+    use crate::core::{Button, State};
+    use streamdeck::Kind;
+
+    #[tokio::test]
+    async fn test_set_counter() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        // Only counters shown by a button can be set.
+        assert!(!state.set_counter("mail", 4));
+
+        let mut button = CounterButton::new("mail");
+        button.init(&mut state, deck.key(0)).await.unwrap();
+        assert!(state.set_counter("mail", 4));
+        assert!(!state.set_counter("chat", 4));
+
+        button.deinit(&mut state, deck.key(0)).await.unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::core::{Button, Key, Result, State};
+use async_trait::async_trait;
+use image::DynamicImage;
+
+/// An action run by a [`CycleButton`].
+pub type Action = Box<dyn Fn(&mut State) -> Result<()> + Send + Sync>;
+
+/// Cycles through a list of entries. Every click advances to the next entry,
+/// runs its action and shows its icon.
+///
+/// ```ignore
+/// CycleButton::new()
+///     .entry(headphones, |_| pactl::set_default_sink("headphones"))
+///     .entry(speakers, |_| pactl::set_default_sink("speakers"))
+/// ```
+#[derive(Default)]
+pub struct CycleButton {
+    entries: Vec<(DynamicImage, Action)>,
+    index: usize,
+}
+
+impl CycleButton {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry showing `icon` that runs `action` when it is selected.
+    pub fn entry<F>(mut self, icon: DynamicImage, action: F) -> Self
+    where
+        F: Fn(&mut State) -> Result<()> + Send + Sync + 'static,
+    {
+        self.entries.push((icon, Box::new(action)));
+        self
+    }
+
+    fn render(&self, key: Key) -> Result<()> {
+        match self.entries.get(self.index) {
+            Some((icon, _)) => key.image(icon.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for CycleButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.render(key)
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        self.index = (self.index + 1) % self.entries.len();
+
+        let (_, action) = &self.entries[self.index];
+        action(state)?;
+
+        self.render(key)
+    }
+}
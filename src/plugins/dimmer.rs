@@ -0,0 +1,112 @@
+use crate::core::{Button, Key, Result, State};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::time;
+
+/// The delay between two brightness steps while the key is held.
+const STEP_DELAY: Duration = Duration::from_millis(50);
+
+/// Ramps the brightness of the deck while the key is held. Presses
+/// alternate between ramping down to the minimum brightness and ramping
+/// back up to 100%. The key shows the current brightness as a bar.
+#[derive(Debug)]
+pub struct DimmerButton {
+    min: u8,
+    step: u8,
+    /// Whether the next press ramps the brightness down.
+    dim: bool,
+}
+
+impl DimmerButton {
+    /// Creates a new `DimmerButton` that never dims the deck below `min`
+    /// percent, so the deck can't go fully dark.
+    pub fn new(min: u8) -> Self {
+        Self {
+            min: min.clamp(1, 100),
+            step: 2,
+            dim: true,
+        }
+    }
+
+    /// Sets the brightness change per step in percent. Steps are taken
+    /// every 50ms while the key is held.
+    pub fn step(mut self, step: u8) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
+    fn render(&self, key: &Key, brightness: u8) -> Result<()> {
+        key.progress_bar(brightness, (255, 200, 0), (0, 0, 0))
+    }
+}
+
+impl Default for DimmerButton {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+#[async_trait]
+impl Button for DimmerButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.render(&key, state.brightness)
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let target = match self.dim {
+            true => self.min,
+            false => 100,
+        };
+        self.dim = !self.dim;
+
+        // Take at least one step so a short tap still changes the
+        // brightness.
+        loop {
+            let brightness = match state.brightness < target {
+                true => state.brightness.saturating_add(self.step).min(target),
+                false => state.brightness.saturating_sub(self.step).max(target),
+            };
+
+            // Remember the brightness so it is restored after idle mode.
+            state.brightness = brightness;
+            key.set_brightness(brightness)?;
+            self.render(&key, brightness)?;
+
+            if brightness == target || !key.is_pressed() {
+                return Ok(());
+            }
+
+            time::sleep(STEP_DELAY).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DimmerButton;
+    use crate::core::mock::MockStreamDeck;
+    use crate::core::{Button, State};
+    use streamdeck::Kind;
+
+    #[tokio::test]
+    async fn test_dimmer_button() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+        let mut button = DimmerButton::new(90).step(4);
+
+        // A tap takes a single step.
+        button.on_click(&mut state, deck.key(0)).await.unwrap();
+        assert_eq!(state.brightness, 96);
+        assert_eq!(deck.brightness(), Some(96));
+        assert!(deck.image(0).is_some());
+
+        // Holding the key ramps down to the minimum.
+        button.on_click(&mut state, deck.key(0)).await.unwrap();
+        assert_eq!(state.brightness, 100);
+
+        deck.set_pressed(0, true);
+        button.on_click(&mut state, deck.key(0)).await.unwrap();
+        assert_eq!(state.brightness, 90);
+        assert_eq!(deck.brightness(), Some(90));
+    }
+}
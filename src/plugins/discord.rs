@@ -0,0 +1,454 @@
+//! Mute/Deafen in Discord using the local RPC socket of the Discord client.
+//!
+//! Changing the voice settings requires an OAuth2 access token with the
+//! `rpc` and `rpc.voice.write` scopes for a Discord application. Insert a
+//! [`DiscordConfig`] into the typemap before the buttons are initialized.
+
+use crate::{
+    core::{Button, Error, Key, Result, State},
+    debug, error, load_icon,
+};
+use async_trait::async_trait;
+use image::DynamicImage;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    sync::{mpsc, oneshot, watch},
+    task::{self, JoinHandle},
+};
+
+/// The opcodes of the IPC frames.
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+
+/// The Discord client listens on the first free socket of
+/// `discord-ipc-0` to `discord-ipc-9`.
+const MAX_SOCKETS: u8 = 10;
+
+/// The maximum length of a frame payload accepted from the Discord client.
+const MAX_FRAME_LEN: u32 = 1 << 20;
+
+/// The credentials used to authenticate with the Discord client.
+#[derive(Clone, Debug)]
+pub struct DiscordConfig {
+    /// The id of the Discord application.
+    pub client_id: String,
+    /// An OAuth2 access token of the application with the `rpc` and
+    /// `rpc.voice.write` scopes.
+    pub access_token: String,
+}
+
+/// The voice settings of the Discord user.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoiceSettings {
+    pub mute: bool,
+    pub deaf: bool,
+}
+
+/// An error returned by the Discord client.
+#[derive(Debug)]
+pub struct DiscordError(String);
+
+impl Display for DiscordError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Discord error: {}", self.0)
+    }
+}
+
+impl std::error::Error for DiscordError {}
+
+/// Discord RPC client shared between all buttons.
+#[derive(Clone, Debug)]
+struct DiscordClient {
+    tx: mpsc::Sender<Message>,
+    /// The last voice settings reported by the Discord client.
+    settings: watch::Receiver<VoiceSettings>,
+}
+
+enum Message {
+    SetVoiceSettings(Value, oneshot::Sender<Result<()>>),
+}
+
+impl DiscordClient {
+    /// Returns the `DiscordClient` from the typemap, connecting a new one if
+    /// none exists or the connection of the existing one was closed. Returns
+    /// an error if no [`DiscordConfig`] was inserted.
+    fn get_or_insert(state: &State) -> Result<Self> {
+        let mut typemap = state.typemap.write().unwrap();
+        if let Some(client) = typemap.get::<Self>() {
+            if !client.tx.is_closed() {
+                return Ok(client.clone());
+            }
+        }
+
+        let config = match typemap.get::<DiscordConfig>() {
+            Some(config) => config.clone(),
+            None => return Err(DiscordError(String::from("missing DiscordConfig")).into()),
+        };
+
+        let (tx, rx) = mpsc::channel(32);
+        let (settings_tx, settings) = watch::channel(VoiceSettings::default());
+
+        task::spawn(async move {
+            if let Err(err) = run(config, rx, settings_tx).await {
+                error!("[Discord] {:?}", err);
+            }
+        });
+
+        // Replaces the closed client, if any.
+        let client = Self { tx, settings };
+        typemap.insert(client.clone());
+        Ok(client)
+    }
+
+    /// Change the voice settings to the fields in `settings`.
+    async fn set_voice_settings(&self, settings: Value) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.tx.send(Message::SetVoiceSettings(settings, tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+}
+
+/// Connect to the first available Discord IPC socket.
+async fn connect() -> io::Result<UnixStream> {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+
+    let mut last_err = io::Error::from(io::ErrorKind::NotFound);
+    for i in 0..MAX_SOCKETS {
+        match UnixStream::connect(dir.join(format!("discord-ipc-{}", i))).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+async fn write_frame(writer: &mut OwnedWriteHalf, op: u32, payload: &Value) -> Result<()> {
+    let payload = serde_json::to_vec(payload)?;
+
+    let mut buf = Vec::with_capacity(8 + payload.len());
+    buf.extend(op.to_le_bytes());
+    buf.extend((payload.len() as u32).to_le_bytes());
+    buf.extend(payload);
+
+    writer.write_all(&buf).await?;
+    Ok(())
+}
+
+async fn read_frame(reader: &mut OwnedReadHalf) -> Result<(u32, Value)> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).await?;
+
+    let op = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if len > MAX_FRAME_LEN {
+        return Err(DiscordError(format!("frame of {} bytes is too large", len)).into());
+    }
+
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+
+    Ok((op, serde_json::from_slice(&payload)?))
+}
+
+/// Read frames until the response to the command with `nonce` arrives.
+/// Returns an error if the command failed.
+async fn read_response(reader: &mut OwnedReadHalf, nonce: &str) -> Result<Value> {
+    loop {
+        let (op, payload) = read_frame(reader).await?;
+        if op == OP_CLOSE {
+            return Err(DiscordError(payload["message"].to_string()).into());
+        }
+
+        if payload["nonce"] == nonce {
+            return response_data(payload);
+        }
+    }
+}
+
+/// Returns the data of a command response or an error if the command failed.
+fn response_data(mut payload: Value) -> Result<Value> {
+    match payload["evt"].as_str() {
+        Some("ERROR") => Err(DiscordError(payload["data"]["message"].to_string()).into()),
+        _ => Ok(payload["data"].take()),
+    }
+}
+
+fn parse_voice_settings(data: &Value) -> Option<VoiceSettings> {
+    Some(VoiceSettings {
+        mute: data["mute"].as_bool()?,
+        deaf: data["deaf"].as_bool()?,
+    })
+}
+
+/// Connect to the Discord client and handle messages until all clients are
+/// dropped.
+async fn run(
+    config: DiscordConfig,
+    mut rx: mpsc::Receiver<Message>,
+    settings: watch::Sender<VoiceSettings>,
+) -> Result<()> {
+    let (mut reader, mut writer) = connect().await?.into_split();
+
+    write_frame(
+        &mut writer,
+        OP_HANDSHAKE,
+        &json!({ "v": 1, "client_id": config.client_id }),
+    )
+    .await?;
+
+    // The client answers the handshake with a READY event.
+    loop {
+        let (op, payload) = read_frame(&mut reader).await?;
+        if op == OP_CLOSE {
+            return Err(DiscordError(payload["message"].to_string()).into());
+        }
+
+        if payload["evt"] == "READY" {
+            break;
+        }
+    }
+
+    let mut nonce = 0u64;
+    let mut command = |cmd: &str, args: Value, evt: Option<&str>| {
+        nonce += 1;
+
+        let mut payload = json!({ "cmd": cmd, "args": args, "nonce": nonce.to_string() });
+        if let Some(evt) = evt {
+            payload["evt"] = evt.into();
+        }
+
+        (nonce.to_string(), payload)
+    };
+
+    let (id, payload) = command(
+        "AUTHENTICATE",
+        json!({ "access_token": config.access_token }),
+        None,
+    );
+    write_frame(&mut writer, OP_FRAME, &payload).await?;
+    read_response(&mut reader, &id).await?;
+
+    debug!("[Discord] Authenticated");
+
+    let (id, payload) = command("GET_VOICE_SETTINGS", json!({}), None);
+    write_frame(&mut writer, OP_FRAME, &payload).await?;
+    if let Some(initial) = parse_voice_settings(&read_response(&mut reader, &id).await?) {
+        let _ = settings.send(initial);
+    }
+
+    let (id, payload) = command("SUBSCRIBE", json!({}), Some("VOICE_SETTINGS_UPDATE"));
+    write_frame(&mut writer, OP_FRAME, &payload).await?;
+    read_response(&mut reader, &id).await?;
+
+    // Read frames on a separate task so reading is never cancelled in the
+    // middle of a frame.
+    let (frames_tx, mut frames) = mpsc::unbounded_channel();
+    task::spawn(async move {
+        loop {
+            let frame = read_frame(&mut reader).await;
+            let is_err = frame.is_err();
+
+            if frames_tx.send(frame).is_err() || is_err {
+                break;
+            }
+        }
+    });
+
+    // Commands waiting for their response by nonce.
+    let mut pending: HashMap<String, oneshot::Sender<Result<()>>> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(Message::SetVoiceSettings(args, tx)) => {
+                    let (id, payload) = command("SET_VOICE_SETTINGS", args, None);
+
+                    match write_frame(&mut writer, OP_FRAME, &payload).await {
+                        Ok(()) => {
+                            pending.insert(id, tx);
+                        }
+                        Err(err) => {
+                            let _ = tx.send(Err(err));
+                        }
+                    }
+                }
+                None => return Ok(()),
+            },
+            frame = frames.recv() => {
+                let (op, payload) = match frame {
+                    Some(frame) => frame?,
+                    None => return Ok(()),
+                };
+
+                if op == OP_CLOSE {
+                    return Err(DiscordError(payload["message"].to_string()).into());
+                }
+
+                if let Some(new_settings) = parse_voice_settings(&payload["data"]) {
+                    let _ = settings.send(new_settings);
+                }
+
+                let tx = payload["nonce"].as_str().and_then(|id| pending.remove(id));
+                if let Some(tx) = tx {
+                    let _ = tx.send(response_data(payload).map(|_| ()));
+                }
+            }
+        }
+    }
+}
+
+/// Spawn a task rerendering the key whenever the voice settings of `client`
+/// change. The task exits once the connection of `client` was closed, in
+/// which case a new task is spawned for the reconnected client.
+fn watch_settings<F>(
+    handle: &mut Option<JoinHandle<()>>,
+    client: &DiscordClient,
+    key: Key,
+    render: F,
+) where
+    F: Fn(VoiceSettings, &Key) -> Result<()> + Send + 'static,
+{
+    if handle.as_ref().is_some_and(|task| !task.is_finished()) {
+        return;
+    }
+
+    let mut settings = client.settings.clone();
+    *handle = Some(task::spawn(async move {
+        while settings.changed().await.is_ok() {
+            let value = *settings.borrow();
+            let _ = render(value, &key);
+        }
+    }));
+}
+
+/// Mute/Unmute the microphone in Discord.
+#[derive(Debug)]
+pub struct DiscordMuteButton {
+    icons: [DynamicImage; 2],
+    /// The task rerendering the key once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl Default for DiscordMuteButton {
+    fn default() -> Self {
+        let icon_mute_off = load_icon!("../../icons/audio/audio_mute_off.png");
+        let icon_mute_on = load_icon!("../../icons/audio/audio_mute_on.png");
+
+        Self {
+            icons: [icon_mute_off, icon_mute_on],
+            task: None,
+        }
+    }
+}
+
+impl DiscordMuteButton {
+    fn watch(&mut self, client: &DiscordClient, key: &Key) {
+        let icons = self.icons.clone();
+        watch_settings(&mut self.task, client, key.clone(), move |settings, key| {
+            key.image(icons[settings.mute as usize].clone())
+        });
+    }
+}
+
+#[async_trait]
+impl Button for DiscordMuteButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = DiscordClient::get_or_insert(state)?;
+
+        self.watch(&client, &key);
+
+        let settings = *client.settings.borrow();
+        key.image(self.icons[settings.mute as usize].clone())
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = DiscordClient::get_or_insert(state)?;
+        self.watch(&client, &key);
+
+        let mute = client.settings.borrow().mute;
+        client.set_voice_settings(json!({ "mute": !mute })).await
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// Deafen/Undeafen in Discord.
+#[derive(Debug)]
+pub struct DiscordDeafenButton {
+    icons: [DynamicImage; 2],
+    /// The task rerendering the key once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl Default for DiscordDeafenButton {
+    fn default() -> Self {
+        let icon_deaf_off = load_icon!("../../icons/audio/audio_deaf_off.png");
+        let icon_deaf_on = load_icon!("../../icons/audio/audio_deaf_on.png");
+
+        Self {
+            icons: [icon_deaf_off, icon_deaf_on],
+            task: None,
+        }
+    }
+}
+
+impl DiscordDeafenButton {
+    fn watch(&mut self, client: &DiscordClient, key: &Key) {
+        let icons = self.icons.clone();
+        watch_settings(&mut self.task, client, key.clone(), move |settings, key| {
+            key.image(icons[settings.deaf as usize].clone())
+        });
+    }
+}
+
+#[async_trait]
+impl Button for DiscordDeafenButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = DiscordClient::get_or_insert(state)?;
+
+        self.watch(&client, &key);
+
+        let settings = *client.settings.borrow();
+        key.image(self.icons[settings.deaf as usize].clone())
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = DiscordClient::get_or_insert(state)?;
+        self.watch(&client, &key);
+
+        let deaf = client.settings.borrow().deaf;
+        client.set_voice_settings(json!({ "deaf": !deaf })).await
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
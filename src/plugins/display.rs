@@ -0,0 +1,157 @@
+//! Control the power and brightness of the monitors.
+
+use crate::core::{Button, Key, Result, State};
+use crate::plugins::run_tool;
+use async_trait::async_trait;
+use std::env;
+
+/// The tool used to switch the monitors on and off.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerBackend {
+    /// X11 using `xset dpms`.
+    Xset,
+    /// The sway Wayland compositor using `swaymsg`.
+    Sway,
+}
+
+impl PowerBackend {
+    /// Returns the backend for the running session: `Sway` if running under
+    /// sway, `Xset` otherwise.
+    pub fn detect() -> Self {
+        match env::var_os("SWAYSOCK") {
+            Some(_) => Self::Sway,
+            None => Self::Xset,
+        }
+    }
+
+    async fn set_power(&self, on: bool) -> Result<()> {
+        let state = match on {
+            true => "on",
+            false => "off",
+        };
+
+        match self {
+            Self::Xset => run_tool("xset", &["dpms", "force", state]).await,
+            Self::Sway => run_tool("swaymsg", &["output", "*", "dpms", state]).await,
+        }
+    }
+}
+
+/// Switches all monitors off and back on again on the next press.
+#[derive(Debug)]
+pub struct DisplayOffButton {
+    backend: PowerBackend,
+    off: bool,
+}
+
+impl DisplayOffButton {
+    /// Use `backend` instead of detecting it.
+    pub fn backend(mut self, backend: PowerBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    fn render(&self, key: &Key) -> Result<()> {
+        match self.off {
+            true => key.text("Display", (120, 120, 120), (0, 0, 0)),
+            false => key.text("Display", (255, 255, 255), (0, 0, 0)),
+        }
+    }
+}
+
+impl Default for DisplayOffButton {
+    fn default() -> Self {
+        Self {
+            backend: PowerBackend::detect(),
+            off: false,
+        }
+    }
+}
+
+#[async_trait]
+impl Button for DisplayOffButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.render(&key)
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.backend.set_power(self.off).await?;
+        self.off = !self.off;
+
+        self.render(&key)
+    }
+}
+
+/// The tool used to change the brightness of the monitors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrightnessBackend {
+    /// Laptop backlights using `brightnessctl`.
+    Brightnessctl,
+    /// External monitors over DDC/CI using `ddcutil`.
+    Ddcutil,
+}
+
+impl BrightnessBackend {
+    /// Change the brightness by `step` percent.
+    async fn change_brightness(&self, step: i8) -> Result<()> {
+        let (sign, step) = match step < 0 {
+            true => ("-", step.unsigned_abs()),
+            false => ("+", step as u8),
+        };
+
+        match self {
+            Self::Brightnessctl => {
+                run_tool("brightnessctl", &["set", &format!("{}%{}", step, sign)]).await
+            }
+            // VCP feature 0x10 is the brightness.
+            Self::Ddcutil => run_tool("ddcutil", &["setvcp", "10", sign, &step.to_string()]).await,
+        }
+    }
+}
+
+/// Changes the brightness of the monitors by a fixed step.
+#[derive(Debug)]
+pub struct DisplayBrightnessButton {
+    backend: BrightnessBackend,
+    step: i8,
+}
+
+impl DisplayBrightnessButton {
+    /// Creates a button increasing the brightness by `step` percent.
+    pub fn up(step: u8) -> Self {
+        Self {
+            backend: BrightnessBackend::Brightnessctl,
+            step: step.min(100) as i8,
+        }
+    }
+
+    /// Creates a button decreasing the brightness by `step` percent.
+    pub fn down(step: u8) -> Self {
+        Self {
+            backend: BrightnessBackend::Brightnessctl,
+            step: -(step.min(100) as i8),
+        }
+    }
+
+    /// Use `backend` instead of `brightnessctl`.
+    pub fn backend(mut self, backend: BrightnessBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+}
+
+#[async_trait]
+impl Button for DisplayBrightnessButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let text = match self.step < 0 {
+            true => "Bright -",
+            false => "Bright +",
+        };
+
+        key.text(text, (255, 255, 255), (0, 0, 0))
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        self.backend.change_brightness(self.step).await
+    }
+}
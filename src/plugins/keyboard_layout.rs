@@ -0,0 +1,119 @@
+//! Switch between keyboard layouts.
+
+use crate::core::{Button, Key, Result, State};
+use crate::plugins::run_tool;
+use async_trait::async_trait;
+use std::env;
+
+/// The tool used to switch the keyboard layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutBackend {
+    /// X11 using `setxkbmap`.
+    Setxkbmap,
+    /// The sway Wayland compositor using `swaymsg`.
+    Sway,
+}
+
+impl LayoutBackend {
+    /// Returns the backend for the running session: `Sway` if running under
+    /// sway, `Setxkbmap` otherwise.
+    pub fn detect() -> Self {
+        match env::var_os("SWAYSOCK") {
+            Some(_) => Self::Sway,
+            None => Self::Setxkbmap,
+        }
+    }
+
+    /// Returns the program and arguments switching all keyboards to
+    /// `layout`.
+    fn command(&self, layout: &str) -> (&'static str, Vec<String>) {
+        match self {
+            Self::Setxkbmap => ("setxkbmap", vec![layout.to_owned()]),
+            Self::Sway => (
+                "swaymsg",
+                ["input", "type:keyboard", "xkb_layout", layout]
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect(),
+            ),
+        }
+    }
+
+    async fn set_layout(&self, layout: &str) -> Result<()> {
+        let (program, args) = self.command(layout);
+        run_tool(program, &args).await
+    }
+}
+
+/// Cycles through a list of keyboard layouts, e.g. `us` and `de`. The key
+/// shows the code of the current layout. The first layout is assumed to be
+/// active at the start.
+#[derive(Debug)]
+pub struct LayoutCycleButton {
+    layouts: Vec<String>,
+    backend: LayoutBackend,
+    /// The index of the current layout.
+    current: usize,
+}
+
+impl LayoutCycleButton {
+    /// Creates a new `LayoutCycleButton` cycling through `layouts`.
+    pub fn new<I, T>(layouts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            layouts: layouts.into_iter().map(Into::into).collect(),
+            backend: LayoutBackend::detect(),
+            current: 0,
+        }
+    }
+
+    /// Use `backend` instead of detecting it.
+    pub fn backend(mut self, backend: LayoutBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    fn render(&self, key: &Key) -> Result<()> {
+        let layout = self.layouts.get(self.current).map_or("", String::as_str);
+        key.text(&layout.to_uppercase(), (255, 255, 255), (0, 0, 0))
+    }
+}
+
+#[async_trait]
+impl Button for LayoutCycleButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.render(&key)
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        if self.layouts.is_empty() {
+            return Ok(());
+        }
+
+        let next = (self.current + 1) % self.layouts.len();
+        self.backend.set_layout(&self.layouts[next]).await?;
+        self.current = next;
+
+        self.render(&key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LayoutBackend;
+
+    #[test]
+    fn test_layout_command() {
+        assert_eq!(
+            LayoutBackend::Setxkbmap.command("de"),
+            ("setxkbmap", vec![String::from("de")])
+        );
+
+        let (program, args) = LayoutBackend::Sway.command("us");
+        assert_eq!(program, "swaymsg");
+        assert_eq!(args, ["input", "type:keyboard", "xkb_layout", "us"]);
+    }
+}
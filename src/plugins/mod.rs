@@ -1,4 +1,102 @@
+#[cfg(feature = "audio")]
 pub mod audio;
+pub mod command;
+pub mod confirm;
+pub mod counter;
+pub mod cycle;
+pub mod dimmer;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "display")]
+pub mod display;
+#[cfg(feature = "keyboard_layout")]
+pub mod keyboard_layout;
+#[cfg(any(feature = "spotify", feature = "vlc"))]
+pub mod mpris;
+#[cfg(feature = "obs")]
 pub mod obs;
+pub mod ratelimit;
+pub mod repeat;
+#[cfg(feature = "screenshot")]
 pub mod screenshot;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod sequence;
+#[cfg(feature = "spotify")]
+pub mod spotify;
+pub mod stopwatch;
+#[cfg(feature = "vlc")]
 pub mod vlc;
+#[cfg(feature = "audio")]
+pub mod vumeter;
+#[cfg(feature = "weather")]
+pub mod weather;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+use crate::core::{Error, Result};
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::process::Output;
+use tokio::process::Command;
+
+/// A tool required by a button is not installed.
+#[derive(Debug)]
+pub struct MissingTool(&'static str);
+
+impl Display for MissingTool {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} is not installed", self.0)
+    }
+}
+
+impl std::error::Error for MissingTool {}
+
+/// Converts the error of starting `program` into a [`MissingTool`] error if
+/// it is not installed.
+fn tool_error(program: &'static str, err: io::Error) -> Error {
+    match err.kind() {
+        ErrorKind::NotFound => MissingTool(program).into(),
+        _ => err.into(),
+    }
+}
+
+/// Run `program` with `args` and return its output, returning a
+/// [`MissingTool`] error if it is not installed.
+async fn tool_output<S>(program: &'static str, args: &[S]) -> Result<Output>
+where
+    S: AsRef<OsStr>,
+{
+    Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| tool_error(program, err))
+}
+
+/// Returns an error with the stderr of `program` if `output` is of a failed
+/// command.
+fn check_output(program: &str, output: &Output) -> Result<()> {
+    match output.status.success() {
+        true => Ok(()),
+        false => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(io::Error::new(
+                ErrorKind::Other,
+                format!("{} failed: {}", program, stderr.trim()),
+            )
+            .into())
+        }
+    }
+}
+
+/// Run `program` with `args` until it exits, returning a [`MissingTool`]
+/// error if it is not installed and an error if it fails.
+async fn run_tool<S>(program: &'static str, args: &[S]) -> Result<()>
+where
+    S: AsRef<OsStr>,
+{
+    let output = tool_output(program, args).await?;
+    check_output(program, &output)
+}
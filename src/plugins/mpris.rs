@@ -0,0 +1,381 @@
+//! Helpers for media players implementing the MPRIS D-Bus interface.
+
+use crate::core::Result;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+use std::result;
+use std::sync::{Arc, Mutex};
+
+/// The `dbus-monitor` rule matching property changes of media players.
+const PROPERTIES_CHANGED: &str = "type='signal',path='/org/mpris/MediaPlayer2',\
+    interface='org.freedesktop.DBus.Properties',member='PropertiesChanged'";
+
+#[derive(Debug)]
+pub enum MprisError {
+    /// The media player is not running. Contains the name of the player.
+    NotRunning(String),
+    /// `dbus-send` failed. Contains the error message.
+    CommandFailed(String),
+    /// The reply could not be parsed. Contains the reply.
+    InvalidReply(String),
+}
+
+impl Display for MprisError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::NotRunning(player) => write!(f, "{} is not running", player),
+            Self::CommandFailed(msg) => write!(f, "dbus-send failed: {}", msg),
+            Self::InvalidReply(reply) => write!(f, "invalid D-Bus reply: {:?}", reply),
+        }
+    }
+}
+
+impl std::error::Error for MprisError {}
+
+/// The playback status of a media player.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// Returns the `dbus-send` command calling `args` on the MPRIS object of
+/// `player`.
+fn dbus_send_command(player: &str, args: &[&str]) -> Command {
+    let mut command = Command::new("dbus-send");
+    command
+        .args([
+            "--print-reply",
+            "--session",
+            &format!("--dest=org.mpris.MediaPlayer2.{}", player),
+            "/org/mpris/MediaPlayer2",
+        ])
+        .args(args);
+    command
+}
+
+/// Returns the printed reply of a `dbus-send` call to `player`.
+fn dbus_send_reply(player: &str, output: io::Result<Output>) -> result::Result<String, MprisError> {
+    let output = output.map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => {
+            MprisError::CommandFailed(String::from("dbus-send is not installed"))
+        }
+        _ => MprisError::CommandFailed(err.to_string()),
+    })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(
+            match stderr.contains("org.freedesktop.DBus.Error.ServiceUnknown") {
+                true => MprisError::NotRunning(player.to_owned()),
+                false => MprisError::CommandFailed(stderr.trim().to_owned()),
+            },
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Call `dbus-send` with `args` on the MPRIS object of `player` and return
+/// the printed reply. Blocks until `dbus-send` exits.
+fn dbus_send(player: &str, args: &[&str]) -> result::Result<String, MprisError> {
+    dbus_send_reply(player, dbus_send_command(player, args).output())
+}
+
+/// Like [`dbus_send`], but without blocking the runtime.
+async fn dbus_send_async(player: &str, args: &[&str]) -> result::Result<String, MprisError> {
+    let output = tokio::process::Command::from(dbus_send_command(player, args))
+        .output()
+        .await;
+
+    dbus_send_reply(player, output)
+}
+
+/// Returns the property `name` of the `org.mpris.MediaPlayer2.Player`
+/// interface of `player` as printed by `dbus-send`.
+fn get_property(player: &str, name: &str) -> result::Result<String, MprisError> {
+    dbus_send(
+        player,
+        &[
+            "org.freedesktop.DBus.Properties.Get",
+            "string:org.mpris.MediaPlayer2.Player",
+            &format!("string:{}", name),
+        ],
+    )
+}
+
+/// The metadata of the current track.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub art_url: Option<String>,
+}
+
+/// Returns the metadata of the track currently playing in `player` (e.g.
+/// "vlc"). Returns empty metadata if the player is not running.
+pub fn metadata(player: &str) -> Metadata {
+    match get_property(player, "Metadata") {
+        Ok(output) => parse_metadata(&output),
+        Err(_) => Metadata::default(),
+    }
+}
+
+/// Returns the playback status of `player`.
+pub async fn playback_status(player: &str) -> Result<PlaybackStatus> {
+    let output = dbus_send_async(
+        player,
+        &[
+            "org.freedesktop.DBus.Properties.Get",
+            "string:org.mpris.MediaPlayer2.Player",
+            "string:PlaybackStatus",
+        ],
+    )
+    .await?;
+
+    Ok(parse_playback_status(&output).ok_or(MprisError::InvalidReply(output))?)
+}
+
+/// Parse the `PlaybackStatus` property as printed by
+/// `dbus-send --print-reply`.
+fn parse_playback_status(output: &str) -> Option<PlaybackStatus> {
+    let status = output.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("variant")?
+            .trim_start()
+            .strip_prefix("string ")
+    })?;
+
+    match status {
+        "\"Playing\"" => Some(PlaybackStatus::Playing),
+        "\"Paused\"" => Some(PlaybackStatus::Paused),
+        "\"Stopped\"" => Some(PlaybackStatus::Stopped),
+        _ => None,
+    }
+}
+
+/// Parse the `xesam:title`, `xesam:artist` and `mpris:artUrl` entries from
+/// the `Metadata` property as printed by `dbus-send --print-reply`. Only the
+/// first artist is used.
+pub fn parse_metadata(output: &str) -> Metadata {
+    let mut metadata = Metadata::default();
+
+    // The field the next string value belongs to.
+    let mut field = None;
+    for line in output.lines().map(str::trim) {
+        match line {
+            "string \"xesam:title\"" => field = Some(&mut metadata.title),
+            "string \"xesam:artist\"" => field = Some(&mut metadata.artist),
+            "string \"mpris:artUrl\"" => field = Some(&mut metadata.art_url),
+            _ => {
+                let value = line
+                    .find("string \"")
+                    .map(|start| &line[start + "string \"".len()..])
+                    .and_then(|value| value.strip_suffix('"'));
+
+                if let Some(value) = value {
+                    if let Some(field) = field.take() {
+                        *field = Some(value.to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    metadata
+}
+
+/// Call the method `message` (e.g. "org.mpris.MediaPlayer2.Player.Next") on
+/// `player`. Returns [`MprisError::NotRunning`] if `player` is not running.
+pub async fn send(player: &str, message: &str) -> Result<()> {
+    dbus_send_async(player, &[message]).await?;
+    Ok(())
+}
+
+/// A `dbus-monitor` process started by [`on_properties_changed`] or
+/// [`on_playback_status_changed`]. The process is killed and its thread
+/// exits when the `Monitor` is dropped.
+#[derive(Debug)]
+pub struct Monitor {
+    child: Arc<Mutex<Child>>,
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        let _ = self.child.lock().unwrap().kill();
+    }
+}
+
+/// Spawn `dbus-monitor` with `rule` and a thread calling `f` with every
+/// printed line, until the returned [`Monitor`] is dropped.
+fn monitor<F>(rule: &str, mut f: F) -> Result<Monitor>
+where
+    F: FnMut(&str) + Send + 'static,
+{
+    let mut child = Command::new("dbus-monitor")
+        .args(["--session", rule])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().unwrap();
+    let child = Arc::new(Mutex::new(child));
+
+    {
+        let child = child.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => f(&line),
+                    Err(_) => break,
+                }
+            }
+
+            let _ = child.lock().unwrap().wait();
+        });
+    }
+
+    Ok(Monitor { child })
+}
+
+/// Spawn a thread calling `f` every time the properties of any media player
+/// change, until the returned [`Monitor`] is dropped.
+pub fn on_properties_changed<F>(mut f: F) -> Result<Monitor>
+where
+    F: FnMut() + Send + 'static,
+{
+    monitor(PROPERTIES_CHANGED, move |line| {
+        if line.starts_with("signal") {
+            f();
+        }
+    })
+}
+
+/// Spawn a thread calling `f` with the new status every time the playback
+/// status of `player` changes, until the returned [`Monitor`] is dropped.
+pub fn on_playback_status_changed<F>(player: &str, mut f: F) -> Result<Monitor>
+where
+    F: FnMut(PlaybackStatus) + Send + 'static,
+{
+    let rule = format!(
+        "{},sender='org.mpris.MediaPlayer2.{}'",
+        PROPERTIES_CHANGED, player
+    );
+
+    let mut reader = StatusReader::default();
+    monitor(&rule, move |line| {
+        if let Some(status) = reader.line(line) {
+            f(status);
+        }
+    })
+}
+
+/// Reads the `PlaybackStatus` entries of `PropertiesChanged` signals as
+/// printed by `dbus-monitor`, line by line.
+#[derive(Debug, Default)]
+struct StatusReader {
+    /// Whether the previous line was the `PlaybackStatus` key.
+    status_next: bool,
+}
+
+impl StatusReader {
+    fn line(&mut self, line: &str) -> Option<PlaybackStatus> {
+        if std::mem::take(&mut self.status_next) {
+            return parse_playback_status(line);
+        }
+
+        self.status_next = line.trim() == "string \"PlaybackStatus\"";
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_metadata, parse_playback_status, Metadata, PlaybackStatus, StatusReader};
+
+    const METADATA: &str = r#"method return time=1650000000.000000 sender=:1.50 -> destination=:1.80 serial=120 reply_serial=2
+   variant       array [
+         dict entry(
+            string "mpris:trackid"
+            variant                object path "/org/videolan/vlc/playlist/3"
+         )
+         dict entry(
+            string "mpris:artUrl"
+            variant                string "https://i.scdn.co/image/ab67616d0000b273"
+         )
+         dict entry(
+            string "xesam:url"
+            variant                string "file:///home/user/Music/song.flac"
+         )
+         dict entry(
+            string "xesam:title"
+            variant                string "Song Title"
+         )
+         dict entry(
+            string "xesam:artist"
+            variant                array [
+                  string "First Artist"
+                  string "Second Artist"
+               ]
+         )
+      ]
+"#;
+
+    #[test]
+    fn test_parse_metadata() {
+        assert_eq!(
+            parse_metadata(METADATA),
+            Metadata {
+                title: Some("Song Title".into()),
+                artist: Some("First Artist".into()),
+                art_url: Some("https://i.scdn.co/image/ab67616d0000b273".into()),
+            }
+        );
+        assert_eq!(parse_metadata(""), Metadata::default());
+    }
+
+    #[test]
+    fn test_parse_playback_status() {
+        let output = "method return time=1650000000.000000 sender=:1.50 -> destination=:1.80 serial=121 reply_serial=2
+   variant       string \"Paused\"
+";
+        assert_eq!(parse_playback_status(output), Some(PlaybackStatus::Paused));
+        assert_eq!(
+            parse_playback_status("   variant       string \"Playing\"\n"),
+            Some(PlaybackStatus::Playing)
+        );
+        assert_eq!(
+            parse_playback_status("   variant       string \"Unknown\"\n"),
+            None
+        );
+        assert_eq!(parse_playback_status(""), None);
+    }
+
+    #[test]
+    fn test_status_reader() {
+        let signal = r#"signal time=1650000000.000000 sender=:1.50 -> destination=(null destination) serial=130 path=/org/mpris/MediaPlayer2; interface=org.freedesktop.DBus.Properties; member=PropertiesChanged
+   string "org.mpris.MediaPlayer2.Player"
+   array [
+      dict entry(
+         string "Metadata"
+         variant             array [
+            ]
+      )
+      dict entry(
+         string "PlaybackStatus"
+         variant             string "Playing"
+      )
+   ]
+   array [
+   ]
+"#;
+
+        let mut reader = StatusReader::default();
+        let statuses: Vec<_> = signal
+            .lines()
+            .filter_map(|line| reader.line(line))
+            .collect();
+        assert_eq!(statuses, [PlaybackStatus::Playing]);
+    }
+}
@@ -1,16 +1,24 @@
 use crate::{
-    core::{Button, Error, Key, Result, State},
-    load_icon,
+    core::{AnimationHandle, Button, Color, Error, Key, Result, SharedService, State},
+    debug, error, load_icon, warn,
 };
 use async_trait::async_trait;
 use image::DynamicImage;
-use obws::{responses::RecordingStatus, Client};
+use obws::{
+    requests::{SourceScreenshot, Transition},
+    responses::{RecordingStatus, SceneList, StreamingStatus, VirtualCamStatus},
+    Client,
+};
+use std::fmt::{self, Display, Formatter};
 use tokio::{
     sync::{mpsc, oneshot},
-    task,
+    task::{self, JoinHandle},
     time::{self, Duration},
 };
 
+/// The color of keys whose button can't reach OBS.
+const OFFLINE_COLOR: (u8, u8, u8) = (60, 0, 0);
+
 const OBS_CLIENT_HOST: &str = "127.0.0.1";
 const OBS_CLIENT_PORT: u16 = 4444;
 
@@ -18,28 +26,147 @@ const OBS_CLIENT_PORT: u16 = 4444;
 const OBS_CLIENT_RECONNECT: Option<Duration> = None;
 
 /// OBS WebSocket client shared between all buttons. Used to communicate
-/// with OBS using just a single connection.
+/// with OBS using just a single connection. Register it using
+/// [`State::register_service`] when using any OBS button.
 #[derive(Clone, Debug)]
-struct OBSClient {
+pub struct OBSClient {
     tx: mpsc::Sender<Message>,
 }
 
 enum Message {
+    StreamingStatus(oneshot::Sender<Result<StreamingStatus>>),
     RecordingStatus(oneshot::Sender<Result<RecordingStatus>>),
     RecordingStart(oneshot::Sender<Result<()>>),
     RecordingStop(oneshot::Sender<Result<()>>),
-    SaveReplayBuffer,
+    SaveReplayBuffer(oneshot::Sender<Result<()>>),
+    ReplayBufferStatus(oneshot::Sender<Result<bool>>),
+    ReplayBufferStart(oneshot::Sender<Result<()>>),
+    ReplayBufferStop(oneshot::Sender<Result<()>>),
+    GetSceneList(oneshot::Sender<Result<SceneList>>),
+    SetCurrentScene(String, oneshot::Sender<Result<()>>),
+    /// Responds with the names of all profiles and the current profile.
+    GetProfileList(oneshot::Sender<Result<(Vec<String>, String)>>),
+    SetCurrentProfile(String, oneshot::Sender<Result<()>>),
+    /// Responds with the names of all scene collections and the current
+    /// scene collection.
+    GetSceneCollectionList(oneshot::Sender<Result<(Vec<String>, String)>>),
+    SetCurrentSceneCollection(String, oneshot::Sender<Result<()>>),
+    VirtualCamStatus(oneshot::Sender<Result<VirtualCamStatus>>),
+    VirtualCamToggle(oneshot::Sender<Result<()>>),
+    GetMute(String, oneshot::Sender<Result<bool>>),
+    SetMute(String, bool, oneshot::Sender<Result<()>>),
+    StudioModeStatus(oneshot::Sender<Result<bool>>),
+    /// Take a PNG screenshot of a source scaled to the given width and
+    /// height. Responds with the image as a data URI.
+    SourceScreenshot(String, u32, u32, oneshot::Sender<Result<String>>),
+    /// Transition the preview to the program scene, optionally using the
+    /// named transition with a duration.
+    Transition(
+        Option<(String, Option<Duration>)>,
+        oneshot::Sender<Result<()>>,
+    ),
 }
 
-impl OBSClient {
-    async fn insert_new(state: &mut State) -> std::result::Result<(), obws::Error> {
-        // Skip adding a new `OBSClient` when one already exists
-        // in the typemap.
+/// The [`OBSClient`] service was not registered before starting
+/// the main loop.
+#[derive(Debug)]
+pub struct NotStarted;
+
+impl Display for NotStarted {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "OBSClient is not registered as a service")
+    }
+}
+
+impl std::error::Error for NotStarted {}
+
+/// The audio source passed to [`ObsMuteButton`] does not exist in OBS.
+#[derive(Debug)]
+pub struct UnknownSource(String);
+
+impl Display for UnknownSource {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "OBS has no source named {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSource {}
+
+/// The profile passed to [`ProfileSwitchButton`] does not exist in OBS.
+#[derive(Debug)]
+pub struct UnknownProfile(String);
+
+impl Display for UnknownProfile {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "OBS has no profile named {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownProfile {}
+
+/// The scene collection passed to [`SceneCollectionButton`] does not exist
+/// in OBS.
+#[derive(Debug)]
+pub struct UnknownSceneCollection(String);
+
+impl Display for UnknownSceneCollection {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "OBS has no scene collection named {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSceneCollection {}
+
+/// OBS responded to a screenshot request without a valid image.
+#[derive(Debug)]
+pub struct InvalidScreenshot(String);
+
+impl Display for InvalidScreenshot {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid screenshot of source {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidScreenshot {}
+
+/// Convert an error of a request for `source`, replacing the error
+/// obs-websocket returns for missing sources with [`UnknownSource`].
+fn source_error(source: &str, err: obws::Error) -> Error {
+    match err {
+        obws::Error::Api(msg) if msg.contains("source doesn't exist") => {
+            UnknownSource(source.to_owned()).into()
+        }
+        err => err.into(),
+    }
+}
+
+/// Sets `lost` if `err` means that the connection to OBS was lost.
+fn check_connection(err: obws::Error, lost: &mut bool) -> obws::Error {
+    if matches!(
+        err,
+        obws::Error::Send(_) | obws::Error::ReceiveMessage(_) | obws::Error::Disconnected
+    ) {
+        *lost = true;
+    }
+
+    err
+}
+
+#[async_trait]
+impl SharedService for OBSClient {
+    async fn start(state: &mut State) -> Result<()> {
+        // Skip adding a new `OBSClient` when a connected one already exists
+        // in the typemap. A client whose connection task exited is removed
+        // and replaced.
         let typemap = state.typemap.read().unwrap();
-        if typemap.contains_key::<Self>() {
-            return Ok(());
+        match typemap.get::<Self>() {
+            Some(client) if !client.tx.is_closed() => return Ok(()),
+            Some(_) => {
+                drop(typemap);
+                state.remove::<Self>();
+            }
+            None => drop(typemap),
         }
-        drop(typemap);
 
         let mut typemap = state.typemap.write().unwrap();
         let (tx, mut rx) = mpsc::channel(32);
@@ -51,7 +178,7 @@ impl OBSClient {
                 let client = match Client::connect(OBS_CLIENT_HOST, OBS_CLIENT_PORT).await {
                     Ok(client) => client,
                     Err(err) => {
-                        eprintln!("[OBS] Failed to connect: {:?}", err);
+                        error!("[OBS] Failed to connect: {:?}", err);
                         time::sleep(match OBS_CLIENT_RECONNECT {
                             Some(dur) => dur,
                             None => break,
@@ -61,34 +188,203 @@ impl OBSClient {
                     }
                 };
 
+                // Whether a request failed because OBS was closed.
+                let mut lost = false;
+
                 while let Some(msg) = rx.recv().await {
                     match msg {
+                        Message::StreamingStatus(tx) => {
+                            let res = client.streaming().get_streaming_status().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
                         Message::RecordingStatus(tx) => {
                             let res = client.recording().get_recording_status().await;
 
-                            let res = res.map_err(|err| err.into());
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
                             let _ = tx.send(res);
                         }
                         Message::RecordingStart(tx) => {
                             let res = client.recording().start_recording().await;
 
-                            let res = res.map_err(|err| err.into());
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
                             let _ = tx.send(res);
                         }
                         Message::RecordingStop(tx) => {
                             let res = client.recording().stop_recording().await;
 
-                            let res = res.map_err(|err| err.into());
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
                             let _ = tx.send(res);
                         }
 
-                        Message::SaveReplayBuffer => {
+                        Message::SaveReplayBuffer(tx) => {
                             let res = client.replay_buffer().save_replay_buffer().await;
-                            // let _ = tx.send(res);
-                            println!("{:?}", res);
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::ReplayBufferStatus(tx) => {
+                            let res = client.replay_buffer().get_replay_buffer_status().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::ReplayBufferStart(tx) => {
+                            let res = client.replay_buffer().start_replay_buffer().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::ReplayBufferStop(tx) => {
+                            let res = client.replay_buffer().stop_replay_buffer().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::GetSceneList(tx) => {
+                            let res = client.scenes().get_scene_list().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::SetCurrentScene(name, tx) => {
+                            let res = client.scenes().set_current_scene(&name).await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::GetProfileList(tx) => {
+                            let res = async {
+                                let profiles = client.profiles().list_profiles().await?;
+                                let current = client.profiles().get_current_profile().await?;
+
+                                let names = profiles.into_iter().map(|p| p.profile_name);
+                                Ok((names.collect(), current))
+                            };
+
+                            let res = res
+                                .await
+                                .map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::SetCurrentProfile(name, tx) => {
+                            let res = client.profiles().set_current_profile(&name).await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::GetSceneCollectionList(tx) => {
+                            let collections = client.scene_collections();
+                            let res = async {
+                                let list = collections.list_scene_collections().await?;
+                                let current = collections.get_current_scene_collection().await?;
+
+                                let names = list.into_iter().map(|sc| sc.sc_name);
+                                Ok((names.collect(), current))
+                            };
+
+                            let res = res
+                                .await
+                                .map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::SetCurrentSceneCollection(name, tx) => {
+                            let res = client
+                                .scene_collections()
+                                .set_current_scene_collection(&name)
+                                .await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::VirtualCamStatus(tx) => {
+                            let res = client.virtual_cam().get_virtual_cam_status().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::VirtualCamToggle(tx) => {
+                            let res = client.virtual_cam().start_stop_virtual_cam().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::GetMute(source, tx) => {
+                            let res = client.sources().get_mute(&source).await;
+
+                            let res = res.map(|mute| mute.muted).map_err(|err| {
+                                source_error(&source, check_connection(err, &mut lost))
+                            });
+                            let _ = tx.send(res);
+                        }
+                        Message::SetMute(source, mute, tx) => {
+                            let res = client.sources().set_mute(&source, mute).await;
+
+                            let res = res.map_err(|err| {
+                                source_error(&source, check_connection(err, &mut lost))
+                            });
+                            let _ = tx.send(res);
+                        }
+                        Message::SourceScreenshot(source, width, height, tx) => {
+                            let res = client
+                                .sources()
+                                .take_source_screenshot(SourceScreenshot {
+                                    source_name: Some(&source),
+                                    embed_picture_format: Some("png"),
+                                    save_to_file_path: None,
+                                    file_format: None,
+                                    compress_quality: None,
+                                    width: Some(width),
+                                    height: Some(height),
+                                })
+                                .await;
+
+                            let res = match res {
+                                Ok(screenshot) => screenshot
+                                    .img
+                                    .ok_or_else(|| InvalidScreenshot(source).into()),
+                                Err(err) => {
+                                    Err(source_error(&source, check_connection(err, &mut lost)))
+                                }
+                            };
+                            let _ = tx.send(res);
+                        }
+                        Message::StudioModeStatus(tx) => {
+                            let res = client.studio_mode().get_studio_mode_status().await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
+                        }
+                        Message::Transition(transition, tx) => {
+                            let transition =
+                                transition.as_ref().map(|(name, duration)| Transition {
+                                    name,
+                                    duration: duration.and_then(|duration| {
+                                        chrono::Duration::from_std(duration).ok()
+                                    }),
+                                });
+                            let res = client.studio_mode().transition_to_program(transition).await;
+
+                            let res = res.map_err(|err| check_connection(err, &mut lost).into());
+                            let _ = tx.send(res);
                         }
                     }
+
+                    if lost {
+                        break;
+                    }
+                }
+
+                // Connect again, e.g. after OBS was restarted. If that fails,
+                // the task exits and `bind_client` replaces the client.
+                if lost {
+                    warn!("[OBS] Lost the connection, reconnecting");
+                    continue;
                 }
+
+                // All `OBSClient`s were dropped by `stop`.
+                break;
             }
         });
 
@@ -96,14 +392,66 @@ impl OBSClient {
         Ok(())
     }
 
+    async fn stop(state: &mut State) -> Result<()> {
+        // Dropping the last sender stops the connection task.
+        state.remove::<Self>();
+        Ok(())
+    }
+}
+
+impl OBSClient {
     async fn send(&self, msg: Message) -> Result<()> {
         let _ = self.tx.send(msg).await;
         Ok(())
     }
 
+    /// Returns the current streaming and recording status of
+    /// the OBS client.
+    pub(crate) async fn streaming_status(&self) -> Result<StreamingStatus> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::StreamingStatus(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Takes a screenshot of `source` scaled to `width`x`height`.
+    /// Scenes are sources as well.
+    pub(crate) async fn source_screenshot(
+        &self,
+        source: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<DynamicImage> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .send(Message::SourceScreenshot(
+                source.to_owned(),
+                width,
+                height,
+                tx,
+            ))
+            .await;
+
+        let uri = match rx.await {
+            Ok(res) => res?,
+            Err(_) => return Err(Error::NoResponse),
+        };
+
+        // The image is a data URI: "data:image/png;base64,<data>".
+        let data = match uri.split_once(',') {
+            Some((_, data)) => base64::decode(data)?,
+            None => return Err(InvalidScreenshot(source.to_owned()).into()),
+        };
+
+        Ok(image::load_from_memory(&data)?)
+    }
+
     /// Returns the current recording status of the OBS
     /// client.
-    async fn recording_status(&self) -> Result<RecordingStatus> {
+    pub(crate) async fn recording_status(&self) -> Result<RecordingStatus> {
         let (tx, rx) = oneshot::channel();
         let _ = self.send(Message::RecordingStatus(tx)).await;
 
@@ -115,7 +463,7 @@ impl OBSClient {
 
     /// Starts recording on the OBS client. Returns an error
     /// when the client is already recording.
-    async fn recording_start(&self) -> Result<()> {
+    pub(crate) async fn recording_start(&self) -> Result<()> {
         let (tx, rx) = oneshot::channel();
         let _ = self.send(Message::RecordingStart(tx)).await;
 
@@ -127,7 +475,7 @@ impl OBSClient {
 
     /// Stops recording on the OBS client. Returns an error
     /// when the client is not recording.
-    async fn recording_stop(&self) -> Result<()> {
+    pub(crate) async fn recording_stop(&self) -> Result<()> {
         let (tx, rx) = oneshot::channel();
         let _ = self.send(Message::RecordingStop(tx)).await;
 
@@ -136,70 +484,1042 @@ impl OBSClient {
             Err(_) => Err(Error::NoResponse),
         }
     }
-}
 
-/// A button to toggle the current recording status
-/// in OBS.
-#[derive(Debug)]
-pub struct RecordingButton {}
+    /// Saves the current replay buffer. Returns an error when
+    /// the replay buffer is not active.
+    pub(crate) async fn save_replay_buffer(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::SaveReplayBuffer(tx)).await;
 
-#[async_trait]
-impl Button for RecordingButton {
-    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
-        OBSClient::insert_new(state).await.unwrap();
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
 
-        key.color((0, 0, 250))
+    /// Returns `true` if the replay buffer is active.
+    async fn replay_buffer_status(&self) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::ReplayBufferStatus(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
     }
 
-    async fn on_click(&mut self, state: &mut State, _key: Key) -> Result<()> {
-        let client = get_client_from_state(state);
+    /// Starts the replay buffer. Returns an error when the
+    /// replay buffer is already active.
+    async fn replay_buffer_start(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::ReplayBufferStart(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
 
-        let status = client.recording_status().await?;
+    /// Stops the replay buffer. Returns an error when the
+    /// replay buffer is not active.
+    async fn replay_buffer_stop(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::ReplayBufferStop(tx)).await;
 
-        match status.is_recording {
-            // Stop the recording.
-            true => client.recording_stop().await?,
-            // Start the recording.
-            false => client.recording_start().await?,
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
         }
+    }
 
-        Ok(())
+    /// Returns all scenes and the name of the current scene.
+    async fn scene_list(&self) -> Result<SceneList> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::GetSceneList(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
     }
-}
 
-/// Save and flush the current replay buffer it it exists.
-#[derive(Debug)]
-pub struct SaveReplayBufferButton {
-    icon: DynamicImage,
-}
+    /// Switches to the scene with the given name.
+    pub(crate) async fn set_current_scene(&self, name: String) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::SetCurrentScene(name, tx)).await;
 
-impl Default for SaveReplayBufferButton {
-    fn default() -> Self {
-        let icon = load_icon!("../../icons/obs/obs.png");
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
 
-        Self { icon }
+    /// Returns the names of all profiles and the name of the current
+    /// profile.
+    async fn profile_list(&self) -> Result<(Vec<String>, String)> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::GetProfileList(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
     }
-}
 
-#[async_trait]
-impl Button for SaveReplayBufferButton {
-    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
-        OBSClient::insert_new(state).await.unwrap();
+    /// Switches to the profile with the given name. Returns an
+    /// [`UnknownProfile`] error if the profile does not exist.
+    async fn set_current_profile(&self, name: String) -> Result<()> {
+        let (profiles, _) = self.profile_list().await?;
+        if !profiles.contains(&name) {
+            return Err(UnknownProfile(name).into());
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::SetCurrentProfile(name, tx)).await;
 
-        key.image(self.icon.clone())
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
     }
 
-    async fn on_click(&mut self, state: &mut State, _key: Key) -> Result<()> {
-        let client = get_client_from_state(state);
+    /// Returns the names of all scene collections and the name of the
+    /// current scene collection.
+    async fn scene_collection_list(&self) -> Result<(Vec<String>, String)> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::GetSceneCollectionList(tx)).await;
 
-        let _ = client.send(Message::SaveReplayBuffer).await;
-        Ok(())
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
     }
-}
 
-/// Returns a cloned [`OBSClient`] from the global [`State`].
-fn get_client_from_state(state: &State) -> OBSClient {
-    let typemap = state.typemap.read().unwrap();
-    let client = typemap.get::<OBSClient>().unwrap();
-    client.clone()
+    /// Switches to the scene collection with the given name. Returns an
+    /// [`UnknownSceneCollection`] error if the scene collection does not
+    /// exist.
+    async fn set_current_scene_collection(&self, name: String) -> Result<()> {
+        let (collections, _) = self.scene_collection_list().await?;
+        if !collections.contains(&name) {
+            return Err(UnknownSceneCollection(name).into());
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .send(Message::SetCurrentSceneCollection(name, tx))
+            .await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Switches to the scene `offset` positions away from the
+    /// current scene, wrapping around at the ends of the list.
+    async fn cycle_scene(&self, offset: isize) -> Result<()> {
+        let list = self.scene_list().await?;
+
+        let names: Vec<&str> = list
+            .scenes
+            .iter()
+            .map(|scene| scene.name.as_str())
+            .collect();
+        match adjacent_scene(&names, &list.current_scene, offset) {
+            Some(name) => self.set_current_scene(name.to_owned()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the current virtual camera status of the OBS
+    /// client.
+    async fn virtual_cam_status(&self) -> Result<VirtualCamStatus> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::VirtualCamStatus(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Starts or stops the virtual camera on the OBS client,
+    /// depending on its current state.
+    async fn virtual_cam_toggle(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::VirtualCamToggle(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Returns `true` if the audio source is muted in the OBS
+    /// mixer.
+    async fn get_mute(&self, source: String) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::GetMute(source, tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Mutes or unmutes the audio source in the OBS mixer.
+    async fn set_mute(&self, source: String, mute: bool) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::SetMute(source, mute, tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Returns `true` if studio mode is enabled.
+    async fn studio_mode_status(&self) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::StudioModeStatus(tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+
+    /// Transitions the preview scene to program using the named transition
+    /// or the active transition if `None`. Returns an error when studio mode
+    /// is not enabled.
+    async fn transition_to_program(
+        &self,
+        transition: Option<(String, Option<Duration>)>,
+    ) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.send(Message::Transition(transition, tx)).await;
+
+        match rx.await {
+            Ok(res) => res,
+            Err(_) => Err(Error::NoResponse),
+        }
+    }
+}
+
+/// A button to toggle the current recording status
+/// in OBS.
+#[derive(Debug)]
+pub struct RecordingButton {}
+
+#[async_trait]
+impl Button for RecordingButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.recording_status().await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(()) => key.color((0, 0, 250)),
+            Err(err) => {
+                warn!("[OBS] RecordingButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        let status = match client.recording_status().await {
+            Ok(status) => status,
+            Err(err) => {
+                key.color(OFFLINE_COLOR)?;
+                return Err(err);
+            }
+        };
+
+        match status.is_recording {
+            // Stop the recording.
+            true => client.recording_stop().await?,
+            // Start the recording.
+            false => client.recording_start().await?,
+        }
+
+        key.color((0, 0, 250))
+    }
+}
+
+/// The interval in which [`OnAirButton`] polls the OBS status.
+const ON_AIR_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An indicator that is red while OBS is recording or streaming. While on
+/// air, the outer ring of keys of the deck pulses in red, the images of the
+/// covered keys are restored once OBS stops.
+#[derive(Debug)]
+pub struct OnAirButton {
+    color: Color,
+    /// The task polling the OBS status once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl Default for OnAirButton {
+    fn default() -> Self {
+        Self {
+            color: Color::from((250, 0, 0)),
+            task: None,
+        }
+    }
+}
+
+impl OnAirButton {
+    /// Sets the color of the key and the border while on air.
+    pub fn color<T>(mut self, color: T) -> Self
+    where
+        T: Into<Color>,
+    {
+        self.color = color.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Button for OnAirButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        key.color((40, 40, 40))?;
+
+        if self.task.is_some() {
+            return Ok(());
+        }
+
+        let state = state.clone();
+        let color = self.color.clone();
+        self.task = Some(task::spawn(async move {
+            let mut pulse: Option<AnimationHandle> = None;
+            let mut interval = time::interval(ON_AIR_POLL_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                // Treat a missing or disconnected client as off air.
+                let on_air = match get_client_from_state(&state) {
+                    Ok(client) => match client.streaming_status().await {
+                        Ok(status) => status.streaming || status.recording,
+                        Err(_) => false,
+                    },
+                    Err(_) => false,
+                };
+
+                let res = match (on_air, pulse.take()) {
+                    (true, None) => {
+                        pulse = Some(key.deck().pulse_border(color.clone()));
+                        key.color(color.clone())
+                    }
+                    (true, Some(handle)) => {
+                        pulse = Some(handle);
+                        Ok(())
+                    }
+                    (false, Some(handle)) => {
+                        handle.stop().await;
+                        key.deck()
+                            .set_border(None)
+                            .and_then(|_| key.color((40, 40, 40)))
+                    }
+                    (false, None) => Ok(()),
+                };
+
+                // The deck is gone.
+                if res.is_err() {
+                    break;
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _state: &mut State, key: Key) -> Result<()> {
+        // Aborting the task drops the handle of the pulse, but the border
+        // stays in its last shade.
+        if let Some(task) = self.task.take() {
+            task.abort();
+            let _ = task.await;
+            key.deck().set_border(None)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows a live thumbnail of an OBS source or scene, e.g. the webcam. The
+/// thumbnail is refreshed in the configured interval while OBS is connected.
+#[derive(Debug)]
+pub struct SourceThumbnailButton {
+    source: String,
+    interval: Duration,
+    /// The task refreshing the thumbnail once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl SourceThumbnailButton {
+    /// Creates a new `SourceThumbnailButton` showing `source`, refreshed
+    /// every 2 seconds.
+    pub fn new<T>(source: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            source: source.to_string(),
+            interval: Duration::from_secs(2),
+            task: None,
+        }
+    }
+
+    /// Sets the interval in which the thumbnail is refreshed.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+#[async_trait]
+impl Button for SourceThumbnailButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        key.color((0, 0, 0))?;
+
+        if self.task.is_some() {
+            return Ok(());
+        }
+
+        let state = state.clone();
+        let source = self.source.clone();
+        let mut interval = time::interval(self.interval);
+        self.task = Some(task::spawn(async move {
+            let (width, height) = key.image_size();
+
+            loop {
+                interval.tick().await;
+
+                // Skip the update while OBS is not connected.
+                let client = match get_client_from_state(&state) {
+                    Ok(client) => client,
+                    Err(_) => continue,
+                };
+
+                let image = match client.source_screenshot(&source, width, height).await {
+                    Ok(image) => image,
+                    Err(err) => {
+                        debug!("[OBS] Failed to take screenshot of {}: {:?}", source, err);
+                        continue;
+                    }
+                };
+
+                // The deck is gone.
+                if key.image(image).is_err() {
+                    break;
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// A button to toggle the virtual camera in OBS. The button
+/// is red while the virtual camera is active.
+#[derive(Debug, Default)]
+pub struct VirtualCamButton {}
+
+impl VirtualCamButton {
+    fn render(&self, is_active: bool, key: Key) -> Result<()> {
+        match is_active {
+            true => key.color((250, 0, 0)),
+            false => key.color((40, 40, 40)),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for VirtualCamButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.virtual_cam_status().await,
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(status) => self.render(status.is_virtual_cam, key),
+            Err(err) => {
+                warn!("[OBS] VirtualCamButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        let status = match client.virtual_cam_status().await {
+            Ok(status) => status,
+            Err(err) => {
+                key.color(OFFLINE_COLOR)?;
+                return Err(err);
+            }
+        };
+
+        client.virtual_cam_toggle().await?;
+        self.render(!status.is_virtual_cam, key)
+    }
+}
+
+/// Save and flush the current replay buffer it it exists.
+#[derive(Debug)]
+pub struct SaveReplayBufferButton {
+    icon: DynamicImage,
+}
+
+impl Default for SaveReplayBufferButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/obs/obs.png");
+
+        Self { icon }
+    }
+}
+
+#[async_trait]
+impl Button for SaveReplayBufferButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.replay_buffer_status().await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(()) => key.image(self.icon.clone()),
+            Err(err) => {
+                warn!("[OBS] SaveReplayBufferButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        // Only a failing connection turns the key offline, saving fails as
+        // well if the replay buffer is not active.
+        if let Err(err) = client.replay_buffer_status().await {
+            key.color(OFFLINE_COLOR)?;
+            return Err(err);
+        }
+
+        key.image(self.icon.clone())?;
+        client.save_replay_buffer().await
+    }
+}
+
+/// A button to start and stop the replay buffer in OBS. The
+/// button is red while the replay buffer is active.
+#[derive(Debug, Default)]
+pub struct ReplayBufferButton {}
+
+impl ReplayBufferButton {
+    fn render(&self, is_active: bool, key: Key) -> Result<()> {
+        match is_active {
+            true => key.color((250, 0, 0)),
+            false => key.color((40, 40, 40)),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for ReplayBufferButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.replay_buffer_status().await,
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(is_active) => self.render(is_active, key),
+            Err(err) => {
+                warn!("[OBS] ReplayBufferButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        let is_active = match client.replay_buffer_status().await {
+            Ok(is_active) => is_active,
+            Err(err) => {
+                key.color(OFFLINE_COLOR)?;
+                return Err(err);
+            }
+        };
+
+        match is_active {
+            true => client.replay_buffer_stop().await?,
+            false => client.replay_buffer_start().await?,
+        }
+
+        self.render(!is_active, key)
+    }
+}
+
+/// A button to mute an audio source (e.g. "Mic/Aux" or "Desktop
+/// Audio") in the OBS mixer. Unlike the pactl based
+/// `MuteButton` this only affects what OBS records and streams.
+/// The button is red while the source is muted.
+#[derive(Debug)]
+pub struct ObsMuteButton {
+    source: String,
+}
+
+impl ObsMuteButton {
+    pub fn new<T>(source: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            source: source.to_string(),
+        }
+    }
+
+    fn render(&self, is_muted: bool, key: Key) -> Result<()> {
+        match is_muted {
+            true => key.color((250, 0, 0)),
+            false => key.color((40, 40, 40)),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for ObsMuteButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.get_mute(self.source.clone()).await,
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(is_muted) => self.render(is_muted, key),
+            Err(err) => {
+                warn!("[OBS] ObsMuteButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        let is_muted = match client.get_mute(self.source.clone()).await {
+            Ok(is_muted) => is_muted,
+            Err(err) => {
+                key.color(OFFLINE_COLOR)?;
+                return Err(err);
+            }
+        };
+
+        client.set_mute(self.source.clone(), !is_muted).await?;
+
+        self.render(!is_muted, key)
+    }
+}
+
+/// Transitions the preview scene to program in studio mode. The key is
+/// colored while studio mode is enabled.
+#[derive(Debug, Default)]
+pub struct TransitionButton {
+    transition: Option<(String, Option<Duration>)>,
+}
+
+impl TransitionButton {
+    /// Use the transition `name` instead of the active transition. Uses the
+    /// default duration of the transition if `duration` is `None`.
+    pub fn transition<T>(mut self, name: T, duration: Option<Duration>) -> Self
+    where
+        T: ToString,
+    {
+        self.transition = Some((name.to_string(), duration));
+        self
+    }
+
+    fn render(&self, studio_mode: bool, key: Key) -> Result<()> {
+        let bg = match studio_mode {
+            true => (0, 100, 200),
+            false => (40, 40, 40),
+        };
+
+        key.text("Transition", (255, 255, 255), bg)
+    }
+}
+
+#[async_trait]
+impl Button for TransitionButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let res = match bind_client(state).await {
+            Ok(client) => client.studio_mode_status().await,
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(studio_mode) => self.render(studio_mode, key),
+            Err(err) => {
+                warn!("[OBS] TransitionButton is offline: {:?}", err);
+                key.color(OFFLINE_COLOR)
+            }
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        let studio_mode = match client.studio_mode_status().await {
+            Ok(studio_mode) => studio_mode,
+            Err(err) => {
+                key.color(OFFLINE_COLOR)?;
+                return Err(err);
+            }
+        };
+        if studio_mode {
+            client
+                .transition_to_program(self.transition.clone())
+                .await?;
+        } else {
+            warn!("[OBS] Ignoring transition, studio mode is not enabled");
+        }
+
+        self.render(studio_mode, key)
+    }
+}
+
+/// The state shown by a [`ProfileSwitchButton`] or [`SceneCollectionButton`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SwitchState {
+    Offline,
+    Inactive,
+    Active,
+}
+
+impl SwitchState {
+    /// Returns the state of the switch to `name` from the result of listing
+    /// all names and the current name. Returns an error if `name` doesn't
+    /// exist.
+    fn from_list<E>(name: &str, list: Result<(Vec<String>, String)>, unknown: E) -> Result<Self>
+    where
+        E: FnOnce() -> Error,
+    {
+        match list {
+            Ok((names, _)) if !names.iter().any(|n| n == name) => Err(unknown()),
+            Ok((_, current)) if current == name => Ok(Self::Active),
+            Ok(_) => Ok(Self::Inactive),
+            Err(err) => {
+                debug!("[OBS] Switch to {} is offline: {:?}", name, err);
+                Ok(Self::Offline)
+            }
+        }
+    }
+}
+
+/// Render the name of a profile or scene collection, highlighted while it
+/// is the active one. Does nothing if `switch` is already `shown`.
+fn render_switch(
+    name: &str,
+    switch: SwitchState,
+    shown: &mut Option<SwitchState>,
+    key: Key,
+) -> Result<()> {
+    if *shown == Some(switch) {
+        return Ok(());
+    }
+    *shown = Some(switch);
+
+    match switch {
+        SwitchState::Offline => key.color(OFFLINE_COLOR),
+        SwitchState::Inactive => key.text(name, (255, 255, 255), (40, 40, 40)),
+        SwitchState::Active => key.text(name, (255, 255, 255), (0, 100, 200)),
+    }
+}
+
+/// Switches to an OBS profile, e.g. to stream different games with
+/// different settings. The key is highlighted while the profile is active.
+///
+/// Profile changes made elsewhere are polled in [`Button::on_tick`], so
+/// [`State::tick`] must be set to follow them.
+///
+/// [`State::tick`]: crate::core::State::tick
+#[derive(Debug)]
+pub struct ProfileSwitchButton {
+    profile: String,
+    /// The state currently shown on the key.
+    shown: Option<SwitchState>,
+}
+
+impl ProfileSwitchButton {
+    pub fn new<T>(profile: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            profile: profile.to_string(),
+            shown: None,
+        }
+    }
+
+    async fn switch_state(&self, client: Result<OBSClient>) -> Result<SwitchState> {
+        let list = match client {
+            Ok(client) => client.profile_list().await,
+            Err(err) => Err(err),
+        };
+
+        SwitchState::from_list(&self.profile, list, || {
+            UnknownProfile(self.profile.clone()).into()
+        })
+    }
+}
+
+#[async_trait]
+impl Button for ProfileSwitchButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let switch = self.switch_state(bind_client(state).await).await?;
+
+        self.shown = None;
+        render_switch(&self.profile, switch, &mut self.shown, key)
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        client.set_current_profile(self.profile.clone()).await?;
+        render_switch(&self.profile, SwitchState::Active, &mut self.shown, key)
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // Don't reconnect on every tick, the client is bound on clicks. A
+        // deleted entry is shown as inactive instead of failing every tick.
+        let switch = self
+            .switch_state(get_client_from_state(state))
+            .await
+            .unwrap_or(SwitchState::Inactive);
+        render_switch(&self.profile, switch, &mut self.shown, key)
+    }
+}
+
+/// Switches to an OBS scene collection. The key is highlighted while the
+/// scene collection is active.
+///
+/// Scene collection changes made elsewhere are polled in
+/// [`Button::on_tick`], so [`State::tick`] must be set to follow them.
+///
+/// [`State::tick`]: crate::core::State::tick
+#[derive(Debug)]
+pub struct SceneCollectionButton {
+    collection: String,
+    /// The state currently shown on the key.
+    shown: Option<SwitchState>,
+}
+
+impl SceneCollectionButton {
+    pub fn new<T>(collection: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            collection: collection.to_string(),
+            shown: None,
+        }
+    }
+
+    async fn switch_state(&self, client: Result<OBSClient>) -> Result<SwitchState> {
+        let list = match client {
+            Ok(client) => client.scene_collection_list().await,
+            Err(err) => Err(err),
+        };
+
+        SwitchState::from_list(&self.collection, list, || {
+            UnknownSceneCollection(self.collection.clone()).into()
+        })
+    }
+}
+
+#[async_trait]
+impl Button for SceneCollectionButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // OBS is usually not running yet at boot. The client is bound again
+        // on the next click.
+        let switch = self.switch_state(bind_client(state).await).await?;
+
+        self.shown = None;
+        render_switch(&self.collection, switch, &mut self.shown, key)
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        client
+            .set_current_scene_collection(self.collection.clone())
+            .await?;
+        render_switch(&self.collection, SwitchState::Active, &mut self.shown, key)
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // Don't reconnect on every tick, the client is bound on clicks. A
+        // deleted entry is shown as inactive instead of failing every tick.
+        let switch = self
+            .switch_state(get_client_from_state(state))
+            .await
+            .unwrap_or(SwitchState::Inactive);
+        render_switch(&self.collection, switch, &mut self.shown, key)
+    }
+}
+
+/// Returns the scene `offset` positions away from `current` in
+/// `scenes`, wrapping around at the ends. Returns `None` if
+/// `current` is not in `scenes`.
+fn adjacent_scene<'a>(scenes: &[&'a str], current: &str, offset: isize) -> Option<&'a str> {
+    let index = scenes.iter().position(|name| *name == current)?;
+    let len = scenes.len() as isize;
+
+    let index = (index as isize + offset).rem_euclid(len);
+    Some(scenes[index as usize])
+}
+
+/// Switch to the next scene in OBS.
+#[derive(Debug, Default)]
+pub struct NextSceneButton {}
+
+#[async_trait]
+impl Button for NextSceneButton {
+    async fn init(&mut self, _state: &mut State, key: Key) -> Result<()> {
+        key.text("Next Scene", (255, 255, 255), (0, 0, 0))
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        if let Err(err) = client.cycle_scene(1).await {
+            key.color(OFFLINE_COLOR)?;
+            return Err(err);
+        }
+
+        key.text("Next Scene", (255, 255, 255), (0, 0, 0))
+    }
+}
+
+/// Switch to the previous scene in OBS.
+#[derive(Debug, Default)]
+pub struct PreviousSceneButton {}
+
+#[async_trait]
+impl Button for PreviousSceneButton {
+    async fn init(&mut self, _state: &mut State, key: Key) -> Result<()> {
+        key.text("Previous Scene", (255, 255, 255), (0, 0, 0))
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let client = bind_client(state).await?;
+
+        if let Err(err) = client.cycle_scene(-1).await {
+            key.color(OFFLINE_COLOR)?;
+            return Err(err);
+        }
+
+        key.text("Previous Scene", (255, 255, 255), (0, 0, 0))
+    }
+}
+
+/// Returns a cloned [`OBSClient`] from the global [`State`].
+/// Returns an error if the client was not registered using
+/// [`State::register_service`].
+pub(crate) fn get_client_from_state(state: &State) -> Result<OBSClient> {
+    let typemap = state.typemap.read().unwrap();
+    match typemap.get::<OBSClient>() {
+        Some(client) => Ok(client.clone()),
+        None => Err(NotStarted.into()),
+    }
+}
+
+/// Returns the [`OBSClient`] from the global [`State`], starting a new
+/// connection if the connection of the client was lost, e.g. because OBS
+/// was not running yet. Returns an error if the client was not registered
+/// using [`State::register_service`].
+pub(crate) async fn bind_client(state: &mut State) -> Result<OBSClient> {
+    let client = get_client_from_state(state)?;
+    if !client.tx.is_closed() {
+        return Ok(client);
+    }
+
+    // Replaces the closed client.
+    OBSClient::start(state).await?;
+    get_client_from_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adjacent_scene, NotStarted, SwitchState, UnknownProfile};
+
+    #[test]
+    fn test_adjacent_scene() {
+        let scenes = ["Starting", "Gaming", "Ending"];
+
+        assert_eq!(adjacent_scene(&scenes, "Starting", 1), Some("Gaming"));
+        assert_eq!(adjacent_scene(&scenes, "Ending", 1), Some("Starting"));
+        assert_eq!(adjacent_scene(&scenes, "Starting", -1), Some("Ending"));
+        assert_eq!(adjacent_scene(&scenes, "Gaming", -1), Some("Starting"));
+        assert_eq!(adjacent_scene(&scenes, "Missing", 1), None);
+    }
+
+    #[test]
+    fn test_switch_state() {
+        let list = || {
+            Ok((
+                vec![String::from("A"), String::from("B")],
+                String::from("A"),
+            ))
+        };
+        let unknown = || UnknownProfile(String::from("C")).into();
+
+        let state = |name, list| SwitchState::from_list(name, list, unknown).ok();
+        assert_eq!(state("A", list()), Some(SwitchState::Active));
+        assert_eq!(state("B", list()), Some(SwitchState::Inactive));
+        assert_eq!(state("C", list()), None);
+        assert_eq!(
+            state("A", Err(NotStarted.into())),
+            Some(SwitchState::Offline)
+        );
+    }
 }
@@ -0,0 +1,63 @@
+use crate::core::{Button, Key, Result, State};
+use crate::debug;
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+/// Limits how often a button can be clicked. Clicks within `min_interval`
+/// of the last successful click of the wrapped button are ignored.
+pub struct RateLimited {
+    inner: Box<dyn Button>,
+    min_interval: Duration,
+    /// The time of the last successful click.
+    last_click: Option<Instant>,
+}
+
+impl RateLimited {
+    pub fn new(inner: Box<dyn Button>, min_interval: Duration) -> Self {
+        Self {
+            inner,
+            min_interval,
+            last_click: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Button for RateLimited {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.init(state, key).await
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        if let Some(last_click) = self.last_click {
+            if last_click.elapsed() < self.min_interval {
+                debug!("Ignoring click within {:?}", self.min_interval);
+                return Ok(());
+            }
+        }
+
+        self.inner.on_click(state, key).await?;
+        self.last_click = Some(Instant::now());
+        Ok(())
+    }
+
+    async fn on_long_press(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.on_long_press(state, key).await
+    }
+
+    async fn on_hold_release(&mut self, state: &mut State, key: Key, held: Duration) -> Result<()> {
+        self.inner.on_hold_release(state, key, held).await
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.on_tick(state, key).await
+    }
+
+    async fn deinit(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.deinit(state, key).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    core::{Button, Key, Result, SharedButton, State},
+    warn,
+};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::{task, time};
+
+/// The time a key has to be held before the action starts repeating.
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+
+/// The factor the interval shrinks by after every repetition.
+const RAMP: f32 = 0.85;
+
+/// The timing of an action repeated while a key is held. The action repeats
+/// after [`REPEAT_DELAY`], first every `interval` and then faster the longer
+/// the key is held, down to a quarter of `interval`.
+#[derive(Debug)]
+pub struct Repeat {
+    interval: Duration,
+    /// The task repeating the action of the last press.
+    task: Option<task::JoinHandle<()>>,
+}
+
+impl Repeat {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            task: None,
+        }
+    }
+
+    /// Returns the delays before every repetition of the action.
+    fn delays(&self) -> impl Iterator<Item = Duration> {
+        let min_interval = self.interval / 4;
+
+        let ramp = std::iter::successors(Some(self.interval), move |interval| {
+            Some(interval.mul_f32(RAMP).max(min_interval))
+        });
+        std::iter::once(REPEAT_DELAY).chain(ramp)
+    }
+
+    /// Wait until the next repetition is due. Returns `false` if `key` was
+    /// released in the meantime.
+    async fn wait(key: &Key, delay: Duration) -> bool {
+        time::sleep(delay).await;
+        key.is_pressed()
+    }
+
+    /// Spawn a task calling `action` repeatedly until `key` is released.
+    /// `action` should already have been called once for the initial press.
+    /// The repetition of a previous press is stopped. Repeating stops at the
+    /// first error of `action`.
+    pub fn start<F, Fut>(&mut self, key: Key, mut action: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        self.stop();
+
+        let delays = self.delays();
+        self.task = Some(task::spawn(async move {
+            for delay in delays {
+                if !Self::wait(&key, delay).await {
+                    return;
+                }
+
+                if let Err(err) = action().await {
+                    warn!("Failed to repeat action: {:?}", err);
+                    return;
+                }
+            }
+        }));
+    }
+
+    /// Stop repeating the action of the last press.
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Repeat {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Repeats the clicks of a button while the key is held, like holding a key
+/// on a keyboard. The clicks are repeated in the background, so other keys
+/// are handled while the key is held.
+pub struct Repeating {
+    inner: SharedButton,
+    /// The [`Button::name`] of the inner button, stored to not lock it.
+    name: String,
+    repeat: Repeat,
+    /// Whether the last press was repeated.
+    repeated: Arc<AtomicBool>,
+}
+
+impl Repeating {
+    pub fn new(inner: Box<dyn Button>, interval: Duration) -> Self {
+        Self {
+            name: inner.name().to_owned(),
+            inner: Arc::new(Mutex::new(inner)),
+            repeat: Repeat::new(interval),
+            repeated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for Repeating {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.lock().await.init(state, key).await
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.repeat.stop();
+        self.repeated.store(false, Ordering::SeqCst);
+        self.inner.lock().await.on_click(state, key.clone()).await?;
+
+        let inner = self.inner.clone();
+        let repeated = self.repeated.clone();
+        let state = state.clone();
+        let repeat_key = key.clone();
+        self.repeat.start(key, move || {
+            let inner = inner.clone();
+            let mut state = state.clone();
+            let key = repeat_key.clone();
+            repeated.store(true, Ordering::SeqCst);
+
+            async move { inner.lock().await.on_click(&mut state, key).await }
+        });
+
+        Ok(())
+    }
+
+    async fn on_long_press(&mut self, state: &mut State, key: Key) -> Result<()> {
+        // Holding the key to repeat is not a long press.
+        match self.repeated.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => self.inner.lock().await.on_long_press(state, key).await,
+        }
+    }
+
+    async fn on_hold_release(&mut self, state: &mut State, key: Key, held: Duration) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .on_hold_release(state, key, held)
+            .await
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.inner.lock().await.on_tick(state, key).await
+    }
+
+    async fn deinit(&mut self, state: &mut State, key: Key) -> Result<()> {
+        self.repeat.stop();
+        self.inner.lock().await.deinit(state, key).await
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Repeat, Repeating, REPEAT_DELAY};
+    use crate::core::mock::MockStreamDeck;
+    use crate::core::{Button, Key, NullButton, Result, State};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use streamdeck::Kind;
+    use tokio::time;
+
+    struct CountButton(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Button for CountButton {
+        async fn init(&mut self, _: &mut State, _: Key) -> Result<()> {
+            Ok(())
+        }
+
+        async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeating_in_background() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let clicks = Arc::new(AtomicUsize::new(0));
+        let mut button = Repeating::new(
+            Box::new(CountButton(clicks.clone())),
+            Duration::from_millis(50),
+        );
+
+        // The click returns while the key is still held.
+        deck.set_pressed(0, true);
+        button
+            .on_click(&mut State::new(), deck.key(0))
+            .await
+            .unwrap();
+        assert_eq!(clicks.load(Ordering::SeqCst), 1);
+
+        time::sleep(REPEAT_DELAY + Duration::from_millis(100)).await;
+        assert!(clicks.load(Ordering::SeqCst) > 1);
+
+        // Releasing the key stops repeating.
+        deck.set_pressed(0, false);
+        time::sleep(Duration::from_millis(100)).await;
+        let count = clicks.load(Ordering::SeqCst);
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(clicks.load(Ordering::SeqCst), count);
+    }
+
+    #[test]
+    fn test_repeating_name() {
+        let button = Repeating::new(Box::new(NullButton), Duration::from_millis(200));
+        assert_eq!(button.name(), "NullButton");
+    }
+
+    #[test]
+    fn test_repeat_delays() {
+        let delays: Vec<_> = Repeat::new(Duration::from_millis(200))
+            .delays()
+            .take(20)
+            .collect();
+
+        assert_eq!(delays[0], REPEAT_DELAY);
+        assert_eq!(delays[1], Duration::from_millis(200));
+        assert!(delays[2] < delays[1]);
+        assert_eq!(delays[19], Duration::from_millis(50));
+    }
+}
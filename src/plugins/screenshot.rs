@@ -1,10 +1,16 @@
 use crate::{
     core::{Button, Key, Result, State},
-    load_icon,
+    debug, error, load_icon,
+    plugins::{check_output, run_tool, tool_error, tool_output},
 };
 use async_trait::async_trait;
 use image::DynamicImage;
+use std::io;
+use std::path::PathBuf;
+use std::process::Stdio;
 use std::{env, process::Command};
+use tokio::io::AsyncWriteExt;
+use tokio::task;
 
 /// A button to screenshot the full desktop using the falmeshot cli.
 /// Saves the images in $HOME/Pictures.
@@ -31,7 +37,7 @@ impl Button for FullScreenshotButton {
         let home = env::var("HOME").unwrap();
         let path = format!("{}/Pictures", home);
         Command::new("flameshot")
-            .args(&["full", "-p", &path])
+            .args(["full", "-p", &path])
             .output()
             .unwrap();
         Ok(())
@@ -53,12 +59,249 @@ impl Default for FlameshotButton {
 
 #[async_trait]
 impl Button for FlameshotButton {
-    async fn init(&mut self, _state: &mut State, key: Key) -> Result<()> {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        run_tool("flameshot", &["gui"]).await
+    }
+}
+
+/// A button to screenshot the full desktop directly into the clipboard
+/// without saving a file. Uses `grim` and `wl-copy` on Wayland and the
+/// flameshot cli on X11.
+#[derive(Debug)]
+pub struct ScreenshotClipboardButton {
+    icon: DynamicImage,
+}
+
+impl Default for ScreenshotClipboardButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/screenshot/screenshot.png");
+
+        Self { icon }
+    }
+}
+
+#[async_trait]
+impl Button for ScreenshotClipboardButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
         key.image(self.icon.clone())
     }
 
-    async fn on_click(&mut self, _state: &mut State, _key: Key) -> Result<()> {
-        Command::new("flameshot").arg("gui").output().unwrap();
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        let is_wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+
+        if !is_wayland {
+            return run_tool("flameshot", &["full", "-c"]).await;
+        }
+
+        let out = tool_output("grim", &["-"]).await?;
+        check_output("grim", &out)?;
+        pipe("wl-copy", &[], &out.stdout).await
+    }
+}
+
+/// Write `image` to the stdin of `program`.
+async fn pipe(program: &'static str, args: &[&str], image: &[u8]) -> Result<()> {
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| tool_error(program, err))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(image).await?;
+    drop(stdin);
+
+    let status = child.wait().await?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} exited with {}", program, status),
+        )
+        .into()),
+    }
+}
+
+/// The tools used to select a region of the screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegionBackend {
+    /// Wayland using `slurp` to select the region and `grim` to capture it.
+    /// Copies to the clipboard using `wl-copy`.
+    Grim,
+    /// X11 using `maim`. Copies to the clipboard using `xclip`.
+    Maim,
+    /// The flameshot GUI.
+    Flameshot,
+}
+
+impl RegionBackend {
+    /// Returns the backend for the running session: `Grim` on Wayland,
+    /// `Maim` on X11 and `Flameshot` otherwise.
+    pub fn detect() -> Self {
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            Self::Grim
+        } else if env::var_os("DISPLAY").is_some() {
+            Self::Maim
+        } else {
+            Self::Flameshot
+        }
+    }
+
+    /// Let the user select a region and save it to `target`. Returns
+    /// `Ok(false)` if the selection was cancelled.
+    async fn capture(&self, target: &ScreenshotTarget) -> Result<bool> {
+        match self {
+            Self::Grim => {
+                let selection = tool_output::<&str>("slurp", &[]).await?;
+                if !selection.status.success() {
+                    return Ok(false);
+                }
+
+                let geometry = String::from_utf8_lossy(&selection.stdout);
+                let geometry = geometry.trim();
+
+                match target {
+                    ScreenshotTarget::Directory(dir) => {
+                        let path = screenshot_path(dir);
+                        let out = tool_output("grim", &["-g", geometry, &path]).await?;
+                        check_output("grim", &out)?;
+                    }
+                    ScreenshotTarget::Clipboard => {
+                        let out = tool_output("grim", &["-g", geometry, "-"]).await?;
+                        check_output("grim", &out)?;
+                        pipe("wl-copy", &["--type", "image/png"], &out.stdout).await?;
+                    }
+                }
+            }
+            // maim exits with an error if the selection is cancelled.
+            Self::Maim => match target {
+                ScreenshotTarget::Directory(dir) => {
+                    let out = tool_output("maim", &["-s", &screenshot_path(dir)]).await?;
+                    if !out.status.success() {
+                        return Ok(false);
+                    }
+                }
+                ScreenshotTarget::Clipboard => {
+                    let out = tool_output("maim", &["-s"]).await?;
+                    if !out.status.success() {
+                        return Ok(false);
+                    }
+
+                    let args = ["-selection", "clipboard", "-t", "image/png"];
+                    pipe("xclip", &args, &out.stdout).await?;
+                }
+            },
+            Self::Flameshot => {
+                let out = match target {
+                    ScreenshotTarget::Directory(dir) => {
+                        tool_output("flameshot", &["gui", "-p", &dir.to_string_lossy()]).await?
+                    }
+                    ScreenshotTarget::Clipboard => tool_output("flameshot", &["gui", "-c"]).await?,
+                };
+                check_output("flameshot", &out)?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Where [`ScreenshotRegionButton`] saves the screenshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScreenshotTarget {
+    /// Save the screenshots as PNG files in the directory.
+    Directory(PathBuf),
+    Clipboard,
+}
+
+/// Returns a path for a new screenshot in `dir` named after the current
+/// time.
+fn screenshot_path(dir: &std::path::Path) -> String {
+    let name = chrono::Local::now().format("screenshot_%Y-%m-%d_%H-%M-%S.png");
+    dir.join(name.to_string()).to_string_lossy().into_owned()
+}
+
+/// A button to screenshot an interactively selected region of the screen.
+/// Saves the images in $HOME/Pictures by default. Other keys can still be
+/// used while selecting.
+#[derive(Debug)]
+pub struct ScreenshotRegionButton {
+    icon: DynamicImage,
+    backend: RegionBackend,
+    target: ScreenshotTarget,
+}
+
+impl ScreenshotRegionButton {
+    /// Use `backend` instead of detecting it.
+    pub fn backend(mut self, backend: RegionBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets where the screenshots are saved.
+    pub fn target(mut self, target: ScreenshotTarget) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+impl Default for ScreenshotRegionButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/screenshot/screenshot.png");
+        let home = env::var_os("HOME").unwrap_or_default();
+
+        Self {
+            icon,
+            backend: RegionBackend::detect(),
+            target: ScreenshotTarget::Directory(PathBuf::from(home).join("Pictures")),
+        }
+    }
+}
+
+#[async_trait]
+impl Button for ScreenshotRegionButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        // Select the region in the background to not block other keys.
+        let backend = self.backend;
+        let target = self.target.clone();
+        task::spawn(async move {
+            match backend.capture(&target).await {
+                Ok(true) => debug!("[Screenshot] Saved region to {:?}", target),
+                Ok(false) => debug!("[Screenshot] Region selection cancelled"),
+                Err(err) => error!("[Screenshot] Failed to capture region: {:?}", err),
+            }
+        });
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FlameshotButton, FullScreenshotButton};
+    use crate::core::mock::MockStreamDeck;
+    use crate::core::{Button, State};
+    use streamdeck::Kind;
+
+    #[tokio::test]
+    async fn test_init_paints_icon() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        let mut button = FlameshotButton::default();
+        button.init(&mut state, deck.key(0)).await.unwrap();
+        assert!(deck.image(0).is_some());
+
+        let mut button = FullScreenshotButton::default();
+        button.init(&mut state, deck.key(1)).await.unwrap();
+        assert!(deck.image(1).is_some());
+    }
+}
@@ -0,0 +1,788 @@
+//! A small scripting language to add conditional logic to a button without
+//! recompiling.
+//!
+//! A script is a list of function calls and `if`/`else` statements.
+//! Statements may be terminated by `;`, `!` negates a condition and `//`
+//! starts a comment:
+//!
+//! ```text
+//! // Toggle the recording and show its state.
+//! if obs.is_recording() {
+//!     obs.stop_recording();
+//!     key.color(40, 40, 40);
+//! } else {
+//!     obs.start_recording();
+//!     key.color(250, 0, 0);
+//! }
+//! ```
+//!
+//! Functions:
+//! - `key.color(r, g, b)`, `key.text("text")`
+//! - `obs.is_recording()`, `obs.start_recording()`, `obs.stop_recording()`,
+//!   `obs.toggle_recording()`, `obs.save_replay_buffer()`,
+//!   `obs.set_scene("name")`
+//! - `audio.is_muted()`, `audio.toggle_mute()`, `audio.toggle_deafen()`
+//!
+//! `if` statements and expressions may be nested up to [`MAX_DEPTH`] levels.
+
+use crate::core::{Button, Key, Result, State};
+#[cfg(feature = "audio")]
+use crate::pactl::MuteAction;
+#[cfg(feature = "audio")]
+use crate::plugins::audio::{AudioClient, Device};
+#[cfg(feature = "obs")]
+use crate::plugins::obs;
+
+use async_trait::async_trait;
+use image::DynamicImage;
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::iter::Peekable;
+use std::pin::Pin;
+use std::str::Chars;
+
+/// The maximum nesting depth of statements and expressions. Parsing and
+/// running scripts recurses once per level.
+pub const MAX_DEPTH: usize = 64;
+
+/// A script failed to parse or run.
+#[derive(Debug)]
+pub struct ScriptError(String);
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "script error: {}", self.0)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    Dot,
+    Comma,
+    Semicolon,
+    Not,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+}
+
+fn tokenize(source: &str) -> std::result::Result<Vec<Token>, ScriptError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|c| *c == '\n');
+                continue;
+            }
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '!' => Token::Not,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '"' => Token::Str(read_string(&mut chars)?),
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut digits = c.to_string();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+
+                match digits.parse() {
+                    Ok(n) => Token::Int(n),
+                    Err(_) => return Err(ScriptError(format!("invalid number {}", digits))),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+
+                Token::Ident(ident)
+            }
+            c => return Err(ScriptError(format!("unexpected character {:?}", c))),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Read the rest of a string literal after the opening quote.
+fn read_string(chars: &mut Peekable<Chars>) -> std::result::Result<String, ScriptError> {
+    let mut string = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some(c) => string.push(c),
+                None => break,
+            },
+            Some(c) => string.push(c),
+            None => break,
+        }
+    }
+
+    Err(ScriptError(String::from("unterminated string")))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Not(Box<Expr>),
+    /// A call of `object.function(args)`.
+    Call {
+        object: String,
+        function: String,
+        args: Vec<Expr>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Stmt {
+    Expr(Expr),
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The current nesting depth.
+    depth: usize,
+}
+
+impl Parser {
+    fn parse(source: &str) -> std::result::Result<Vec<Stmt>, ScriptError> {
+        let mut parser = Self {
+            tokens: tokenize(source)?,
+            pos: 0,
+            depth: 0,
+        };
+
+        let mut stmts = Vec::new();
+        while parser.peek().is_some() {
+            stmts.push(parser.stmt()?);
+        }
+
+        Ok(stmts)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> std::result::Result<Token, ScriptError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| ScriptError(String::from("unexpected end of script")))?;
+
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> std::result::Result<(), ScriptError> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(ScriptError(format!(
+                "expected {:?}, found {:?}",
+                expected, token
+            ))),
+        }
+    }
+
+    /// Consume the next token if it is the identifier `name`.
+    fn keyword(&mut self, name: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident == name => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Run `f` one nesting level deeper. Returns an error if the script is
+    /// nested deeper than [`MAX_DEPTH`].
+    fn nested<T, F>(&mut self, f: F) -> std::result::Result<T, ScriptError>
+    where
+        F: FnOnce(&mut Self) -> std::result::Result<T, ScriptError>,
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(ScriptError(format!(
+                "nested deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn stmt(&mut self) -> std::result::Result<Stmt, ScriptError> {
+        if self.keyword("if") {
+            return self.nested(Self::if_stmt);
+        }
+
+        let expr = self.expr()?;
+        if self.peek() == Some(&Token::Semicolon) {
+            self.pos += 1;
+        }
+
+        Ok(Stmt::Expr(expr))
+    }
+
+    /// Parse an `if` statement after the `if` keyword.
+    fn if_stmt(&mut self) -> std::result::Result<Stmt, ScriptError> {
+        let cond = self.expr()?;
+        let then = self.block()?;
+
+        let otherwise = match self.keyword("else") {
+            true if self.keyword("if") => vec![self.nested(Self::if_stmt)?],
+            true => self.block()?,
+            false => Vec::new(),
+        };
+
+        Ok(Stmt::If {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+
+    fn block(&mut self) -> std::result::Result<Vec<Stmt>, ScriptError> {
+        self.expect(Token::LBrace)?;
+
+        let mut stmts = Vec::new();
+        while self.peek() != Some(&Token::RBrace) {
+            stmts.push(self.stmt()?);
+        }
+
+        self.expect(Token::RBrace)?;
+        Ok(stmts)
+    }
+
+    fn expr(&mut self) -> std::result::Result<Expr, ScriptError> {
+        match self.next()? {
+            Token::Not => Ok(Expr::Not(Box::new(self.nested(Self::expr)?))),
+            Token::Int(n) => Ok(Expr::Int(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Ident(ident) if ident == "true" => Ok(Expr::Bool(true)),
+            Token::Ident(ident) if ident == "false" => Ok(Expr::Bool(false)),
+            Token::Ident(object) => {
+                self.expect(Token::Dot)?;
+                let function = match self.next()? {
+                    Token::Ident(function) => function,
+                    token => {
+                        return Err(ScriptError(format!(
+                            "expected function name, found {:?}",
+                            token
+                        )))
+                    }
+                };
+
+                self.expect(Token::LParen)?;
+                let mut args = Vec::new();
+                while self.peek() != Some(&Token::RParen) {
+                    if !args.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    args.push(self.nested(Self::expr)?);
+                }
+                self.expect(Token::RParen)?;
+
+                Ok(Expr::Call {
+                    object,
+                    function,
+                    args,
+                })
+            }
+            token => Err(ScriptError(format!("unexpected {:?}", token))),
+        }
+    }
+}
+
+/// The result of an expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+fn exec<'a>(stmts: &'a [Stmt], state: &'a mut State, key: &'a Key) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Expr(expr) => {
+                    eval(expr, state, key).await?;
+                }
+                Stmt::If {
+                    cond,
+                    then,
+                    otherwise,
+                } => match eval(cond, state, key).await? {
+                    Value::Bool(true) => exec(then, state, key).await?,
+                    Value::Bool(false) => exec(otherwise, state, key).await?,
+                    value => {
+                        return Err(
+                            ScriptError(format!("condition {:?} is not a bool", value)).into()
+                        )
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn eval<'a>(expr: &'a Expr, state: &'a mut State, key: &'a Key) -> BoxFuture<'a, Result<Value>> {
+    Box::pin(async move {
+        match expr {
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Int(n) => Ok(Value::Int(*n)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Not(expr) => match eval(expr, state, key).await? {
+                Value::Bool(b) => Ok(Value::Bool(!b)),
+                value => Err(ScriptError(format!("cannot negate {:?}", value)).into()),
+            },
+            Expr::Call {
+                object,
+                function,
+                args,
+            } => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(eval(arg, state, key).await?);
+                }
+
+                call(object, function, &values, state, key).await
+            }
+        }
+    })
+}
+
+/// Check that `args` has `n` elements.
+fn arity(name: &str, args: &[Value], n: usize) -> std::result::Result<(), ScriptError> {
+    match args.len() == n {
+        true => Ok(()),
+        false => Err(ScriptError(format!(
+            "{} takes {} arguments, got {}",
+            name,
+            n,
+            args.len()
+        ))),
+    }
+}
+
+fn color_arg(args: &[Value], index: usize) -> std::result::Result<u8, ScriptError> {
+    match &args[index] {
+        Value::Int(n) => u8::try_from(*n)
+            .map_err(|_| ScriptError(format!("color component {} is out of range", n))),
+        value => Err(ScriptError(format!("expected a number, found {:?}", value))),
+    }
+}
+
+fn str_arg(args: &[Value], index: usize) -> std::result::Result<&str, ScriptError> {
+    match &args[index] {
+        Value::Str(s) => Ok(s),
+        value => Err(ScriptError(format!("expected a string, found {:?}", value))),
+    }
+}
+
+// `state` is only used by the plugin bindings.
+#[cfg_attr(not(any(feature = "obs", feature = "audio")), allow(unused_variables))]
+async fn call(
+    object: &str,
+    function: &str,
+    args: &[Value],
+    state: &mut State,
+    key: &Key,
+) -> Result<Value> {
+    let name = format!("{}.{}", object, function);
+
+    match (object, function) {
+        ("key", "color") => {
+            arity(&name, args, 3)?;
+            let color = (
+                color_arg(args, 0)?,
+                color_arg(args, 1)?,
+                color_arg(args, 2)?,
+            );
+            key.color(color)?;
+        }
+        ("key", "text") => {
+            arity(&name, args, 1)?;
+            key.text(str_arg(args, 0)?, (255, 255, 255), (0, 0, 0))?;
+        }
+        #[cfg(feature = "obs")]
+        ("obs", _) => return call_obs(&name, function, args, state).await,
+        #[cfg(feature = "audio")]
+        ("audio", _) => return call_audio(&name, function, args, state),
+        _ => return Err(ScriptError(format!("unknown function {}", name)).into()),
+    }
+
+    Ok(Value::Unit)
+}
+
+#[cfg(feature = "obs")]
+async fn call_obs(name: &str, function: &str, args: &[Value], state: &State) -> Result<Value> {
+    let client = obs::get_client_from_state(state)?;
+
+    match function {
+        "is_recording" => {
+            arity(name, args, 0)?;
+            let status = client.recording_status().await?;
+            return Ok(Value::Bool(status.is_recording));
+        }
+        "start_recording" => {
+            arity(name, args, 0)?;
+            client.recording_start().await?;
+        }
+        "stop_recording" => {
+            arity(name, args, 0)?;
+            client.recording_stop().await?;
+        }
+        "toggle_recording" => {
+            arity(name, args, 0)?;
+            match client.recording_status().await?.is_recording {
+                true => client.recording_stop().await?,
+                false => client.recording_start().await?,
+            }
+        }
+        "save_replay_buffer" => {
+            arity(name, args, 0)?;
+            client.save_replay_buffer().await?;
+        }
+        "set_scene" => {
+            arity(name, args, 1)?;
+            client
+                .set_current_scene(str_arg(args, 0)?.to_owned())
+                .await?;
+        }
+        _ => return Err(ScriptError(format!("unknown function {}", name)).into()),
+    }
+
+    Ok(Value::Unit)
+}
+
+#[cfg(feature = "audio")]
+fn call_audio(name: &str, function: &str, args: &[Value], state: &State) -> Result<Value> {
+    let backend = AudioClient::get_or_insert(state);
+
+    match function {
+        "is_muted" => {
+            arity(name, args, 0)?;
+            let default_source = backend.default_source()?;
+            let mute = backend
+                .list_sources()?
+                .iter()
+                .any(|source| source.name == default_source && source.mute);
+
+            return Ok(Value::Bool(mute));
+        }
+        "toggle_mute" => {
+            arity(name, args, 0)?;
+            backend.set_source_mute(Device::Default, MuteAction::Toggle)?;
+        }
+        "toggle_deafen" => {
+            arity(name, args, 0)?;
+            backend.set_sink_mute(Device::Default, MuteAction::Toggle)?;
+        }
+        _ => return Err(ScriptError(format!("unknown function {}", name)).into()),
+    }
+
+    Ok(Value::Unit)
+}
+
+/// Runs a script when pressed. See the [module documentation](self) for
+/// the language.
+#[derive(Debug)]
+pub struct ScriptButton {
+    script: Vec<Stmt>,
+    icon: Option<DynamicImage>,
+}
+
+impl ScriptButton {
+    /// Parses `source` into a new `ScriptButton`. Returns an error if the
+    /// script is malformed. Unknown functions are only reported when they
+    /// are called.
+    pub fn new(source: &str) -> std::result::Result<Self, ScriptError> {
+        Ok(Self {
+            script: Parser::parse(source)?,
+            icon: None,
+        })
+    }
+
+    /// Show `icon` on the key until the script changes it.
+    pub fn icon(mut self, icon: DynamicImage) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+}
+
+#[async_trait]
+impl Button for ScriptButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        match &self.icon {
+            Some(icon) => key.image(icon.clone()),
+            None => key.text("Script", (255, 255, 255), (0, 0, 0)),
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        exec(&self.script, state, &key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exec, Expr, Parser, Stmt, MAX_DEPTH};
+    use crate::core::mock::MockStreamDeck;
+    use crate::core::{Color, State};
+    use streamdeck::Kind;
+
+    fn call(object: &str, function: &str, args: Vec<Expr>) -> Expr {
+        Expr::Call {
+            object: object.to_owned(),
+            function: function.to_owned(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let script = r#"
+            // Toggle the recording.
+            if !obs.is_recording() {
+                obs.start_recording();
+                key.color(250, 0, 0)
+            } else if false {
+            } else {
+                key.text("a \"b\"");
+            }
+        "#;
+
+        assert_eq!(
+            Parser::parse(script).unwrap(),
+            vec![Stmt::If {
+                cond: Expr::Not(Box::new(call("obs", "is_recording", vec![]))),
+                then: vec![
+                    Stmt::Expr(call("obs", "start_recording", vec![])),
+                    Stmt::Expr(call(
+                        "key",
+                        "color",
+                        vec![Expr::Int(250), Expr::Int(0), Expr::Int(0)]
+                    )),
+                ],
+                otherwise: vec![Stmt::If {
+                    cond: Expr::Bool(false),
+                    then: vec![],
+                    otherwise: vec![Stmt::Expr(call(
+                        "key",
+                        "text",
+                        vec![Expr::Str(String::from("a \"b\""))]
+                    ))],
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(Parser::parse("key.color(1, 2").is_err());
+        assert!(Parser::parse("key.color(1 2)").is_err());
+        assert!(Parser::parse("if true { key.text(\"a\")").is_err());
+        assert!(Parser::parse("key.text(\"a)").is_err());
+        assert!(Parser::parse("color(1, 2, 3)").is_err());
+        assert!(Parser::parse("key.color(1, 2, 3) $").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let nested = |depth| {
+            let mut script = "if true { ".repeat(depth);
+            script.push_str(&"} ".repeat(depth));
+            script
+        };
+
+        assert!(Parser::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Parser::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Parser::parse(&format!("{}true", "!".repeat(100_000))).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exec() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        let script = Parser::parse(
+            r#"
+            if !false {
+                key.color(1, 2, 3);
+            } else {
+                key.text("unreachable");
+            }
+            "#,
+        )
+        .unwrap();
+        exec(&script, &mut state, &deck.key(0)).await.unwrap();
+        assert_eq!(deck.color(0), Some(Color::from((1, 2, 3))));
+
+        let script = Parser::parse("if false {} else if true { key.text(\"a\") }").unwrap();
+        exec(&script, &mut state, &deck.key(1)).await.unwrap();
+        assert!(deck.image(1).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_exec_errors() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        for source in [
+            "if 1 { }",
+            "!\"a\"",
+            "key.color(1, 2)",
+            "key.color(1, 2, 256)",
+            "key.text(1)",
+            "key.unknown()",
+            "unknown.call()",
+        ] {
+            let script = Parser::parse(source).unwrap();
+            assert!(
+                exec(&script, &mut state, &deck.key(0)).await.is_err(),
+                "{}",
+                source
+            );
+        }
+
+        // Statements after a failed one are not run.
+        let script = Parser::parse("key.unknown(); key.color(1, 2, 3)").unwrap();
+        assert!(exec(&script, &mut state, &deck.key(0)).await.is_err());
+        assert_eq!(deck.color(0), None);
+    }
+
+    #[cfg(feature = "obs")]
+    #[tokio::test]
+    async fn test_exec_obs_not_started() {
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+
+        let script = Parser::parse("obs.is_recording()").unwrap();
+        assert!(exec(&script, &mut state, &deck.key(0)).await.is_err());
+    }
+
+    #[cfg(feature = "audio")]
+    #[tokio::test]
+    async fn test_exec_audio() {
+        use crate::core::Result;
+        use crate::pactl::{MuteAction, Sink, Source};
+        use crate::plugins::audio::{AudioBackend, AudioClient, Device, EventStream};
+        use std::sync::{Arc, Mutex};
+
+        /// A backend with a single default source.
+        #[derive(Default)]
+        struct Backend {
+            mute: Mutex<bool>,
+        }
+
+        impl AudioBackend for Backend {
+            fn list_sinks(&self) -> Result<Vec<Sink>> {
+                Ok(Vec::new())
+            }
+
+            fn list_sources(&self) -> Result<Vec<Source>> {
+                Ok(vec![Source {
+                    name: String::from("mic"),
+                    mute: *self.mute.lock().unwrap(),
+                    ..Default::default()
+                }])
+            }
+
+            fn default_sink(&self) -> Result<String> {
+                Ok(String::from("speakers"))
+            }
+
+            fn default_source(&self) -> Result<String> {
+                Ok(String::from("mic"))
+            }
+
+            fn set_sink_mute(&self, _: Device, _: MuteAction) -> Result<()> {
+                Ok(())
+            }
+
+            fn set_source_mute(&self, _: Device, action: MuteAction) -> Result<()> {
+                let mut mute = self.mute.lock().unwrap();
+                *mute = match action {
+                    MuteAction::On => true,
+                    MuteAction::Off => false,
+                    MuteAction::Toggle => !*mute,
+                };
+                Ok(())
+            }
+
+            fn set_sink_volume(&self, _: Device, _: f32) -> Result<()> {
+                Ok(())
+            }
+
+            fn set_default_sink(&self, _: &str) -> Result<()> {
+                Ok(())
+            }
+
+            fn subscribe(&self) -> Result<Box<dyn EventStream>> {
+                unimplemented!()
+            }
+        }
+
+        let deck = MockStreamDeck::new(Kind::Original);
+        let mut state = State::new();
+        let backend = Arc::new(Backend::default());
+        state
+            .typemap
+            .write()
+            .unwrap()
+            .insert(AudioClient(backend.clone()));
+
+        let script = Parser::parse(
+            r#"
+            audio.toggle_mute();
+            if audio.is_muted() { key.color(255, 0, 0) }
+            "#,
+        )
+        .unwrap();
+        exec(&script, &mut state, &deck.key(0)).await.unwrap();
+        assert!(*backend.mute.lock().unwrap());
+        assert_eq!(deck.color(0), Some(Color::from((255, 0, 0))));
+
+        let script = Parser::parse("audio.toggle_mute(1)").unwrap();
+        assert!(exec(&script, &mut state, &deck.key(0)).await.is_err());
+        assert!(*backend.mute.lock().unwrap());
+    }
+}
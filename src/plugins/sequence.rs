@@ -0,0 +1,146 @@
+use crate::{
+    core::{Button, Error, Key, Result, State},
+    error,
+};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Runs a sequence of buttons on a single press. The buttons are clicked in
+/// order and share the same key and [`State`].
+pub struct MacroButton {
+    actions: Vec<Box<dyn Button>>,
+    continue_on_error: bool,
+}
+
+impl MacroButton {
+    pub fn new(actions: Vec<Box<dyn Button>>) -> Self {
+        Self {
+            actions,
+            continue_on_error: false,
+        }
+    }
+
+    /// Run the remaining actions if an action fails instead of stopping. The
+    /// error of the first failed action is returned after all actions ran.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Record the result of the action at `index`. Returns the error if the
+    /// remaining actions should be skipped.
+    fn handle_result(
+        &self,
+        index: usize,
+        res: Result<()>,
+        first_err: &mut Option<Error>,
+    ) -> Result<()> {
+        let err = match res {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        if !self.continue_on_error {
+            return Err(err);
+        }
+
+        error!("Macro action {} failed: {:?}", index, err);
+        if first_err.is_none() {
+            *first_err = Some(err);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Button for MacroButton {
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut first_err = None;
+
+        for index in 0..self.actions.len() {
+            let res = self.actions[index].init(state, key.clone()).await;
+            self.handle_result(index, res, &mut first_err)?;
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut first_err = None;
+
+        for index in 0..self.actions.len() {
+            let res = self.actions[index].on_click(state, key.clone()).await;
+            self.handle_result(index, res, &mut first_err)?;
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    async fn on_long_press(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut first_err = None;
+
+        for index in 0..self.actions.len() {
+            let res = self.actions[index].on_long_press(state, key.clone()).await;
+            self.handle_result(index, res, &mut first_err)?;
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    async fn on_hold_release(&mut self, state: &mut State, key: Key, held: Duration) -> Result<()> {
+        let mut first_err = None;
+
+        for index in 0..self.actions.len() {
+            let res = self.actions[index]
+                .on_hold_release(state, key.clone(), held)
+                .await;
+            self.handle_result(index, res, &mut first_err)?;
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    async fn on_tick(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut first_err = None;
+
+        for index in 0..self.actions.len() {
+            let res = self.actions[index].on_tick(state, key.clone()).await;
+            self.handle_result(index, res, &mut first_err)?;
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Deinitializes all actions, even if `continue_on_error` is not set.
+    async fn deinit(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let mut first_err = None;
+
+        for (index, action) in self.actions.iter_mut().enumerate() {
+            if let Err(err) = action.deinit(state, key.clone()).await {
+                error!("Macro action {} failed to deinitialize: {:?}", index, err);
+                first_err.get_or_insert(err);
+            }
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
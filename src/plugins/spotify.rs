@@ -0,0 +1,249 @@
+use crate::{
+    core::{Button, Key, Result, State},
+    debug, load_icon,
+    plugins::mpris::{self, Metadata},
+    plugins::repeat::Repeat,
+    warn,
+};
+use async_trait::async_trait;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+/// The MPRIS name of the Spotify client.
+const PLAYER: &str = "spotify";
+
+/// The number of album arts remembered by [`NowPlayingButton`].
+const ART_CACHE_SIZE: usize = 16;
+
+/// The album art by URL.
+type ArtCache = Mutex<HashMap<String, DynamicImage>>;
+
+#[derive(Debug)]
+pub struct PlayPauseButton {
+    icon: DynamicImage,
+}
+
+impl Default for PlayPauseButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/spotify/spotify_play.png");
+
+        Self { icon }
+    }
+}
+
+#[async_trait]
+impl Button for PlayPauseButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        mpris::send(PLAYER, "org.mpris.MediaPlayer2.Player.PlayPause").await?;
+
+        debug!("[Spotify] [PlayPause]");
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct NextButton {
+    icon: DynamicImage,
+    repeat: Option<Repeat>,
+}
+
+impl NextButton {
+    /// Repeat the action while the key is held, starting every `interval`
+    /// and speeding up the longer the key is held.
+    pub fn repeat_while_held(mut self, interval: Duration) -> Self {
+        self.repeat = Some(Repeat::new(interval));
+        self
+    }
+}
+
+impl Default for NextButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/spotify/spotify_next.png");
+
+        Self { icon, repeat: None }
+    }
+}
+
+#[async_trait]
+impl Button for NextButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let action = || mpris::send(PLAYER, "org.mpris.MediaPlayer2.Player.Next");
+        action().await?;
+
+        debug!("[Spotify] [Next]");
+
+        if let Some(repeat) = &mut self.repeat {
+            repeat.start(key, action);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct PreviousButton {
+    icon: DynamicImage,
+    repeat: Option<Repeat>,
+}
+
+impl PreviousButton {
+    /// Repeat the action while the key is held, starting every `interval`
+    /// and speeding up the longer the key is held.
+    pub fn repeat_while_held(mut self, interval: Duration) -> Self {
+        self.repeat = Some(Repeat::new(interval));
+        self
+    }
+}
+
+impl Default for PreviousButton {
+    fn default() -> Self {
+        let icon = load_icon!("../../icons/spotify/spotify_previous.png");
+
+        Self { icon, repeat: None }
+    }
+}
+
+#[async_trait]
+impl Button for PreviousButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        key.image(self.icon.clone())
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let action = || mpris::send(PLAYER, "org.mpris.MediaPlayer2.Player.Previous");
+        action().await?;
+
+        debug!("[Spotify] [Previous]");
+
+        if let Some(repeat) = &mut self.repeat {
+            repeat.start(key, action);
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows the album art of the track currently playing in Spotify. Falls back
+/// to the title and artist if the track has no album art.
+#[derive(Debug, Default)]
+pub struct NowPlayingButton {
+    /// The monitor of property changes rerendering the key.
+    monitor: Option<mpris::Monitor>,
+    /// The album art by URL, shared with the monitor.
+    art_cache: Arc<ArtCache>,
+}
+
+impl NowPlayingButton {
+    /// Render `metadata` onto `key`. Fetching the album art blocks, so this
+    /// must not be called from the async runtime.
+    fn render(metadata: &Metadata, key: &Key, art_cache: &ArtCache) -> Result<()> {
+        if let Some(url) = &metadata.art_url {
+            let cached = art_cache.lock().unwrap().get(url).cloned();
+            let art = match cached {
+                Some(art) => Some(art),
+                None => match fetch_art(url, key.image_size()) {
+                    Ok(art) => {
+                        let mut art_cache = art_cache.lock().unwrap();
+                        if art_cache.len() >= ART_CACHE_SIZE {
+                            art_cache.clear();
+                        }
+                        art_cache.insert(url.clone(), art.clone());
+                        Some(art)
+                    }
+                    Err(err) => {
+                        warn!("[Spotify] Failed to fetch album art {}: {:?}", url, err);
+                        None
+                    }
+                },
+            };
+
+            if let Some(art) = art {
+                return key.image(art);
+            }
+        }
+
+        let text = match (&metadata.artist, &metadata.title) {
+            (Some(artist), Some(title)) => format!("{}\n{}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => String::from("Nothing playing"),
+        };
+
+        key.text(&text, (255, 255, 255), (0, 0, 0))
+    }
+}
+
+#[async_trait]
+impl Button for NowPlayingButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let mut metadata = {
+            let key = key.clone();
+            let art_cache = self.art_cache.clone();
+            task::spawn_blocking(move || {
+                let metadata = mpris::metadata(PLAYER);
+                Self::render(&metadata, &key, &art_cache).map(|()| metadata)
+            })
+            .await??
+        };
+
+        if self.monitor.is_some() {
+            return Ok(());
+        }
+
+        // Property changes are also emitted for position updates. Only
+        // rerender the key when the metadata changes.
+        let art_cache = self.art_cache.clone();
+        self.monitor = Some(mpris::on_properties_changed(move || {
+            let new_metadata = mpris::metadata(PLAYER);
+            if new_metadata != metadata {
+                metadata = new_metadata;
+                let _ = Self::render(&metadata, &key, &art_cache);
+            }
+        })?);
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        // Dropping the monitor kills `dbus-monitor`.
+        self.monitor = None;
+        Ok(())
+    }
+}
+
+/// Download the image at `url` using `curl` and scale it to `size`. Gives
+/// up after 10 seconds.
+fn fetch_art(url: &str, size: (u32, u32)) -> Result<DynamicImage> {
+    let output = Command::new("curl")
+        .args(["--silent", "--fail", "--location", "--max-time", "10", url])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("curl exited with {}", output.status),
+        )
+        .into());
+    }
+
+    let image = image::load_from_memory(&output.stdout)?;
+    Ok(image.resize_to_fill(size.0, size.1, FilterType::Lanczos3))
+}
@@ -0,0 +1,70 @@
+use crate::core::{Button, Key, Result, State};
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+/// A stopwatch counting up from zero. A press starts or pauses the
+/// stopwatch, a long press resets it.
+#[derive(Debug, Default)]
+pub struct StopwatchButton {
+    /// The time at which the stopwatch was last started. `None` while the
+    /// stopwatch is paused.
+    started: Option<Instant>,
+    /// The time accumulated before the stopwatch was last started.
+    elapsed: Duration,
+}
+
+impl StopwatchButton {
+    fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(started) => self.elapsed + started.elapsed(),
+            None => self.elapsed,
+        }
+    }
+
+    fn render(&self, key: &Key) -> Result<()> {
+        let secs = self.elapsed().as_secs();
+
+        let color = match self.started {
+            Some(_) => (0, 200, 0),
+            None => (255, 255, 255),
+        };
+
+        key.text(
+            &format!("{:02}:{:02}", secs / 60, secs % 60),
+            color,
+            (0, 0, 0),
+        )
+    }
+}
+
+#[async_trait]
+impl Button for StopwatchButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.render(&key)
+    }
+
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        match self.started.take() {
+            // Pause the stopwatch.
+            Some(started) => self.elapsed += started.elapsed(),
+            // Start the stopwatch.
+            None => self.started = Some(Instant::now()),
+        }
+
+        self.render(&key)
+    }
+
+    async fn on_long_press(&mut self, _: &mut State, key: Key) -> Result<()> {
+        self.started = None;
+        self.elapsed = Duration::ZERO;
+
+        self.render(&key)
+    }
+
+    async fn on_tick(&mut self, _: &mut State, key: Key) -> Result<()> {
+        match self.started {
+            Some(_) => self.render(&key),
+            None => Ok(()),
+        }
+    }
+}
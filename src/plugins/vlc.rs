@@ -1,23 +1,43 @@
 use crate::{
     core::{Button, Key, Result, State},
-    load_icon,
+    debug, load_icon,
+    plugins::mpris::{self, Metadata, PlaybackStatus},
+    plugins::repeat::Repeat,
+    store::StateStore,
+    warn,
 };
 use async_trait::async_trait;
 use image::DynamicImage;
-use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct PlayPauseButton {
-    pause: bool,
+    pause: Arc<AtomicBool>,
     icons: [DynamicImage; 2],
+    /// The monitor of playback status changes rerendering the key.
+    monitor: Option<mpris::Monitor>,
 }
 
 impl PlayPauseButton {
-    fn render(&self, key: Key) -> Result<()> {
-        match self.pause {
-            false => key.image(self.icons[0].clone()),
-            true => key.image(self.icons[1].clone()),
+    /// The id of the play state in the [`StateStore`].
+    const STATE_ID: &'static str = "vlc.pause";
+
+    fn render(pause: bool, icons: &[DynamicImage; 2], key: &Key) -> Result<()> {
+        match pause {
+            false => key.image(icons[0].clone()),
+            true => key.image(icons[1].clone()),
+        }
+    }
+
+    /// Render and persist the new play state.
+    fn update(pause: bool, icons: &[DynamicImage; 2], key: &Key, store: &StateStore) -> Result<()> {
+        if let Err(err) = store.set(Self::STATE_ID, pause) {
+            warn!("Failed to persist VLC play state: {:?}", err);
         }
+
+        Self::render(pause, icons, key)
     }
 }
 
@@ -27,39 +47,95 @@ impl Default for PlayPauseButton {
         let icon_pause = load_icon!("../../icons/vlc/vlc_playpause_pause.png");
 
         Self {
-            pause: false,
+            pause: Arc::new(AtomicBool::new(false)),
             icons: [icon_play, icon_pause],
+            monitor: None,
         }
     }
 }
 
 #[async_trait]
 impl Button for PlayPauseButton {
-    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
-        self.render(key)
+    async fn init(&mut self, state: &mut State, key: Key) -> Result<()> {
+        let store = StateStore::get_or_insert(state);
+
+        // Fall back to the last known state if VLC is not running.
+        let pause = match mpris::playback_status("vlc").await {
+            Ok(status) => status == PlaybackStatus::Playing,
+            Err(_) => store.get(Self::STATE_ID).unwrap_or_default(),
+        };
+        self.pause.store(pause, Ordering::Relaxed);
+        Self::render(pause, &self.icons, &key)?;
+
+        if self.monitor.is_some() {
+            return Ok(());
+        }
+
+        // Follow the real status instead of toggling on click to not get out
+        // of sync with changes made in VLC itself.
+        let shared = self.pause.clone();
+        let icons = self.icons.clone();
+        let monitor = mpris::on_playback_status_changed("vlc", move |status| {
+            let pause = status == PlaybackStatus::Playing;
+            if shared.swap(pause, Ordering::Relaxed) != pause {
+                let _ = Self::update(pause, &icons, &key, &store);
+            }
+        });
+
+        self.monitor = match monitor {
+            Ok(monitor) => Some(monitor),
+            Err(err) => {
+                warn!("[VLC] Failed to monitor the playback status: {:?}", err);
+                None
+            }
+        };
+
+        Ok(())
     }
 
-    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
-        let output = vlc_dbus_send("org.mpris.MediaPlayer2.Player.PlayPause");
+    async fn on_click(&mut self, state: &mut State, key: Key) -> Result<()> {
+        vlc_dbus_send("org.mpris.MediaPlayer2.Player.PlayPause").await?;
 
-        #[cfg(debug_assertions)]
-        println!("[VLC] [PlayPause] {:?}", output);
+        debug!("[VLC] [PlayPause]");
 
-        self.pause = !self.pause;
-        self.render(key)
+        // The monitor rerenders the key once VLC changed the status. Without
+        // it the best guess is that the status toggled.
+        if self.monitor.is_none() {
+            let pause = !self.pause.fetch_xor(true, Ordering::Relaxed);
+            let store = StateStore::get_or_insert(state);
+            Self::update(pause, &self.icons, &key, &store)?;
+        }
+
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        // Dropping the monitor kills `dbus-monitor`.
+        self.monitor = None;
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 pub struct NextButton {
     icon: DynamicImage,
+    repeat: Option<Repeat>,
+}
+
+impl NextButton {
+    /// Repeat the action while the key is held, starting every `interval`
+    /// and speeding up the longer the key is held.
+    pub fn repeat_while_held(mut self, interval: Duration) -> Self {
+        self.repeat = Some(Repeat::new(interval));
+        self
+    }
 }
 
 impl Default for NextButton {
     fn default() -> Self {
         let icon = load_icon!("../../icons/vlc/vlc_next.png");
 
-        Self { icon }
+        Self { icon, repeat: None }
     }
 }
 
@@ -69,11 +145,15 @@ impl Button for NextButton {
         key.image(self.icon.clone())
     }
 
-    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
-        let output = vlc_dbus_send("org.mpris.MediaPlayer2.Player.Next");
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let action = || vlc_dbus_send("org.mpris.MediaPlayer2.Player.Next");
+        action().await?;
 
-        #[cfg(debug_assertions)]
-        println!("[VLC] [Next] {:?}", output);
+        debug!("[VLC] [Next]");
+
+        if let Some(repeat) = &mut self.repeat {
+            repeat.start(key, action);
+        }
 
         Ok(())
     }
@@ -82,13 +162,23 @@ impl Button for NextButton {
 #[derive(Debug)]
 pub struct PreviousButton {
     icon: DynamicImage,
+    repeat: Option<Repeat>,
+}
+
+impl PreviousButton {
+    /// Repeat the action while the key is held, starting every `interval`
+    /// and speeding up the longer the key is held.
+    pub fn repeat_while_held(mut self, interval: Duration) -> Self {
+        self.repeat = Some(Repeat::new(interval));
+        self
+    }
 }
 
 impl Default for PreviousButton {
     fn default() -> Self {
         let icon = load_icon!("../../icons/vlc/vlc_previous.png");
 
-        Self { icon }
+        Self { icon, repeat: None }
     }
 }
 
@@ -98,25 +188,73 @@ impl Button for PreviousButton {
         key.image(self.icon.clone())
     }
 
-    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
-        let output = vlc_dbus_send("org.mpris.MediaPlayer2.Player.Previous");
+    async fn on_click(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let action = || vlc_dbus_send("org.mpris.MediaPlayer2.Player.Previous");
+        action().await?;
+
+        debug!("[VLC] [Previous]");
+
+        if let Some(repeat) = &mut self.repeat {
+            repeat.start(key, action);
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows the title and artist of the track currently playing in VLC.
+#[derive(Debug, Default)]
+pub struct NowPlayingButton {
+    /// The monitor of property changes rerendering the key.
+    monitor: Option<mpris::Monitor>,
+}
+
+impl NowPlayingButton {
+    fn render(metadata: &Metadata, key: &Key) -> Result<()> {
+        let text = match (&metadata.artist, &metadata.title) {
+            (Some(artist), Some(title)) => format!("{}\n{}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => String::from("Nothing playing"),
+        };
+
+        key.text(&text, (255, 255, 255), (0, 0, 0))
+    }
+}
+
+#[async_trait]
+impl Button for NowPlayingButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        let mut metadata = mpris::metadata("vlc");
+        Self::render(&metadata, &key)?;
+
+        if self.monitor.is_some() {
+            return Ok(());
+        }
 
-        #[cfg(debug_assertions)]
-        println!("[VLC] [Previous] {:?}", output);
+        // Listen on changes of the player properties and rerender the key when
+        // the metadata changes.
+        self.monitor = Some(mpris::on_properties_changed(move || {
+            let new_metadata = mpris::metadata("vlc");
+            if new_metadata != metadata {
+                metadata = new_metadata;
+                let _ = Self::render(&metadata, &key);
+            }
+        })?);
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
 
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        // Dropping the monitor kills `dbus-monitor`.
+        self.monitor = None;
         Ok(())
     }
 }
 
-fn vlc_dbus_send(message: &str) -> std::process::Output {
-    Command::new("dbus-send")
-        .args(&[
-            "--print-reply",
-            "--session",
-            "--dest=org.mpris.MediaPlayer2.vlc",
-            "/org/mpris/MediaPlayer2",
-            message,
-        ])
-        .output()
-        .unwrap()
+async fn vlc_dbus_send(message: &str) -> Result<()> {
+    mpris::send("vlc", message).await
 }
@@ -0,0 +1,200 @@
+use crate::{
+    core::{Button, Key, Result, State},
+    warn,
+};
+use async_trait::async_trait;
+use std::process::Stdio;
+use tokio::{
+    io::AsyncReadExt,
+    process::Command,
+    task::{self, JoinHandle},
+};
+
+/// The sample rate requested from the sound server. The meter only needs the
+/// loudness, so a low rate keeps the CPU usage down.
+const SAMPLE_RATE: u32 = 8000;
+
+/// The number of times per second the meter is repainted.
+const FRAME_RATE: u32 = 10;
+
+/// The level shown as an empty meter in dBFS.
+const MIN_DB: f32 = -60.0;
+
+/// How much the peak marker falls per frame, relative to the meter height.
+const PEAK_DECAY: f32 = 0.02;
+
+/// A live level meter of an audio source. The bar shows the RMS level and a
+/// marker the recent peak. Reads the samples using `parec`, which works with
+/// both PulseAudio and PipeWire.
+#[derive(Debug)]
+pub struct VuMeterButton {
+    source: String,
+    /// The task reading the samples once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl Default for VuMeterButton {
+    fn default() -> Self {
+        Self {
+            source: String::from("@DEFAULT_MONITOR@"),
+            task: None,
+        }
+    }
+}
+
+impl VuMeterButton {
+    /// Sets the source to monitor. Defaults to the monitor of the default
+    /// sink, i.e. the desktop audio. Use `@DEFAULT_SOURCE@` for the
+    /// microphone.
+    pub fn source<T>(mut self, source: T) -> Self
+    where
+        T: ToString,
+    {
+        self.source = source.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl Button for VuMeterButton {
+    async fn init(&mut self, _state: &mut State, key: Key) -> Result<()> {
+        key.color((0, 0, 0))?;
+
+        if self.task.is_some() {
+            return Ok(());
+        }
+
+        let mut child = Command::new("parec")
+            .args([
+                &format!("--device={}", self.source),
+                "--format=s16le",
+                &format!("--rate={}", SAMPLE_RATE),
+                "--channels=1",
+                &format!("--latency-msec={}", 1000 / FRAME_RATE),
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdout = child.stdout.take().unwrap();
+
+        let (width, height) = key.image_size();
+        self.task = Some(task::spawn(async move {
+            // 16 bits per sample.
+            let mut buf = vec![0; (SAMPLE_RATE / FRAME_RATE) as usize * 2];
+            let mut peak = 0.0;
+
+            while stdout.read_exact(&mut buf).await.is_ok() {
+                let (rms, max) = levels(&buf);
+
+                peak = meter_level(max).max(peak - PEAK_DECAY);
+
+                let image = render_meter(width, height, meter_level(rms), peak);
+                if key.image_rgba(width, height, &image).is_err() {
+                    break;
+                }
+            }
+
+            warn!("[VuMeter] Stopped reading samples");
+            drop(child);
+        }));
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _state: &mut State, _key: Key) -> Result<()> {
+        // Dropping the task kills `parec`.
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the RMS and peak amplitude of the signed 16-bit little endian
+/// samples in `buf`, both in the range of `0.0` to `1.0`.
+fn levels(buf: &[u8]) -> (f32, f32) {
+    let (mut sum, mut peak) = (0.0, 0.0_f32);
+    let mut count = 0;
+
+    for sample in buf.chunks_exact(2) {
+        let sample = i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0;
+
+        sum += sample * sample;
+        peak = peak.max(sample.abs());
+        count += 1;
+    }
+
+    match count {
+        0 => (0.0, 0.0),
+        count => ((sum / count as f32).sqrt(), peak),
+    }
+}
+
+/// Map an amplitude to the filled fraction of the meter on a dB scale from
+/// [`MIN_DB`] to 0 dBFS.
+fn meter_level(amplitude: f32) -> f32 {
+    let db = 20.0 * amplitude.max(f32::EPSILON).log10();
+    (1.0 - db / MIN_DB).clamp(0.0, 1.0)
+}
+
+/// Render the meter as RGBA bytes: a vertical bar filled from the bottom to
+/// `level` and a white marker at `peak`. The bar turns yellow and red towards
+/// the top.
+fn render_meter(width: u32, height: u32, level: f32, peak: f32) -> Vec<u8> {
+    let mut image = vec![0; (width * height * 4) as usize];
+
+    let bar = (height as f32 * level) as u32;
+    let marker = ((height as f32 * peak) as u32).min(height - 1);
+    let margin = width / 4;
+
+    for y in 0..height {
+        // Measured from the bottom.
+        let row = height - 1 - y;
+
+        let color = if peak > 0.0 && row == marker {
+            [255, 255, 255, 255]
+        } else if row < bar {
+            match row as f32 / height as f32 {
+                fraction if fraction < 0.7 => [0, 200, 0, 255],
+                fraction if fraction < 0.9 => [230, 200, 0, 255],
+                _ => [230, 0, 0, 255],
+            }
+        } else {
+            [30, 30, 30, 255]
+        };
+
+        for x in margin..width - margin {
+            let index = ((y * width + x) * 4) as usize;
+            image[index..index + 4].copy_from_slice(&color);
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{levels, meter_level};
+
+    #[test]
+    fn test_levels() {
+        let buf: Vec<u8> = [i16::MAX, i16::MIN, 0, 0]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        let (rms, peak) = levels(&buf);
+        assert!((rms - 0.707).abs() < 0.01);
+        assert_eq!(peak, 1.0);
+
+        assert_eq!(meter_level(0.0), 0.0);
+        assert_eq!(meter_level(1.0), 1.0);
+        assert!((meter_level(0.001) - 0.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    core::{Button, Key, Result, State},
+    debug, warn,
+};
+use async_trait::async_trait;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::Value;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tokio::{
+    process::Command,
+    task::{self, JoinHandle},
+    time::{self, Duration},
+};
+
+/// The default interval in which the weather is fetched.
+const WEATHER_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Shows the current temperature and conditions for a location, fetched
+/// from wttr.in. The last known weather is kept if fetching fails.
+#[derive(Debug)]
+pub struct WeatherButton {
+    location: String,
+    interval: Duration,
+    /// The text of the last fetched weather, shared with the fetching task.
+    /// `None` until the task was started.
+    text: Option<Arc<Mutex<String>>>,
+    /// The task fetching the weather once it was started.
+    task: Option<JoinHandle<()>>,
+}
+
+impl WeatherButton {
+    pub fn new<T>(location: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            location: location.into(),
+            interval: WEATHER_INTERVAL,
+            text: None,
+            task: None,
+        }
+    }
+
+    /// Set the interval in which the weather is fetched.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+#[async_trait]
+impl Button for WeatherButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        if let Some(text) = &self.text {
+            let text = text.lock().unwrap().clone();
+            return key.text(&text, (255, 255, 255), (0, 0, 0));
+        }
+
+        key.text("--", (255, 255, 255), (0, 0, 0))?;
+
+        let text = Arc::new(Mutex::new(String::from("--")));
+        self.text = Some(text.clone());
+
+        let location = self.location.clone();
+        let mut interval = time::interval(self.interval);
+        self.task = Some(task::spawn(async move {
+            // The last successfully fetched weather.
+            let mut last = None;
+
+            loop {
+                interval.tick().await;
+
+                let weather = match fetch_weather(&location).await {
+                    Ok(weather) => weather,
+                    Err(err) => {
+                        warn!(
+                            "[Weather] Failed to fetch weather for {}: {:?}",
+                            location, err
+                        );
+                        continue;
+                    }
+                };
+
+                debug!("[Weather] {}: {:?}", location, weather);
+
+                if last.as_ref() != Some(&weather) {
+                    let new_text = format!("{}\n{}°C", weather.glyph(), weather.temperature);
+                    let _ = key.text(&new_text, (255, 255, 255), (0, 0, 0));
+                    *text.lock().unwrap() = new_text;
+                    last = Some(weather);
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deinit(&mut self, _: &mut State, _: Key) -> Result<()> {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.text = None;
+
+        Ok(())
+    }
+}
+
+/// The current weather at a location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Weather {
+    /// The temperature in degrees Celsius.
+    temperature: i32,
+    /// The WWO weather code used by wttr.in.
+    code: u32,
+}
+
+impl Weather {
+    /// Returns a symbol for the conditions.
+    fn glyph(&self) -> char {
+        match self.code {
+            113 => '☀',
+            116 | 119 | 122 => '☁',
+            143 | 248 | 260 => '≋',
+            200 | 386 | 389 | 392 | 395 => '⚡',
+            179 | 182 | 185 | 227 | 230 | 317 | 320 | 323..=338 | 350 | 362..=377 => '❄',
+            _ => '☂',
+        }
+    }
+}
+
+/// Fetch the current weather at `location` using `curl`.
+async fn fetch_weather(location: &str) -> Result<Weather> {
+    let url = location_url(location);
+
+    let output = Command::new("curl")
+        .args(["--silent", "--fail", "--location", &url])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("curl exited with {}", output.status),
+        )
+        .into());
+    }
+
+    let value = serde_json::from_slice(&output.stdout)?;
+    parse_weather(&value).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "invalid weather response").into()
+    })
+}
+
+/// Returns the wttr.in URL of the `j1` report for `location`. The location
+/// is percent-encoded as a path segment.
+fn location_url(location: &str) -> String {
+    format!(
+        "https://wttr.in/{}?format=j1",
+        utf8_percent_encode(location, NON_ALPHANUMERIC)
+    )
+}
+
+/// Parse the current conditions of a wttr.in `j1` response.
+fn parse_weather(value: &Value) -> Option<Weather> {
+    let current = value["current_condition"].get(0)?;
+
+    Some(Weather {
+        temperature: current["temp_C"].as_str()?.parse().ok()?,
+        code: current["weatherCode"].as_str()?.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{location_url, parse_weather, Weather};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_weather() {
+        let value = json!({
+            "current_condition": [{
+                "temp_C": "-3",
+                "temp_F": "27",
+                "weatherCode": "326",
+                "weatherDesc": [{ "value": "Light snow" }]
+            }],
+            "nearest_area": []
+        });
+
+        let weather = parse_weather(&value).unwrap();
+        assert_eq!(
+            weather,
+            Weather {
+                temperature: -3,
+                code: 326,
+            }
+        );
+        assert_eq!(weather.glyph(), '❄');
+
+        assert_eq!(parse_weather(&json!({})), None);
+        assert_eq!(parse_weather(&json!({ "current_condition": [] })), None);
+    }
+
+    #[test]
+    fn test_location_url() {
+        assert_eq!(
+            location_url("New York"),
+            "https://wttr.in/New%20York?format=j1"
+        );
+        assert_eq!(
+            location_url("a/b?c#d&e"),
+            "https://wttr.in/a%2Fb%3Fc%23d%26e?format=j1"
+        );
+        assert_eq!(location_url("Köln"), "https://wttr.in/K%C3%B6ln?format=j1");
+    }
+}
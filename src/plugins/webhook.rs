@@ -0,0 +1,150 @@
+use crate::{
+    core::{Button, Key, Result, State},
+    debug,
+};
+use async_trait::async_trait;
+use image::DynamicImage;
+use serde_json::Value;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use tokio::process::Command;
+
+/// The time after which a request is aborted.
+const TIMEOUT_SECS: u32 = 5;
+
+/// The exit code of curl if the request timed out.
+const CURL_TIMEOUT: i32 = 28;
+
+#[derive(Debug)]
+pub enum WebhookError {
+    /// The request did not complete within the timeout.
+    Timeout,
+    /// The server responded with a status code other than 2xx.
+    Status(u16),
+}
+
+impl Display for WebhookError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "request timed out after {}s", TIMEOUT_SECS),
+            Self::Status(status) => write!(f, "server responded with status {}", status),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+/// Sends an HTTP request with a JSON body on press, e.g. to trigger a home
+/// automation. Uses `curl`.
+#[derive(Debug)]
+pub struct WebhookButton {
+    url: String,
+    method: String,
+    body: Option<Value>,
+    label: String,
+    icon: Option<DynamicImage>,
+}
+
+impl WebhookButton {
+    /// Creates a new `WebhookButton` sending a `POST` request without a
+    /// body to `url`.
+    pub fn new<T>(url: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            url: url.to_string(),
+            method: String::from("POST"),
+            body: None,
+            label: String::from("Hook"),
+            icon: None,
+        }
+    }
+
+    /// Sets the HTTP method of the request.
+    pub fn method<T>(mut self, method: T) -> Self
+    where
+        T: ToString,
+    {
+        self.method = method.to_string();
+        self
+    }
+
+    /// Sends `body` as JSON with the request.
+    pub fn body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Sets the text shown on the key if no icon is set.
+    pub fn label<T>(mut self, label: T) -> Self
+    where
+        T: ToString,
+    {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Show `icon` on the key instead of the label.
+    pub fn icon(mut self, icon: DynamicImage) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Send the request and return the response status.
+    async fn send(&self) -> Result<u16> {
+        let mut cmd = Command::new("curl");
+        cmd.args(["--silent", "--show-error", "--location"])
+            .args(["--max-time", &TIMEOUT_SECS.to_string()])
+            .args(["--request", &self.method])
+            .args(["--output", "/dev/null", "--write-out", "%{http_code}"]);
+
+        if let Some(body) = &self.body {
+            cmd.args(["--header", "Content-Type: application/json"])
+                .args(["--data", &body.to_string()]);
+        }
+
+        let output = cmd.arg(&self.url).output().await?;
+
+        match output.status.code() {
+            Some(0) => (),
+            Some(CURL_TIMEOUT) => return Err(WebhookError::Timeout.into()),
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("curl failed: {}", stderr.trim()),
+                )
+                .into());
+            }
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid status code").into())
+    }
+}
+
+#[async_trait]
+impl Button for WebhookButton {
+    async fn init(&mut self, _: &mut State, key: Key) -> Result<()> {
+        match &self.icon {
+            Some(icon) => key.image(icon.clone()),
+            None => key.text(&self.label, (255, 255, 255), (0, 0, 0)),
+        }
+    }
+
+    async fn on_click(&mut self, _: &mut State, _: Key) -> Result<()> {
+        let status = self.send().await?;
+        debug!(
+            "[Webhook] {} {} responded with {}",
+            self.method, self.url, status
+        );
+
+        match status {
+            200..=299 => Ok(()),
+            status => Err(WebhookError::Status(status).into()),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Persistent state of buttons backed by fire-and-forget commands whose
+//! state can't be queried from the system.
+
+use crate::core::{Result, State};
+use crate::warn;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A JSON file storing values by a string id. Every change is written to the
+/// file immediately.
+#[derive(Debug)]
+pub struct StateStore {
+    path: PathBuf,
+    values: Mutex<Map<String, Value>>,
+}
+
+impl StateStore {
+    /// Open the store at `path`. The store starts out empty if the file
+    /// doesn't exist or is invalid.
+    pub fn open<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        let values = match fs::read(&path) {
+            Ok(buf) => match serde_json::from_slice(&buf) {
+                Ok(values) => values,
+                Err(err) => {
+                    warn!("Ignoring invalid state file {:?}: {}", path, err);
+                    Map::new()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => Map::new(),
+            Err(err) => {
+                warn!("Failed to read state file {:?}: {}", path, err);
+                Map::new()
+            }
+        };
+
+        Self {
+            path,
+            values: Mutex::new(values),
+        }
+    }
+
+    /// Returns the default location of the store,
+    /// `$XDG_STATE_HOME/streamdeck/state.json`. Falls back to
+    /// `$HOME/.local/state` if `XDG_STATE_HOME` is unset.
+    pub fn default_path() -> PathBuf {
+        let base = match env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let home = env::var_os("HOME").unwrap_or_default();
+                Path::new(&home).join(".local/state")
+            }
+        };
+
+        base.join("streamdeck").join("state.json")
+    }
+
+    /// Returns the `StateStore` from the typemap, opening the store at
+    /// [`StateStore::default_path`] if none exists.
+    pub fn get_or_insert(state: &State) -> Arc<Self> {
+        state.with_typemap(|typemap| {
+            if let Some(store) = typemap.get::<Arc<Self>>() {
+                return store.clone();
+            }
+
+            let store = Arc::new(Self::open(Self::default_path()));
+            typemap.insert(store.clone());
+            store
+        })
+    }
+
+    /// Returns the value stored under `id`. Returns `None` if there is no
+    /// value or it has a different type.
+    pub fn get<T>(&self, id: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        let values = self.values.lock().unwrap();
+        serde_json::from_value(values.get(id)?.clone()).ok()
+    }
+
+    /// Store `value` under `id` and write the store to disk.
+    pub fn set<T>(&self, id: &str, value: T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let mut values = self.values.lock().unwrap();
+        values.insert(id.to_owned(), serde_json::to_value(value)?);
+
+        self.write(&values)
+    }
+
+    /// Write `values` to a temporary file and rename it over the store file
+    /// to never leave a partially written file behind.
+    fn write(&self, values: &Map<String, Value>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(values)?)?;
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StateStore;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_state_store() {
+        let path = env::temp_dir().join(format!("streamdeck-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let store = StateStore::open(&path);
+        assert_eq!(store.get::<bool>("vlc.pause"), None);
+
+        store.set("vlc.pause", true).unwrap();
+        assert_eq!(store.get::<bool>("vlc.pause"), Some(true));
+        assert_eq!(store.get::<u32>("vlc.pause"), None);
+
+        let store = StateStore::open(&path);
+        assert_eq!(store.get::<bool>("vlc.pause"), Some(true));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+use crate::core::Color;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use imageproc::drawing::draw_text_mut;
+use rusttype::{point, Font, Scale};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::OnceLock;
+
+/// The height of a line of text in pixels.
+pub const FONT_SIZE: f32 = 14.0;
+
+/// The space between the text and the edge of the key in pixels.
+const PADDING: u32 = 4;
+
+/// The scale glyphs are measured at before scaling them to fit the key.
+const GLYPH_REFERENCE_SIZE: f32 = 100.0;
+
+/// The bundled font has no glyph for the character.
+#[derive(Debug)]
+pub struct MissingGlyph(pub char);
+
+impl Display for MissingGlyph {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "no glyph for {:?} (U+{:04X})", self.0, self.0 as u32)
+    }
+}
+
+impl error::Error for MissingGlyph {}
+
+/// Returns the font used to render text.
+fn font() -> &'static Font<'static> {
+    static FONT: OnceLock<Font<'static>> = OnceLock::new();
+
+    FONT.get_or_init(|| {
+        const BYTES: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+        Font::from_bytes(BYTES).unwrap()
+    })
+}
+
+/// Returns the width of `text` in pixels.
+fn text_width(font: &Font, scale: Scale, text: &str) -> f32 {
+    font.layout(text, scale, point(0.0, 0.0))
+        .last()
+        .map(|glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.0)
+}
+
+/// Break `text` into lines no wider than `width`. Words longer than a line
+/// are broken at the character that overflows.
+fn wrap_text(font: &Font, scale: Scale, text: &str, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_owned(),
+                false => format!("{} {}", line, word),
+            };
+
+            if text_width(font, scale, &candidate) <= width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(line);
+            }
+
+            // Break the word if it doesn't fit on a line on its own.
+            line = String::new();
+            for c in word.chars() {
+                line.push(c);
+                if text_width(font, scale, &line) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(line);
+                    line = c.to_string();
+                }
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Render `text` centered onto an image of the size `width`x`height`. The
+/// text is wrapped at word boundaries and lines that don't fit are cut off,
+/// replacing the end of the last visible line with "...".
+pub fn render_text(width: u32, height: u32, text: &str, fg: Color, bg: Color) -> DynamicImage {
+    let font = font();
+    let scale = Scale::uniform(FONT_SIZE);
+    let max_width = (width - 2 * PADDING) as f32;
+
+    let mut lines = wrap_text(font, scale, text, max_width);
+
+    let max_lines = ((height - 2 * PADDING) as f32 / FONT_SIZE) as usize;
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+
+        if let Some(line) = lines.last_mut() {
+            line.push_str("...");
+            while text_width(font, scale, line) > max_width && line.len() > 3 {
+                line.truncate(line.len() - 3);
+                line.pop();
+                line.push_str("...");
+            }
+        }
+    }
+
+    let mut image = RgbImage::from_pixel(width, height, Rgb([bg.r, bg.g, bg.b]));
+    let fg = Rgb([fg.r, fg.g, fg.b]);
+
+    let top = (height as f32 - lines.len() as f32 * FONT_SIZE) / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        let x = (width as f32 - text_width(font, scale, line)) / 2.0;
+        let y = top + i as f32 * FONT_SIZE;
+
+        draw_text_mut(&mut image, fg, x as u32, y as u32, scale, font, line);
+    }
+
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Draw a single line of `text` horizontally centered onto `image` with the
+/// top of the line at `y`. Unlike [`render_text`] the text is neither wrapped
+/// nor cut off.
+pub fn draw_line(image: &mut RgbImage, text: &str, y: u32, fg: Color) {
+    let font = font();
+    let scale = Scale::uniform(FONT_SIZE);
+
+    let x = (image.width() as f32 - text_width(font, scale, text)).max(0.0) / 2.0;
+
+    draw_text_mut(
+        image,
+        Rgb([fg.r, fg.g, fg.b]),
+        x as u32,
+        y,
+        scale,
+        font,
+        text,
+    );
+}
+
+/// Render the single character `ch` as large as possible centered onto an
+/// image of the size `width`x`height`. Glyphs wider than tall are scaled to
+/// fit the width.
+///
+/// Only the symbols of the bundled DejaVu Sans font are available, e.g.
+/// arrows, `☀`, `⚡`, `♫` and `✓`. It has no color emoji and covers few
+/// emoji at all, characters outside of it return [`MissingGlyph`].
+pub fn render_glyph(
+    width: u32,
+    height: u32,
+    ch: char,
+    fg: Color,
+    bg: Color,
+) -> Result<DynamicImage, MissingGlyph> {
+    let glyph = font().glyph(ch);
+    if glyph.id().0 == 0 {
+        return Err(MissingGlyph(ch));
+    }
+
+    let mut image = RgbImage::from_pixel(width, height, Rgb([bg.r, bg.g, bg.b]));
+
+    // Measure the glyph at a reference size and scale it to fit.
+    let reference = glyph
+        .clone()
+        .scaled(Scale::uniform(GLYPH_REFERENCE_SIZE))
+        .positioned(point(0.0, 0.0));
+
+    // Whitespace has no bounding box.
+    let bounds = match reference.pixel_bounding_box() {
+        Some(bounds) => bounds,
+        None => return Ok(DynamicImage::ImageRgb8(image)),
+    };
+
+    let max_width = (width - 2 * PADDING) as f32;
+    let max_height = (height - 2 * PADDING) as f32;
+    let factor = f32::min(
+        max_width / bounds.width() as f32,
+        max_height / bounds.height() as f32,
+    );
+
+    let scaled = glyph
+        .scaled(Scale::uniform(GLYPH_REFERENCE_SIZE * factor))
+        .positioned(point(0.0, 0.0));
+    let bounds = scaled.pixel_bounding_box().unwrap_or(bounds);
+
+    // Move the bounding box into the center of the image.
+    let x = (width as i32 - bounds.width()) / 2 - bounds.min.x;
+    let y = (height as i32 - bounds.height()) / 2 - bounds.min.y;
+    let glyph = scaled
+        .into_unpositioned()
+        .positioned(point(x as f32, y as f32));
+
+    let bounds = match glyph.pixel_bounding_box() {
+        Some(bounds) => bounds,
+        None => return Ok(DynamicImage::ImageRgb8(image)),
+    };
+
+    glyph.draw(|gx, gy, coverage| {
+        let x = bounds.min.x + gx as i32;
+        let y = bounds.min.y + gy as i32;
+        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+            return;
+        }
+
+        let blend = |fg: u8, bg: u8| (fg as f32 * coverage + bg as f32 * (1.0 - coverage)) as u8;
+        image.put_pixel(
+            x as u32,
+            y as u32,
+            Rgb([blend(fg.r, bg.r), blend(fg.g, bg.g), blend(fg.b, bg.b)]),
+        );
+    });
+
+    Ok(DynamicImage::ImageRgb8(image))
+}
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 /// A `HashMap`-like key-value store that uses the type of a value
 /// as a key.
 #[derive(Default)]
-pub struct TypeMap(HashMap<TypeId, Box<(dyn Any + Send + Sync)>>);
+pub struct TypeMap(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
 
 impl TypeMap {
     /// Creates a new empty TypeMap.
@@ -24,7 +24,6 @@ impl TypeMap {
 
     /// Returns a mutable reference to the value of the type
     /// `T`.
-    #[allow(dead_code)]
     pub fn get_mut<T>(&mut self) -> Option<&mut T>
     where
         T: Any,
@@ -45,7 +44,6 @@ impl TypeMap {
 
     /// Removes and returns the value of the type `T` from the
     /// TypeMap.
-    #[allow(dead_code)]
     pub fn remove<T>(&mut self) -> Option<T>
     where
         T: Any,
@@ -64,3 +62,57 @@ impl TypeMap {
         self.0.contains_key(&TypeId::of::<T>())
     }
 }
+
+/// A `HashMap`-like key-value store that uses a key index together with the
+/// type of a value as a key. Used to store per-button state.
+#[derive(Default)]
+pub struct KeyedTypeMap(HashMap<(u8, TypeId), Box<dyn Any + Send + Sync>>);
+
+impl KeyedTypeMap {
+    /// Creates a new empty KeyedTypeMap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a reference to the value of the type `T` stored for
+    /// `key`.
+    pub fn get<T>(&self, key: u8) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.0
+            .get(&(key, TypeId::of::<T>()))
+            .and_then(|b| b.downcast_ref::<T>())
+    }
+
+    /// Returns a mutable reference to the value of the type `T`
+    /// stored for `key`.
+    #[allow(dead_code)]
+    pub fn get_mut<T>(&mut self, key: u8) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        self.0
+            .get_mut(&(key, TypeId::of::<T>()))
+            .and_then(|b| b.downcast_mut::<T>())
+    }
+
+    /// Inserts a value of the type `T` for `key`.
+    pub fn insert<T>(&mut self, key: u8, value: T)
+    where
+        T: Any + Send + Sync,
+    {
+        self.0.insert((key, TypeId::of::<T>()), Box::new(value));
+    }
+
+    /// Removes and returns the value of the type `T` stored for
+    /// `key`.
+    pub fn remove<T>(&mut self, key: u8) -> Option<T>
+    where
+        T: Any,
+    {
+        self.0
+            .remove(&(key, TypeId::of::<T>()))
+            .map(|b| *b.downcast::<T>().unwrap())
+    }
+}
@@ -0,0 +1,308 @@
+//! Wrappers around the `wpctl` cli of WirePlumber, the session manager
+//! of PipeWire.
+
+use crate::pactl::{Sink, Source};
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::process::Command;
+use std::result;
+use std::sync::Mutex;
+
+/// The default sink as understood by `wpctl`.
+pub const DEFAULT_SINK: &str = "@DEFAULT_AUDIO_SINK@";
+
+/// The default source as understood by `wpctl`.
+pub const DEFAULT_SOURCE: &str = "@DEFAULT_AUDIO_SOURCE@";
+
+/// The maximum number of names cached by [`NodeNames`].
+const MAX_NODE_NAMES: usize = 256;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The output of `wpctl` could not be parsed. Contains the reason.
+    InvalidOutput(String),
+    /// `wpctl` failed to run or exited with an error. Contains the error
+    /// message.
+    CommandFailed(String),
+    /// There is no sink with the name.
+    SinkNotFound(String),
+}
+
+type Result<T> = result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidOutput(msg) => write!(f, "invalid wpctl output: {}", msg),
+            Self::CommandFailed(msg) => write!(f, "wpctl failed: {}", msg),
+            Self::SinkNotFound(name) => write!(f, "no sink named {}", name),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+fn new_wpctl() -> Command {
+    Command::new("wpctl")
+}
+
+/// Run `wpctl` with the given arguments and return its stdout.
+fn run(args: &[&str]) -> Result<String> {
+    let output = new_wpctl()
+        .args(args)
+        .output()
+        .map_err(|err| Error::CommandFailed(err.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::CommandFailed(stderr.trim().to_owned()));
+    }
+
+    String::from_utf8(output.stdout).map_err(|err| Error::InvalidOutput(err.to_string()))
+}
+
+/// Returns `true` if the `wpctl` binary is available.
+pub fn is_available() -> bool {
+    new_wpctl().arg("--version").output().is_ok()
+}
+
+/// A single node listed in the output of `wpctl status`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub id: u32,
+    pub description: String,
+    pub volume: f32,
+    pub mute: bool,
+}
+
+/// Parse the nodes listed in the `section` (e.g. "Sinks") of the "Audio"
+/// part of `wpctl status`.
+pub fn parse_status(status: &str, section: &str) -> Vec<Node> {
+    let header = format!("{}:", section);
+
+    let lines = status
+        .lines()
+        .skip_while(|line| line.trim() != "Audio")
+        .skip_while(|line| trim_tree(line) != header)
+        .skip(1);
+
+    let mut nodes = Vec::new();
+    for line in lines {
+        let line = trim_tree(line);
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(node) = parse_node(line) {
+            nodes.push(node);
+        }
+    }
+
+    nodes
+}
+
+/// Strip the tree drawing characters and the default marker from a line of
+/// `wpctl status`.
+fn trim_tree(line: &str) -> &str {
+    line.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '│' | '├' | '└' | '─' | '*'))
+        .trim_end()
+}
+
+/// Parse a node line in the form "{id}. {description} [vol: {volume}( MUTED)]".
+fn parse_node(line: &str) -> Option<Node> {
+    let (id, rest) = line.split_once(". ")?;
+    let id = id.parse().ok()?;
+
+    let (description, props) = rest.split_once('[')?;
+    let props = props.strip_suffix(']')?.strip_prefix("vol: ")?;
+
+    let mut props = props.split(' ');
+    let volume = props.next()?.parse().ok()?;
+    let mute = props.any(|prop| prop == "MUTED");
+
+    Some(Node {
+        id,
+        description: description.trim().to_owned(),
+        volume,
+        mute,
+    })
+}
+
+/// Returns the `node.name` property of the node `id`.
+pub fn node_name(id: &str) -> Result<String> {
+    let output = run(&["inspect", id])?;
+
+    output
+        .lines()
+        .map(|line| line.trim_start_matches(|c: char| c.is_whitespace() || c == '*'))
+        .find_map(|line| line.strip_prefix("node.name = "))
+        .map(|name| name.trim_matches('"').to_owned())
+        .ok_or_else(|| Error::InvalidOutput(format!("node {} has no node.name", id)))
+}
+
+/// Returns the audio sinks and sources listed by a single `wpctl status`.
+pub fn status() -> Result<(Vec<Node>, Vec<Node>)> {
+    let status = run(&["status"])?;
+    Ok((
+        parse_status(&status, "Sinks"),
+        parse_status(&status, "Sources"),
+    ))
+}
+
+/// The `node.name`s of nodes by id. Reading the name takes a `wpctl inspect`
+/// call per node, so names are only read for nodes not seen before.
+#[derive(Debug, Default)]
+pub struct NodeNames(Mutex<HashMap<u32, String>>);
+
+impl NodeNames {
+    /// Returns the names of `nodes`. The names of nodes missing from `nodes`
+    /// are dropped, PipeWire reuses the ids of removed nodes.
+    fn get(&self, nodes: &[&Node]) -> Result<Vec<String>> {
+        let mut names = self.0.lock().unwrap();
+
+        let ids: HashSet<u32> = nodes.iter().map(|node| node.id).collect();
+        names.retain(|id, _| ids.contains(id));
+        if names.len() >= MAX_NODE_NAMES {
+            names.clear();
+        }
+
+        let mut res = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let name = match names.get(&node.id) {
+                Some(name) => name.clone(),
+                None => {
+                    let name = node_name(&node.id.to_string())?;
+                    names.insert(node.id, name.clone());
+                    name
+                }
+            };
+
+            res.push(name);
+        }
+
+        Ok(res)
+    }
+}
+
+/// Returns all audio sinks and sources, reading their names from `names`.
+pub fn list(names: &NodeNames) -> Result<(Vec<Sink>, Vec<Source>)> {
+    let (sinks, sources) = status()?;
+
+    let nodes: Vec<&Node> = sinks.iter().chain(&sources).collect();
+    let mut node_names = names.get(&nodes)?.into_iter();
+
+    let sinks = sinks
+        .into_iter()
+        .zip(&mut node_names)
+        .map(|(node, name)| Sink {
+            id: node.id,
+            name,
+            description: node.description,
+            mute: node.mute,
+            volume: format_volume(node.volume),
+            ..Default::default()
+        })
+        .collect();
+
+    let sources = sources
+        .into_iter()
+        .zip(node_names)
+        .map(|(node, name)| Source {
+            id: node.id,
+            name,
+            description: node.description,
+            mute: node.mute,
+            volume: format_volume(node.volume),
+            ..Default::default()
+        })
+        .collect();
+
+    Ok((sinks, sources))
+}
+
+fn format_volume(volume: f32) -> String {
+    format!("{}%", (volume * 100.0).round())
+}
+
+/// Change the mute state of a node. `action` is one of "1", "0" or "toggle".
+pub fn set_mute(id: &str, action: &str) -> Result<()> {
+    run(&["set-mute", id, action])?;
+    Ok(())
+}
+
+/// Change the volume of a node. A `volume` of `1.0` is 100%.
+pub fn set_volume(id: &str, volume: f32) -> Result<()> {
+    run(&["set-volume", id, &volume.to_string()])?;
+    Ok(())
+}
+
+/// Make the node `id` the default sink or source.
+pub fn set_default(id: &str) -> Result<()> {
+    run(&["set-default", id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_status, Node};
+
+    const STATUS: &str = "PipeWire 'pipewire-0' [0.3.48, user@host, cookie:1234]
+ └─ Clients:
+        33. WirePlumber                         [0.3.48, user@host, pid:1234]
+
+Audio
+ ├─ Devices:
+ │      42. Built-in Audio                      [alsa]
+ │
+ ├─ Sinks:
+ │  *   48. Built-in Audio Analog Stereo        [vol: 0.40]
+ │      50. HDMI Output                         [vol: 1.00 MUTED]
+ │
+ ├─ Sink endpoints:
+ │
+ ├─ Sources:
+ │  *   49. Built-in Audio Analog Stereo        [vol: 0.85 MUTED]
+ │
+ ├─ Source endpoints:
+ │
+ └─ Streams:
+
+Video
+ ├─ Devices:
+ │
+ ├─ Sinks:
+ │      60. Camera                              [vol: 1.00]
+";
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            parse_status(STATUS, "Sinks"),
+            vec![
+                Node {
+                    id: 48,
+                    description: "Built-in Audio Analog Stereo".into(),
+                    volume: 0.40,
+                    mute: false,
+                },
+                Node {
+                    id: 50,
+                    description: "HDMI Output".into(),
+                    volume: 1.00,
+                    mute: true,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_status(STATUS, "Sources"),
+            vec![Node {
+                id: 49,
+                description: "Built-in Audio Analog Stereo".into(),
+                volume: 0.85,
+                mute: true,
+            }]
+        );
+        assert_eq!(parse_status(STATUS, "Filters"), vec![]);
+    }
+}